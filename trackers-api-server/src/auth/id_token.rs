@@ -0,0 +1,49 @@
+//! ID tokens of OpenID Connect which tell the clients who the user is.
+//!
+//! They are signed with the same keys as the access tokens but are meant for
+//! the client alone and are not accepted by the API.
+
+use super::keys::keys;
+
+/// How long the client should accept the ID token.
+const ID_TOKEN_LIFETIME_SECONDS: u64 = 30 * 60;
+
+#[derive(Debug, serde::Serialize)]
+struct IdTokenClaims {
+    iss: aliri::jwt::Issuer,
+    sub: aliri::jwt::Subject,
+    aud: aliri::jwt::Audiences,
+    exp: aliri_clock::UnixTime,
+    iat: aliri_clock::UnixTime,
+    email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+}
+
+/// Issues an ID token for the client identified with the `audience`, or for
+/// the API itself when the session has been started without a client.
+pub fn issue(
+    user_id: &trackers_models::types::Uuid,
+    email: &trackers_models::types::Email,
+    audience: Option<&str>,
+    nonce: Option<&str>,
+) -> aliri::Jwt {
+    let now = std::time::SystemTime::now();
+    let claims = IdTokenClaims {
+        iss: aliri::jwt::Issuer::new(super::layer::issuer()),
+        sub: aliri::jwt::Subject::new(user_id.to_string()),
+        aud: aliri::jwt::Audience::new(
+            audience.map_or_else(super::layer::audience, ToOwned::to_owned),
+        )
+        .into(),
+        exp: aliri_clock::UnixTime::from(
+            now.checked_add(std::time::Duration::from_secs(ID_TOKEN_LIFETIME_SECONDS))
+                .unwrap(),
+        ),
+        iat: aliri_clock::UnixTime::from(now),
+        email: email.to_string(),
+        nonce: nonce.map(ToOwned::to_owned),
+    };
+
+    keys().sign(&claims)
+}
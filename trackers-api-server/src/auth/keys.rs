@@ -0,0 +1,431 @@
+//! Keys used to sign and verify the issued JWTs.
+//!
+//! By default the tokens are signed with HS256 using the `JWT_SHARED_SECRET`
+//! and `JWT_KEY_ID` variables. When `JWT_KEYS_DIR` is set the keys are loaded
+//! from PEM files in that directory instead:
+//! - `<kid>.pub.pem` - a public key, tokens with the `kid` in their header are
+//!   verified with it,
+//! - `<kid>.pem` - the matching private key, required only for the key
+//!   selected with `JWT_SIGNING_KEY_ID`.
+//!
+//! The algorithm of the keys is selected with `JWT_ALGORITHM`, either `RS256`
+//! (the default), `ES256` or `EdDSA` with Ed25519 keys. aliri, which verifies
+//! the tokens in the JWT layer, does not support EdDSA. The tokens signed with
+//! it are handled with `jsonwebtoken` instead and exchanged for tokens signed
+//! with a secret of the running server before they reach the JWT layer, see
+//! [`accept_ed25519_tokens`](super::layer::accept_ed25519_tokens).
+//!
+//! To rotate the keys add a new key pair to the directory, make it the signing
+//! key and keep the public key of the previous one until the tokens signed
+//! with it expire.
+
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+use base64::Engine;
+
+use crate::env_var;
+
+pub struct Keys {
+    algorithm: aliri::jwa::Algorithm,
+    signing_key_id: aliri::jwk::KeyId,
+    signing_key: aliri::Jwk,
+    verifying_keys: aliri::Jwks,
+    public_keys: serde_json::Value,
+    ed25519: Option<Ed25519Keys>,
+}
+
+impl Keys {
+    /// The algorithm of the tokens verified by the JWT layer.
+    pub fn algorithm(&self) -> aliri::jwa::Algorithm {
+        self.algorithm
+    }
+    /// The algorithm of the tokens handed out, which differs from the one of
+    /// [`algorithm`](Self::algorithm) only with EdDSA.
+    pub fn published_algorithm(&self) -> String {
+        match self.ed25519 {
+            Some(_) => String::from("EdDSA"),
+            None => self.algorithm.to_string(),
+        }
+    }
+    /// All the keys accepted by the JWT layer.
+    pub fn verifying_keys(&self) -> &aliri::Jwks {
+        &self.verifying_keys
+    }
+    /// The keys which can be shared with anyone who wants to verify the
+    /// tokens, as a JWK set. Empty when a shared secret is used.
+    pub fn public_keys(&self) -> &serde_json::Value {
+        &self.public_keys
+    }
+    /// The Ed25519 keys, present when the tokens are signed with EdDSA.
+    pub fn ed25519(&self) -> Option<&Ed25519Keys> {
+        self.ed25519.as_ref()
+    }
+    /// Signs a token handed out by the server.
+    pub fn sign(&self, claims: &impl serde::Serialize) -> aliri::Jwt {
+        match &self.ed25519 {
+            Some(ed25519) => aliri::Jwt::new(ed25519.sign(claims)),
+            None => self.sign_for_jwt_layer(claims),
+        }
+    }
+    /// Signs a token accepted by the JWT layer. With EdDSA such tokens never
+    /// leave the server.
+    pub fn sign_for_jwt_layer(&self, claims: &impl serde::Serialize) -> aliri::Jwt {
+        let headers =
+            aliri::jwt::BasicHeaders::with_key_id(self.algorithm, self.signing_key_id.clone());
+        aliri::Jwt::try_from_parts_with_signature(&headers, claims, &self.signing_key).unwrap()
+    }
+}
+
+pub fn keys() -> &'static Keys {
+    static KEYS: once_cell::sync::OnceCell<Keys> = once_cell::sync::OnceCell::new();
+    KEYS.get_or_init(|| match env_var("JWT_KEYS_DIR") {
+        Some(dir) => load_key_files(Path::new(&dir)),
+        None => shared_secret(),
+    })
+}
+
+fn shared_secret() -> Keys {
+    let secret = env_var("JWT_SHARED_SECRET")
+        .expect("JWT_SHARED_SECRET environment variable must be set if JWT_KEYS_DIR is not");
+    let key_id = aliri::jwk::KeyId::from_str(
+        &env_var("JWT_KEY_ID")
+            .expect("JWT_KEY_ID environment variable must be set if JWT_KEYS_DIR is not"),
+    )
+    .unwrap();
+
+    let key = aliri::Jwk::from(aliri::jwa::Hmac::new(secret.into_bytes()))
+        .with_algorithm(aliri::jwa::Algorithm::HS256)
+        .with_key_id(key_id.clone());
+
+    let mut verifying_keys = aliri::Jwks::default();
+    verifying_keys.add_key(key.clone());
+
+    Keys {
+        algorithm: aliri::jwa::Algorithm::HS256,
+        signing_key_id: key_id,
+        signing_key: key,
+        verifying_keys,
+        public_keys: serde_json::to_value(aliri::Jwks::default()).unwrap(),
+        ed25519: None,
+    }
+}
+
+fn load_key_files(dir: &Path) -> Keys {
+    let algorithm = match env_var("JWT_ALGORITHM").as_deref() {
+        None | Some("RS256") => aliri::jwa::Algorithm::RS256,
+        Some("ES256") => aliri::jwa::Algorithm::ES256,
+        Some("EdDSA") => return load_ed25519_key_files(dir),
+        Some(other) => {
+            panic!("unsupported JWT_ALGORITHM {other}, expected RS256, ES256 or EdDSA")
+        }
+    };
+    let signing_key_id = aliri::jwk::KeyId::from_str(&signing_key_id()).unwrap();
+
+    let mut public_keys = aliri::Jwks::default();
+    let mut verifying_keys = aliri::Jwks::default();
+    let mut has_signing_public_key = false;
+    for (key_id, path) in public_key_files(dir) {
+        let pem = read_pem(&path);
+        let key = match algorithm {
+            aliri::jwa::Algorithm::RS256 => aliri::jwa::Rsa::public_key_from_pem(&pem)
+                .map(aliri::Jwk::from)
+                .map_err(|err| err.to_string()),
+            _ => ec_public_key_from_pem(&pem).map(aliri::Jwk::from),
+        }
+        .unwrap_or_else(|err| panic!("failed to parse {}: {err}", path.display()));
+        let key_id = aliri::jwk::KeyId::from_str(&key_id).unwrap();
+        has_signing_public_key |= key_id == signing_key_id;
+        let key = key.with_algorithm(algorithm).with_key_id(key_id);
+        verifying_keys.add_key(key.clone());
+        public_keys.add_key(key);
+    }
+
+    let signing_key_path = dir.join(format!("{signing_key_id}.pem"));
+    let pem = read_pem(&signing_key_path);
+    let signing_key = match algorithm {
+        aliri::jwa::Algorithm::RS256 => aliri::jwa::Rsa::private_key_from_pem(&pem)
+            .map(aliri::Jwk::from)
+            .map_err(|err| err.to_string()),
+        _ => aliri::jwa::ec::PrivateKey::from_pem(&pem)
+            .map(aliri::jwa::EllipticCurve::from)
+            .map(aliri::Jwk::from)
+            .map_err(|err| err.to_string()),
+    }
+    .unwrap_or_else(|err| panic!("failed to parse {}: {err}", signing_key_path.display()))
+    .with_algorithm(algorithm)
+    .with_key_id(signing_key_id.clone());
+
+    if !has_signing_public_key {
+        panic!("the public key of the signing key {signing_key_id} must be in JWT_KEYS_DIR");
+    }
+
+    Keys {
+        algorithm,
+        signing_key_id,
+        signing_key,
+        verifying_keys,
+        public_keys: serde_json::to_value(public_keys).unwrap(),
+        ed25519: None,
+    }
+}
+
+fn load_ed25519_key_files(dir: &Path) -> Keys {
+    let signing_key_id = signing_key_id();
+
+    let signing_key_path = dir.join(format!("{signing_key_id}.pem"));
+    let signing_key =
+        jsonwebtoken::EncodingKey::from_ed_pem(read_pem(&signing_key_path).as_bytes())
+            .unwrap_or_else(|err| {
+                panic!("failed to parse {}: {err}", signing_key_path.display())
+            });
+    let mut ed25519 = Ed25519Keys::new(
+        signing_key_id.clone(),
+        signing_key,
+        &super::layer::issuer(),
+        &super::layer::audience(),
+    );
+
+    let mut public_keys = Vec::new();
+    for (key_id, path) in public_key_files(dir) {
+        let public_key = openssl::pkey::PKey::public_key_from_pem(read_pem(&path).as_bytes())
+            .and_then(|key| key.raw_public_key())
+            .unwrap_or_else(|err| panic!("failed to parse {}: {err}", path.display()));
+        public_keys.push(ed25519.add_verifying_key(key_id, &public_key));
+    }
+    if !ed25519.verifying_keys.contains_key(&signing_key_id) {
+        panic!("the public key of the signing key {signing_key_id} must be in JWT_KEYS_DIR");
+    }
+
+    // Signs the tokens exchanged for the EdDSA ones, is never shared
+    let internal_key_id = aliri::jwk::KeyId::from_static("internal");
+    let internal_key = aliri::Jwk::from(
+        aliri::jwa::Hmac::generate(aliri::jwa::hmac::SigningAlgorithm::HS256)
+            .expect("failed to generate the internal signing key"),
+    )
+    .with_algorithm(aliri::jwa::Algorithm::HS256)
+    .with_key_id(internal_key_id.clone());
+    let mut verifying_keys = aliri::Jwks::default();
+    verifying_keys.add_key(internal_key.clone());
+
+    Keys {
+        algorithm: aliri::jwa::Algorithm::HS256,
+        signing_key_id: internal_key_id,
+        signing_key: internal_key,
+        verifying_keys,
+        public_keys: serde_json::json!({ "keys": public_keys }),
+        ed25519: Some(ed25519),
+    }
+}
+
+fn signing_key_id() -> String {
+    env_var("JWT_SIGNING_KEY_ID")
+        .expect("JWT_SIGNING_KEY_ID environment variable must be set with JWT_KEYS_DIR")
+}
+
+/// The `<kid>.pub.pem` files of the directory along with their key IDs.
+fn public_key_files(dir: &Path) -> Vec<(String, std::path::PathBuf)> {
+    let entries = std::fs::read_dir(dir).expect("failed to read the JWT_KEYS_DIR directory");
+    entries
+        .filter_map(|entry| {
+            let path = entry
+                .expect("failed to read the JWT_KEYS_DIR directory")
+                .path();
+            let key_id = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".pub.pem"))?
+                .to_owned();
+            Some((key_id, path))
+        })
+        .collect()
+}
+
+fn read_pem(path: &Path) -> String {
+    std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("failed to read {}: {err}", path.display()))
+}
+
+/// aliri reads only the private EC keys from PEM files, the public ones are
+/// read with OpenSSL and passed to it as points.
+fn ec_public_key_from_pem(pem: &str) -> Result<aliri::jwa::EllipticCurve, String> {
+    let key = openssl::ec::EcKey::public_key_from_pem(pem.as_bytes()).map_err(|err| err.to_string())?;
+    if key.group().curve_name() != Some(openssl::nid::Nid::X9_62_PRIME256V1) {
+        return Err(String::from("ES256 requires a key on the P-256 curve"));
+    }
+    let mut ctx = openssl::bn::BigNumContext::new().map_err(|err| err.to_string())?;
+    let point = key
+        .public_key()
+        .to_bytes(
+            key.group(),
+            openssl::ec::PointConversionForm::UNCOMPRESSED,
+            &mut ctx,
+        )
+        .map_err(|err| err.to_string())?;
+    Ok(aliri::jwa::EllipticCurve::from(
+        aliri::jwa::ec::PublicKey::from_public_point(aliri::jwa::ec::Curve::P256, point),
+    ))
+}
+
+/// The keys of the tokens signed with EdDSA.
+pub struct Ed25519Keys {
+    signing_key_id: String,
+    signing_key: jsonwebtoken::EncodingKey,
+    verifying_keys: HashMap<String, jsonwebtoken::DecodingKey>,
+    validation: jsonwebtoken::Validation,
+}
+
+impl Ed25519Keys {
+    fn new(
+        signing_key_id: String,
+        signing_key: jsonwebtoken::EncodingKey,
+        issuer: &str,
+        audience: &str,
+    ) -> Self {
+        let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::EdDSA);
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(&[audience]);
+        validation.leeway = 0;
+        Self {
+            signing_key_id,
+            signing_key,
+            verifying_keys: HashMap::new(),
+            validation,
+        }
+    }
+
+    /// Accepts the tokens signed with the matching private key and returns
+    /// the public key as a JWK. The tokens are verified with the published
+    /// JWK so that other services verify them the same way.
+    fn add_verifying_key(&mut self, key_id: String, public_key: &[u8]) -> jsonwebtoken::jwk::Jwk {
+        let jwk = jsonwebtoken::jwk::Jwk {
+            common: jsonwebtoken::jwk::CommonParameters {
+                public_key_use: Some(jsonwebtoken::jwk::PublicKeyUse::Signature),
+                algorithm: Some(jsonwebtoken::Algorithm::EdDSA),
+                key_id: Some(key_id.clone()),
+                ..Default::default()
+            },
+            algorithm: jsonwebtoken::jwk::AlgorithmParameters::OctetKeyPair(
+                jsonwebtoken::jwk::OctetKeyPairParameters {
+                    key_type: jsonwebtoken::jwk::OctetKeyPairType::OctetKeyPair,
+                    curve: jsonwebtoken::jwk::EllipticCurve::Ed25519,
+                    x: base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(public_key),
+                },
+            ),
+        };
+        let key = jsonwebtoken::DecodingKey::from_jwk(&jwk).unwrap();
+        self.verifying_keys.insert(key_id, key);
+        jwk
+    }
+
+    pub fn sign(&self, claims: &impl serde::Serialize) -> String {
+        let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::EdDSA);
+        header.kid = Some(self.signing_key_id.clone());
+        jsonwebtoken::encode(&header, claims, &self.signing_key).unwrap()
+    }
+
+    /// Verifies the signature, the expiry, the issuer and the audience of the
+    /// token.
+    pub fn verify(&self, token: &str) -> Option<super::UserClaims> {
+        let key_id = jsonwebtoken::decode_header(token).ok()?.kid?;
+        let key = self.verifying_keys.get(&key_id)?;
+        jsonwebtoken::decode(token, key, &self.validation)
+            .ok()
+            .map(|data| data.claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ISSUER: &str = "https://tracke.rs";
+    const AUDIENCE: &str = "tracke.rs";
+
+    /// Generates a key pair and accepts the tokens signed with it.
+    fn test_keys(key_id: &str) -> (Ed25519Keys, jsonwebtoken::jwk::Jwk) {
+        let key = openssl::pkey::PKey::generate_ed25519().unwrap();
+        let mut keys = Ed25519Keys::new(
+            key_id.to_owned(),
+            jsonwebtoken::EncodingKey::from_ed_pem(&key.private_key_to_pem_pkcs8().unwrap())
+                .unwrap(),
+            ISSUER,
+            AUDIENCE,
+        );
+        let jwk = keys.add_verifying_key(key_id.to_owned(), &key.raw_public_key().unwrap());
+        (keys, jwk)
+    }
+
+    fn claims(lifetime: std::time::Duration, expired: bool) -> super::super::UserClaims {
+        let now = std::time::SystemTime::now();
+        super::super::UserClaims {
+            exp: aliri_clock::UnixTime::from(if expired {
+                now.checked_sub(lifetime).unwrap()
+            } else {
+                now.checked_add(lifetime).unwrap()
+            }),
+            iss: aliri::jwt::Issuer::new(ISSUER.to_owned()),
+            aud: aliri::jwt::Audience::new(AUDIENCE.to_owned()).into(),
+            jti: uuid::Uuid::now_v7(),
+            scope: aliri_oauth2::Scope::default(),
+            sub: Some(aliri::jwt::Subject::new(
+                "00000000-0000-0000-0000-000000000000".to_owned(),
+            )),
+            client: None,
+        }
+    }
+
+    const HOUR: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+    #[test]
+    fn signed_tokens_verify_against_published_jwk() {
+        let (keys, jwk) = test_keys("test");
+        let claims = claims(HOUR, false);
+        let token = keys.sign(&claims);
+
+        let verified = keys.verify(&token).expect("the token should be valid");
+        assert_eq!(verified.jti(), claims.jti());
+
+        // the way any other service verifies the token
+        let published: jsonwebtoken::jwk::Jwk =
+            serde_json::from_value(serde_json::to_value(&jwk).unwrap()).unwrap();
+        assert_eq!(published.common.key_id.as_deref(), Some("test"));
+        let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::EdDSA);
+        validation.set_audience(&[AUDIENCE]);
+        assert!(jsonwebtoken::decode::<serde_json::Value>(
+            &token,
+            &jsonwebtoken::DecodingKey::from_jwk(&published).unwrap(),
+            &validation,
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn tokens_of_other_keys_are_rejected() {
+        let (keys, _) = test_keys("test");
+        let (other_keys, _) = test_keys("test");
+        let token = other_keys.sign(&claims(HOUR, false));
+        assert!(keys.verify(&token).is_none());
+
+        let (unknown_keys, _) = test_keys("unknown");
+        let token = unknown_keys.sign(&claims(HOUR, false));
+        assert!(keys.verify(&token).is_none());
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let (keys, _) = test_keys("test");
+        let token = keys.sign(&claims(HOUR, false));
+        let mut parts: Vec<&str> = token.split('.').collect();
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(&claims(HOUR * 24, false)).unwrap());
+        parts[1] = &payload;
+        assert!(keys.verify(&parts.join(".")).is_none());
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let (keys, _) = test_keys("test");
+        let token = keys.sign(&claims(HOUR, true));
+        assert!(keys.verify(&token).is_none());
+    }
+}
@@ -5,8 +5,6 @@ use axum::response::IntoResponse;
 
 const ISSUER: OnceLock<String> = OnceLock::new();
 const AUDIENCE: OnceLock<String> = OnceLock::new();
-const KEY_ID: OnceLock<String> = OnceLock::new();
-const SHARED_SECRET: OnceLock<String> = OnceLock::new();
 
 pub async fn require_jwt<B: axum::body::HttpBody>(
     request: axum::http::Request<B>,
@@ -23,77 +21,184 @@ pub async fn require_jwt<B: axum::body::HttpBody>(
     response
 }
 
+/// Rejects the requests authorized with a token that has been revoked. The
+/// revoked tokens are read from the state rather than from an extension so
+/// that the check cannot be skipped. The tokens which cannot be verified are
+/// left for the JWT layer to reject.
+pub async fn reject_revoked_tokens<B: Send>(
+    axum::extract::State(state): axum::extract::State<crate::AppState>,
+    request: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> axum::response::Response {
+    let revoked = request
+        .headers()
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token)
+        .and_then(verify_token)
+        .map_or(false, |claims| state.revoked_tokens.contains(&claims.jti()));
+
+    if revoked {
+        return crate::error::UnathorizedError::default()
+            .with_msg("the token has been revoked")
+            .with_docs()
+            .with_links([("new session", "/api/session/token".into())])
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Lets the personal access tokens be used in place of the JWTs by replacing
+/// them with a short-lived access token holding the same scopes before the
+/// request reaches the JWT layer.
+pub async fn accept_personal_access_tokens<B: Send>(
+    axum::extract::State(state): axum::extract::State<crate::AppState>,
+    mut request: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> axum::response::Response {
+    let Some(token) = request
+        .headers()
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token)
+        .filter(|token| token.starts_with(crate::services::personal_access_token::TOKEN_PREFIX))
+        .map(str::to_owned)
+    else {
+        return next.run(request).await;
+    };
+
+    let personal_token = match state.db.get().await {
+        Ok(mut db_conn) => {
+            crate::services::personal_access_token::use_token(&mut db_conn, &token).await
+        }
+        Err(err) => Err(err.into()),
+    };
+    let personal_token = match personal_token {
+        Ok(Some(personal_token)) => personal_token,
+        Ok(None) => {
+            return crate::error::UnathorizedError::default()
+                .with_msg("the personal access token is invalid or has expired")
+                .with_docs()
+                .with_links([("new session", "/api/session/token".into())])
+                .into_response()
+        }
+        Err(err) => return err.into_response(),
+    };
+
+    let access_token = crate::services::personal_access_token::exchange(&personal_token);
+    request.headers_mut().insert(
+        axum::http::header::AUTHORIZATION,
+        axum::http::HeaderValue::from_str(&format!("Bearer {}", access_token.take()))
+            .expect("failed to save JWT into request headers"),
+    );
+
+    next.run(request).await
+}
+
+/// Lets the JWT layer, which cannot verify the tokens signed with EdDSA,
+/// accept them. The verified token is replaced with one holding the same
+/// claims that is signed with a secret of the running server.
+pub async fn accept_ed25519_tokens<B: Send>(
+    mut request: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> axum::response::Response {
+    let keys = super::keys::keys();
+    let Some(ed25519) = keys.ed25519() else {
+        return next.run(request).await;
+    };
+    // The tokens which fail to verify are left for the JWT layer to reject
+    let Some(claims) = request
+        .headers()
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token)
+        .and_then(|token| ed25519.verify(token))
+    else {
+        return next.run(request).await;
+    };
+
+    let access_token = keys.sign_for_jwt_layer(&claims);
+    request.headers_mut().insert(
+        axum::http::header::AUTHORIZATION,
+        axum::http::HeaderValue::from_str(&format!("Bearer {}", access_token.take()))
+            .expect("failed to save JWT into request headers"),
+    );
+
+    next.run(request).await
+}
+
+fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+}
+
+/// Verifies a token passed in other way than with the `Authorization` header.
+/// The token can hold any scopes. Revocation is not checked.
+pub fn verify_token(token: &str) -> Option<UserClaims> {
+    if let Some(ed25519) = super::keys::keys().ed25519() {
+        return ed25519.verify(token);
+    }
+    authority()
+        .verify_token::<UserClaims>(
+            aliri::jwt::JwtRef::from_str(token),
+            &aliri_oauth2::ScopePolicy::allow_any(),
+        )
+        .ok()
+}
+
 pub fn validator() -> &'static aliri::jwt::CoreValidator {
     static VALID: once_cell::sync::OnceCell<aliri::jwt::CoreValidator> =
         once_cell::sync::OnceCell::new();
 
     VALID.get_or_init(|| {
         aliri::jwt::CoreValidator::default()
-            .add_approved_algorithm(aliri::jwa::Algorithm::HS256)
-            .add_allowed_audience(
-                aliri::jwt::Audience::from_str(AUDIENCE.get_or_init(|| {
-                    #[cfg(feature = "local-dev")]
-                    let audience = dotenvy::var("JWT_AUDIENCE")
-                        .expect("JWT_AUDIENCE environment variable must be set in the .env file");
-                    #[cfg(not(feature = "local-dev"))]
-                    let audience = std::env::var("JWT_AUDIENCE")
-                        .expect("JWT_AUDIENCE environment variable must be set");
-                    audience
-                }))
-                .unwrap(),
-            )
-            .require_issuer(
-                aliri::jwt::Issuer::from_str(ISSUER.get_or_init(|| {
-                    #[cfg(feature = "local-dev")]
-                    let issuer = dotenvy::var("JWT_ISSUER")
-                        .expect("JWT_ISSUER environment variable must be set in the .env file");
-                    #[cfg(not(feature = "local-dev"))]
-                    let issuer = std::env::var("JWT_ISSUER")
-                        .expect("JWT_ISSUER environment variable must be set");
-                    issuer
-                }))
-                .unwrap(),
-            )
+            .add_approved_algorithm(super::keys::keys().algorithm())
+            .add_allowed_audience(aliri::jwt::Audience::from_str(&audience()).unwrap())
+            .require_issuer(aliri::jwt::Issuer::from_str(&issuer()).unwrap())
     })
 }
 
+/// The issuer put into the tokens, taken from `JWT_ISSUER`.
+pub fn issuer() -> String {
+    ISSUER
+        .get_or_init(|| {
+            #[cfg(feature = "local-dev")]
+            let issuer = dotenvy::var("JWT_ISSUER")
+                .expect("JWT_ISSUER environment variable must be set in the .env file");
+            #[cfg(not(feature = "local-dev"))]
+            let issuer =
+                std::env::var("JWT_ISSUER").expect("JWT_ISSUER environment variable must be set");
+            issuer
+        })
+        .to_owned()
+}
+
+/// The audience of the access tokens, taken from `JWT_AUDIENCE`.
+pub fn audience() -> String {
+    AUDIENCE
+        .get_or_init(|| {
+            #[cfg(feature = "local-dev")]
+            let audience = dotenvy::var("JWT_AUDIENCE")
+                .expect("JWT_AUDIENCE environment variable must be set in the .env file");
+            #[cfg(not(feature = "local-dev"))]
+            let audience = std::env::var("JWT_AUDIENCE")
+                .expect("JWT_AUDIENCE environment variable must be set");
+            audience
+        })
+        .to_owned()
+}
+
 pub fn authority() -> &'static aliri_oauth2::Authority {
     static AUTH: once_cell::sync::OnceCell<aliri_oauth2::Authority> =
         once_cell::sync::OnceCell::new();
     AUTH.get_or_init(|| {
-        let key = aliri::Jwk::from(aliri::jwa::Hmac::new(
-            SHARED_SECRET
-                .get_or_init(|| {
-                    #[cfg(feature = "local-dev")]
-                    let secret = dotenvy::var("JWT_SHARED_SECRET").expect(
-                        "JWT_SHARED_SECRET environment variable must be set in the .env file",
-                    );
-                    #[cfg(not(feature = "local-dev"))]
-                    let secret = std::env::var("JWT_SHARED_SECRET")
-                        .expect("JWT_SHARED_SECRET environment variable must be set");
-                    secret
-                })
-                .to_owned()
-                .into_bytes(),
-        ))
-        .with_algorithm(aliri::jwa::Algorithm::HS256)
-        .with_key_id(
-            aliri::jwk::KeyId::from_str(KEY_ID.get_or_init(|| {
-                #[cfg(feature = "local-dev")]
-                let key = dotenvy::var("JWT_KEY_ID")
-                    .expect("JWT_KEY_ID environment variable must be set in the .env file");
-                #[cfg(not(feature = "local-dev"))]
-                let key = std::env::var("JWT_KEY_ID")
-                    .expect("JWT_KEY_ID environment variable must be set");
-                key
-            }))
-            .unwrap(),
-        );
-
-        let mut jwks = aliri::Jwks::default();
-        jwks.add_key(key);
-
-        aliri_oauth2::Authority::new(jwks, validator().clone())
+        aliri_oauth2::Authority::new(
+            super::keys::keys().verifying_keys().clone(),
+            validator().clone(),
+        )
     })
 }
 
@@ -163,85 +268,23 @@ pub fn authorizer() -> aliri_tower::Oauth2Authorizer<crate::auth::UserClaims, Au
 
 pub fn new_token_with_exp_and_scopes(
     lifetime_in_seconds: u64,
+    user_id: &trackers_models::types::Uuid,
     scopes: aliri_oauth2::Scope,
+    client: Option<&str>,
 ) -> aliri::Jwt {
-    let jwt_headers = aliri::jwt::BasicHeaders::with_key_id(
-        aliri::jwa::Algorithm::HS256,
-        aliri::jwk::KeyId::from_str(KEY_ID.get_or_init(|| {
-            #[cfg(feature = "local-dev")]
-            let key = dotenvy::var("JWT_KEY_ID")
-                .expect("JWT_KEY_ID environment variable must be set in the .env file");
-            #[cfg(not(feature = "local-dev"))]
-            let key =
-                std::env::var("JWT_KEY_ID").expect("JWT_KEY_ID environment variable must be set");
-            key
-        }))
-        .unwrap(),
-    );
-    let jwk = aliri::Jwk::from(aliri::jwa::Hmac::new(
-        SHARED_SECRET
-            .get_or_init(|| {
-                #[cfg(feature = "local-dev")]
-                let secret = dotenvy::var("JWT_SHARED_SECRET")
-                    .expect("JWT_SHARED_SECRET environment variable must be set in the .env file");
-                #[cfg(not(feature = "local-dev"))]
-                let secret = std::env::var("JWT_SHARED_SECRET")
-                    .expect("JWT_SHARED_SECRET environment variable must be set");
-                secret
-            })
-            .to_owned()
-            .into_bytes(),
-    ))
-    .with_algorithm(aliri::jwa::Algorithm::HS256)
-    .with_key_id(
-        aliri::jwk::KeyId::from_str(KEY_ID.get_or_init(|| {
-            #[cfg(feature = "local-dev")]
-            let key = dotenvy::var("JWT_KEY_ID")
-                .expect("JWT_KEY_ID environment variable must be set in the .env file");
-            #[cfg(not(feature = "local-dev"))]
-            let key =
-                std::env::var("JWT_KEY_ID").expect("JWT_KEY_ID environment variable must be set");
-            key
-        }))
-        .unwrap(),
-    );
     let claims = UserClaims {
         exp: aliri_clock::UnixTime::from({
             std::time::SystemTime::now()
                 .checked_add(std::time::Duration::from_secs(lifetime_in_seconds))
                 .unwrap()
         }),
-        iss: aliri::jwt::Issuer::new(
-            ISSUER
-                .get_or_init(|| {
-                    #[cfg(feature = "local-dev")]
-                    let issuer = dotenvy::var("JWT_ISSUER")
-                        .expect("JWT_ISSUER environment variable must be set in the .env file");
-                    #[cfg(not(feature = "local-dev"))]
-                    let issuer = std::env::var("JWT_ISSUER")
-                        .expect("JWT_ISSUER environment variable must be set");
-                    issuer
-                })
-                .to_owned(),
-        ),
-        aud: aliri::jwt::Audience::new(
-            AUDIENCE
-                .get_or_init(|| {
-                    #[cfg(feature = "local-dev")]
-                    let audience = dotenvy::var("JWT_AUDIENCE")
-                        .expect("JWT_AUDIENCE environment variable must be set in the .env file");
-                    #[cfg(not(feature = "local-dev"))]
-                    let audience = std::env::var("JWT_AUDIENCE")
-                        .expect("JWT_AUDIENCE environment variable must be set");
-                    audience
-                })
-                .to_owned(),
-        )
-        .into(),
+        iss: aliri::jwt::Issuer::new(issuer()),
+        aud: aliri::jwt::Audience::new(audience()).into(),
         jti: uuid::Uuid::now_v7(),
         scope: scopes,
+        sub: Some(aliri::jwt::Subject::new(user_id.to_string())),
+        client: client.map(ToOwned::to_owned),
     };
-    let jwt_token = aliri::Jwt::try_from_parts_with_signature(&jwt_headers, &claims, &jwk).unwrap();
 
-    jwt_token
+    super::keys::keys().sign(&claims)
 }
@@ -1,8 +1,14 @@
 use crate::prelude::*;
 use axum::async_trait;
 pub use scope::{PathAndScope, VariableScope};
+pub mod id_token;
+pub mod keys;
 pub mod layer;
+pub mod opaque;
+pub mod refresh_token;
+pub mod revocation;
 pub mod scope;
+pub mod totp;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct UserClaims {
@@ -11,6 +17,45 @@ pub struct UserClaims {
     aud: aliri::jwt::Audiences,
     jti: uuid::Uuid,
     scope: aliri_oauth2::oauth2::Scope,
+    /// The user the token has been issued for. Missing in the tokens issued
+    /// before the claim was introduced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sub: Option<aliri::jwt::Subject>,
+    /// Name of the client application or of the personal access token the
+    /// token has been issued to, recorded along with the audit events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client: Option<String>,
+}
+
+impl UserClaims {
+    /// The unique ID of the token.
+    pub fn jti(&self) -> uuid::Uuid {
+        self.jti
+    }
+    pub fn expires_at(&self) -> chrono::DateTime<chrono::Utc> {
+        use chrono::TimeZone;
+        chrono::Utc.timestamp_opt(self.exp.0 as i64, 0).unwrap()
+    }
+    pub fn exp(&self) -> aliri_clock::UnixTime {
+        self.exp
+    }
+    pub fn iss(&self) -> &aliri::jwt::Issuer {
+        &self.iss
+    }
+    pub fn aud(&self) -> &aliri::jwt::Audiences {
+        &self.aud
+    }
+    pub fn client(&self) -> Option<&str> {
+        self.client.as_deref()
+    }
+    /// The scopes held by the token as a space separated list.
+    pub fn scope_list(&self) -> String {
+        self.scope
+            .iter()
+            .map(|token| token.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 }
 
 impl aliri::jwt::CoreClaims for UserClaims {
@@ -27,7 +72,7 @@ impl aliri::jwt::CoreClaims for UserClaims {
         Some(&self.iss)
     }
     fn sub(&self) -> Option<&aliri::jwt::SubjectRef> {
-        None
+        self.sub.as_deref()
     }
 }
 
@@ -37,6 +82,8 @@ impl aliri_oauth2::oauth2::HasScope for UserClaims {
     }
 }
 
+impl aide::OperationInput for UserClaims {}
+
 #[async_trait]
 impl<S> axum::extract::FromRequestParts<S> for UserClaims
 where
@@ -48,7 +95,8 @@ where
         parts: &mut axum::http::request::Parts,
         _state: &S,
     ) -> Result<Self, Self::Rejection> {
-        let Some(claims): Option<UserClaims> = parts.extensions.remove() else {
+        // Cloned so that the claims can be checked more than once
+        let Some(claims): Option<UserClaims> = parts.extensions.get().cloned() else {
             return Err(aliri_axum::AuthFailed::MissingClaims)
         };
         Ok(claims)
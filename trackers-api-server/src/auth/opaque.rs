@@ -0,0 +1,19 @@
+//! Random tokens which carry no claims and are looked up by their hash.
+
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::Digest;
+
+/// Generates a random alphanumeric token using the RNG of the OS.
+pub fn generate(length: usize) -> String {
+    rand::rngs::OsRng
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+/// Hashes a token so that it can be stored and later looked up without
+/// keeping the token itself.
+pub fn hash(token: &str) -> Vec<u8> {
+    sha2::Sha256::digest(token.as_bytes()).to_vec()
+}
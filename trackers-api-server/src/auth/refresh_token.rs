@@ -0,0 +1,35 @@
+//! Opaque refresh tokens.
+//!
+//! Unlike the access tokens the refresh tokens carry no claims, they are
+//! random strings which only the server can map back to a session. Only
+//! a SHA-256 hash of a token is stored so that a leaked database does not
+//! allow continuing anyone's session. Each token can be used once, the used
+//! ones are remembered until the session ends to detect their reuse.
+
+pub use super::opaque::hash;
+
+/// How long a session can be continued with the refresh tokens since the
+/// last refresh.
+pub const REFRESH_TOKEN_LIFETIME_DAYS: i64 = 30;
+
+/// How long a session can be continued with the refresh tokens since it has
+/// started, no matter how often it is refreshed.
+pub const SESSION_MAX_LIFETIME_DAYS: i64 = 90;
+
+const REFRESH_TOKEN_LENGTH: usize = 48;
+
+pub struct RefreshToken(String);
+
+impl RefreshToken {
+    pub fn new() -> Self {
+        Self(super::opaque::generate(REFRESH_TOKEN_LENGTH))
+    }
+
+    pub fn hash(&self) -> Vec<u8> {
+        hash(&self.0)
+    }
+
+    pub fn take(self) -> String {
+        self.0
+    }
+}
@@ -0,0 +1,106 @@
+//! Revocation of the issued tokens before they expire.
+//!
+//! The revoked token IDs (`jti`) are stored in the database and cached in the
+//! [`AppState`]. The cache is reloaded periodically so that the tokens revoked
+//! through other instances of the server are eventually rejected too.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::prelude::*;
+
+/// The IDs of the revoked tokens along with the time the tokens expire.
+#[derive(Debug, Clone, Default)]
+pub struct RevokedTokens {
+    tokens: Arc<RwLock<HashMap<uuid::Uuid, chrono::DateTime<chrono::Utc>>>>,
+}
+
+impl RevokedTokens {
+    pub fn contains(&self, jti: &uuid::Uuid) -> bool {
+        self.tokens.read().unwrap().contains_key(jti)
+    }
+
+    /// Loads all the tokens revoked so far.
+    pub async fn load(
+        db_conn: &mut deadpool::managed::Object<
+            diesel_async::pooled_connection::AsyncDieselConnectionManager<
+                diesel_async::AsyncPgConnection,
+            >,
+        >,
+    ) -> Result<Self, diesel::result::Error> {
+        let revoked = Self::default();
+        revoked.reload(db_conn).await?;
+        Ok(revoked)
+    }
+
+    async fn reload(
+        &self,
+        db_conn: &mut deadpool::managed::Object<
+            diesel_async::pooled_connection::AsyncDieselConnectionManager<
+                diesel_async::AsyncPgConnection,
+            >,
+        >,
+    ) -> Result<(), diesel::result::Error> {
+        let tokens: Vec<(models::types::Uuid, chrono::DateTime<chrono::Utc>)> =
+            db_schema::revoked_tokens::table
+                .filter(db_schema::revoked_tokens::expires_at.gt(chrono::Utc::now()))
+                .load(db_conn)
+                .await?;
+        *self.tokens.write().unwrap() = tokens
+            .into_iter()
+            .map(|(jti, expires_at)| (jti.into(), expires_at))
+            .collect();
+        Ok(())
+    }
+
+    /// Revokes the token in the database. There is no need to keep the token
+    /// revoked after it expires. Meant to be called within a transaction, the
+    /// returned token has to be passed to [`RevokedTokens::remember`] once the
+    /// transaction commits.
+    pub async fn revoke(
+        db_conn: &mut diesel_async::AsyncPgConnection,
+        jti: uuid::Uuid,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Revoked, ApiError> {
+        diesel::insert_into(db_schema::revoked_tokens::table)
+            .values((
+                db_schema::revoked_tokens::jti.eq(models::types::Uuid::from(jti)),
+                db_schema::revoked_tokens::expires_at.eq(expires_at),
+            ))
+            .on_conflict_do_nothing()
+            .execute(db_conn)
+            .await?;
+        Ok(Revoked { jti, expires_at })
+    }
+
+    /// Rejects the tokens revoked in the database from now on, without
+    /// waiting for the next reload.
+    pub fn remember(&self, revoked: impl IntoIterator<Item = Revoked>) {
+        let mut tokens = self.tokens.write().unwrap();
+        for Revoked { jti, expires_at } in revoked {
+            tokens.insert(jti, expires_at);
+        }
+    }
+}
+
+/// A token revoked by [`RevokedTokens::revoke`] which is not in the cache yet.
+#[must_use = "the token has to be remembered once the revocation is committed"]
+#[derive(Debug)]
+pub struct Revoked {
+    jti: uuid::Uuid,
+    expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Forgets the revoked tokens which have expired anyway and picks up the ones
+/// revoked by other instances of the server.
+pub(crate) async fn refresh_revoked_tokens(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::revoked_tokens::table)
+        .filter(db_schema::revoked_tokens::expires_at.le(chrono::Utc::now()))
+        .execute(&mut db_conn)
+        .await?;
+    state.revoked_tokens.reload(&mut db_conn).await?;
+    Ok(())
+}
@@ -177,4 +177,260 @@ use axum::async_trait;
                 )
             }
         }
-    }
\ No newline at end of file
+    }
+    /// Gives read access to the tracker with the ID found in the path and to
+    /// the tasks stored within it.
+    pub struct TrackerReadScope;
+
+    impl PathBasedPolicy<super::UserClaims> for TrackerReadScope {
+        type Path = String;
+        fn path_variable(path: &Self::Path) -> &str {
+            path
+        }
+        fn scope_name() -> &'static str {
+            "tracker_read"
+        }
+    }
+
+    /// Like [`TrackerReadScope`] but also allows adding and modifying the tasks
+    /// of the tracker.
+    pub struct TrackerWriteScope;
+
+    impl PathBasedPolicy<super::UserClaims> for TrackerWriteScope {
+        type Path = String;
+        fn path_variable(path: &Self::Path) -> &str {
+            path
+        }
+        fn scope_name() -> &'static str {
+            "tracker_write"
+        }
+    }
+
+    /// Scopes granting a kind of access to all the resources of the user
+    /// identified with the [`UserIdScope`]. The [`UserIdScope`] alone does not
+    /// allow anything.
+    pub mod permission {
+        pub const TASKS_READ: &str = "tasks:read";
+        pub const TASKS_WRITE: &str = "tasks:write";
+        pub const TRACKERS_ADMIN: &str = "trackers:admin";
+        pub const ACCOUNT_ADMIN: &str = "account:admin";
+
+        pub const ALL: [&str; 4] = [TASKS_READ, TASKS_WRITE, TRACKERS_ADMIN, ACCOUNT_ADMIN];
+    }
+
+    /// The scope which can be requested to be given all the permissions.
+    pub const FULL_ACCESS: &str = "user_resources";
+
+    /// The OpenID Connect scope which makes the server issue an ID token and
+    /// lets the token be used at `/api/userinfo`.
+    pub const OPENID: &str = "openid";
+
+    /// Splits a tracker-bound scope into the policy name and the tracker ID.
+    fn tracker_bound(name: &str) -> Option<(&str, &str)> {
+        let (policy, tracker_id) = name.split_once(':')?;
+        let known_policy = policy == TrackerReadScope::scope_name()
+            || policy == TrackerWriteScope::scope_name();
+        (known_policy && base62::decode(tracker_id).is_ok()).then_some((policy, tracker_id))
+    }
+
+    /// Checks the space separated list of the scopes requested by a client,
+    /// falling back to [`FULL_ACCESS`] when none are requested. Returns `None`
+    /// when any of the scopes is unknown.
+    pub fn parse_requested(scope: Option<&str>) -> Option<Vec<String>> {
+        let mut parsed: Vec<String> = Vec::new();
+        for name in scope
+            .filter(|scope| !scope.trim().is_empty())
+            .unwrap_or(FULL_ACCESS)
+            .split_whitespace()
+        {
+            let known = name == FULL_ACCESS
+                || name == OPENID
+                || permission::ALL.contains(&name)
+                || tracker_bound(name).is_some();
+            if !known {
+                return None;
+            }
+            if !parsed.iter().any(|already| already == name) {
+                parsed.push(name.to_owned());
+            }
+        }
+        Some(parsed)
+    }
+
+    /// Checks if a client allowed to request the `allowed` scopes may request
+    /// the `requested` one.
+    pub fn is_covered(requested: &str, allowed: &str) -> bool {
+        let allowed: Vec<&str> = allowed.split_whitespace().collect();
+        if allowed.contains(&FULL_ACCESS) || allowed.contains(&requested) {
+            return true;
+        }
+        match tracker_bound(requested) {
+            Some((policy, tracker_id)) if policy == TrackerReadScope::scope_name() => {
+                allowed.contains(&permission::TASKS_READ)
+                    || allowed.contains(
+                        &format!("{}:{tracker_id}", TrackerWriteScope::scope_name()).as_str(),
+                    )
+            }
+            Some(_) => allowed.contains(&permission::TASKS_WRITE),
+            None => false,
+        }
+    }
+
+    /// Finds the first of the requested scopes which cannot be handed out by
+    /// the holder of a token with the `held` scopes, so that no one can give
+    /// a token or a client more access than they have themselves. Holding all
+    /// the permissions allows handing out any scope.
+    pub fn first_not_held<'a>(requested: &'a [String], held: &str) -> Option<&'a str> {
+        let holds_everything = permission::ALL
+            .iter()
+            .all(|permission| is_covered(permission, held));
+        if holds_everything {
+            return None;
+        }
+        requested
+            .iter()
+            .map(String::as_str)
+            .find(|name| *name == FULL_ACCESS || !is_covered(name, held))
+    }
+
+    /// Describes what the scope allows in a form which can be presented to
+    /// the user.
+    pub fn describe(name: &str) -> String {
+        match name {
+            FULL_ACCESS => "read and modify all of your tasks, trackers and lists, manage your account settings".to_owned(),
+            permission::TASKS_READ => "view your trackers, tasks and lists".to_owned(),
+            permission::TASKS_WRITE => "create and modify your tasks and lists".to_owned(),
+            permission::TRACKERS_ADMIN => "create, modify and delete your trackers".to_owned(),
+            permission::ACCOUNT_ADMIN => "manage your account, sessions and authorised applications".to_owned(),
+            OPENID => "know who you are and see your email address".to_owned(),
+            _ => match tracker_bound(name) {
+                Some((policy, tracker_id)) if policy == TrackerReadScope::scope_name() => {
+                    format!("view the tracker {tracker_id} and its tasks")
+                }
+                Some((_, tracker_id)) => {
+                    format!("view and modify the tasks of the tracker {tracker_id}")
+                }
+                None => name.to_owned(),
+            },
+        }
+    }
+
+    /// Builds the scope of a token issued to the user for the space separated
+    /// list of the scopes the user has agreed to.
+    pub fn granted(names: &str, user_id: &trackers_models::types::Uuid) -> aliri_oauth2::Scope {
+        let identity = aliri_oauth2::oauth2::ScopeToken::from_string(format!(
+            "{}:{}",
+            UserIdScope::scope_name(),
+            UserIdScope(user_id.clone())
+        ))
+        .unwrap();
+        names
+            .split_whitespace()
+            .flat_map(|name| match name {
+                FULL_ACCESS => permission::ALL.iter().map(|name| name.to_string()).collect(),
+                _ => vec![name.to_owned()],
+            })
+            .filter_map(|name| aliri_oauth2::oauth2::ScopeToken::from_string(name).ok())
+            .fold(aliri_oauth2::Scope::default().and(identity), |scope, token| {
+                scope.and(token)
+            })
+    }
+
+    /// The scope of a token giving access to everything the user can do.
+    pub fn full_access(user_id: &trackers_models::types::Uuid) -> aliri_oauth2::Scope {
+        granted(FULL_ACCESS, user_id)
+    }
+
+    /// Whether the routes accept the tracker-bound scopes.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TrackerAccess {
+        /// [`TrackerReadScope`] or [`TrackerWriteScope`] allow reading
+        Read,
+        /// [`TrackerReadScope`] allows reading, [`TrackerWriteScope`] allows
+        /// also modifying
+        Write,
+    }
+
+    /// The scopes required to access the routes of a router. The requests
+    /// which do not modify anything need the `read` scope, the others need
+    /// the `write` scope.
+    ///
+    /// Use it as the state of the [`require_scope`] middleware, added before
+    /// the JWT layer so that the claims are already verified when it runs.
+    #[derive(Debug, Clone, Copy)]
+    pub struct RequiredScope {
+        read: &'static str,
+        write: &'static str,
+        tracker: Option<TrackerAccess>,
+    }
+
+    impl RequiredScope {
+        pub fn new(read: &'static str, write: &'static str) -> Self {
+            Self {
+                read,
+                write,
+                tracker: None,
+            }
+        }
+
+        /// Accepts also the tracker-bound scopes of the tracker which ID is the
+        /// only parameter of the path.
+        pub fn or_tracker_bound(mut self, access: TrackerAccess) -> Self {
+            self.tracker = Some(access);
+            self
+        }
+    }
+
+    /// Rejects the requests authorized with a token which lacks the
+    /// [`RequiredScope`].
+    pub async fn require_scope<B: Send>(
+        axum::extract::State(required): axum::extract::State<RequiredScope>,
+        request: axum::http::Request<B>,
+        next: axum::middleware::Next<B>,
+    ) -> axum::response::Response {
+        use aliri_oauth2::oauth2::HasScope;
+        use axum::{extract::FromRequestParts, http::Method, response::IntoResponse};
+
+        let write = !matches!(
+            *request.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS
+        );
+        let needed = if write { required.write } else { required.read };
+
+        let (mut parts, body) = request.into_parts();
+        let Ok(claims) = super::UserClaims::from_request_parts(&mut parts, &()).await else {
+            // Missing claims are reported by the handler
+            return next.run(axum::http::Request::from_parts(parts, body)).await;
+        };
+
+        let mut allowed = claims.scope().iter().any(|token| token.as_str() == needed);
+        let tracker_bound_accepted = match required.tracker {
+            Some(TrackerAccess::Read) => !write,
+            Some(TrackerAccess::Write) => true,
+            None => false,
+        };
+        if !allowed && tracker_bound_accepted {
+            allowed = PathAndScope::<TrackerWriteScope, super::UserClaims>::from_request_parts(
+                &mut parts,
+                &(),
+            )
+            .await
+            .is_ok();
+        }
+        if !allowed && tracker_bound_accepted && !write {
+            allowed = PathAndScope::<TrackerReadScope, super::UserClaims>::from_request_parts(
+                &mut parts,
+                &(),
+            )
+            .await
+            .is_ok();
+        }
+
+        if !allowed {
+            return crate::error::ForbiddenError::default()
+                .with_msg(format!("the authorization token does not grant the {needed} scope"))
+                .with_docs()
+                .into_response();
+        }
+        next.run(axum::http::Request::from_parts(parts, body)).await
+    }
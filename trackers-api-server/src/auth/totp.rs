@@ -0,0 +1,122 @@
+//! Time-based one-time passwords as described in RFC 6238, compatible with
+//! the common authenticator apps.
+
+use totp_rs::{Algorithm, Secret, TOTP};
+
+/// Length of the shared secret in bytes, as recommended for HMAC-SHA1.
+const SECRET_LENGTH: usize = 20;
+
+const DIGITS: usize = 6;
+
+const STEP_SECONDS: i64 = 30;
+
+/// How many steps before and after the current one are accepted to make up
+/// for the clock drift.
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+
+const ISSUER: &str = "tracke.rs";
+
+/// Generates a new shared secret using the RNG of the OS.
+pub fn generate_secret() -> Vec<u8> {
+    use rand::RngCore;
+
+    let mut secret = vec![0; SECRET_LENGTH];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// Encodes the secret with base32 without padding, the form expected by the
+/// authenticator apps.
+pub fn encode_secret(secret: &[u8]) -> String {
+    Secret::Raw(secret.to_vec()).to_encoded().to_string()
+}
+
+/// The URI which can be turned into a QR code and scanned with an
+/// authenticator app.
+pub fn otpauth_uri(secret: &[u8], account: &str) -> String {
+    totp(secret, account).get_url()
+}
+
+/// The generator for the secret, the drift is handled by [`verify`] which
+/// needs to know the step of the code.
+fn totp(secret: &[u8], account: &str) -> TOTP {
+    // The secrets are generated with the recommended length, the checked
+    // constructor would only reject accounts with a `:` in them which the
+    // URL encodes anyway.
+    TOTP::new_unchecked(
+        Algorithm::SHA1,
+        DIGITS,
+        0,
+        STEP_SECONDS as u64,
+        secret.to_vec(),
+        Some(ISSUER.to_string()),
+        account.to_string(),
+    )
+}
+
+/// Checks the code against the steps around the current time, returning the
+/// step it matches so that it can be remembered and not accepted again.
+pub fn verify(secret: &[u8], code: &str) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    let totp = totp(secret, "");
+    let current_step = chrono::Utc::now().timestamp() / STEP_SECONDS;
+    (current_step - ALLOWED_DRIFT_STEPS..=current_step + ALLOWED_DRIFT_STEPS)
+        .find(|step| totp.check(code, (*step * STEP_SECONDS) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SHA-1 secret of the test vectors of RFC 6238.
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn codes_match_rfc_6238_test_vectors() {
+        // The RFC lists 8-digit codes, the 6-digit ones are their last digits
+        for (time, expected) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ] {
+            assert_eq!(totp(RFC_SECRET, "").generate(time), expected, "at {time}");
+        }
+    }
+
+    #[test]
+    fn secret_is_encoded_with_base32() {
+        assert_eq!(
+            encode_secret(RFC_SECRET),
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"
+        );
+    }
+
+    #[test]
+    fn uri_names_the_issuer_and_the_account() {
+        assert_eq!(
+            otpauth_uri(RFC_SECRET, "user@tracke.rs"),
+            "otpauth://totp/tracke.rs:user%40tracke.rs?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=tracke.rs"
+        );
+    }
+
+    #[test]
+    fn current_code_is_accepted() {
+        let code = totp(RFC_SECRET, "").generate_current().unwrap();
+
+        assert!(verify(RFC_SECRET, &code).is_some());
+    }
+
+    #[test]
+    fn malformed_code_is_rejected() {
+        assert_eq!(verify(RFC_SECRET, "12345"), None);
+        assert_eq!(verify(RFC_SECRET, "1234567"), None);
+        assert_eq!(verify(RFC_SECRET, "12345a"), None);
+    }
+}
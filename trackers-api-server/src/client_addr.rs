@@ -0,0 +1,124 @@
+//! The address of the client which sent the request.
+//!
+//! Behind a reverse proxy every request comes from the address of the proxy,
+//! which would put all the users under the same rate limits. When the server
+//! runs behind trusted proxies, their number is set with `TRUSTED_PROXIES` and
+//! the address is taken from the `X-Forwarded-For` header instead. Each proxy
+//! appends the address it has received the request from, so the entries
+//! before the ones added by the trusted proxies can be forged by the client
+//! and are ignored.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+
+use axum::async_trait;
+
+use crate::error::ServerError;
+
+const FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// The address of the client, either of the peer or the one forwarded by the
+/// trusted proxies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddr(pub IpAddr);
+
+/// How many proxies in front of the server can be trusted to append the
+/// address of their peer to `X-Forwarded-For`, none by default.
+fn trusted_proxies() -> usize {
+    static TRUSTED_PROXIES: OnceLock<usize> = OnceLock::new();
+    *TRUSTED_PROXIES.get_or_init(|| {
+        crate::env_var("TRUSTED_PROXIES")
+            .map(|proxies| {
+                proxies.parse().expect(
+                    "TRUSTED_PROXIES must be the number of the proxies in front of the server",
+                )
+            })
+            .unwrap_or(0)
+    })
+}
+
+/// Picks the address added by the outermost of the trusted proxies. Falls back
+/// to the peer when the header does not have enough valid entries, which
+/// means that the request has not passed through the proxies.
+fn forwarded_addr<'a>(
+    peer: IpAddr,
+    forwarded_for: impl Iterator<Item = &'a str>,
+    trusted_proxies: usize,
+) -> IpAddr {
+    if trusted_proxies == 0 {
+        return peer;
+    }
+    let entries: Vec<&str> = forwarded_for
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+    entries
+        .len()
+        .checked_sub(trusted_proxies)
+        .and_then(|index| entries[index].parse().ok())
+        .unwrap_or(peer)
+}
+
+#[async_trait]
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for ClientAddr {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let axum::extract::ConnectInfo(peer) = parts
+            .extensions
+            .get::<axum::extract::ConnectInfo<SocketAddr>>()
+            .ok_or_else(|| ServerError {
+                err: anyhow::anyhow!("the address of the peer is missing"),
+            })?;
+        let forwarded_for = parts
+            .headers
+            .get_all(FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok());
+        Ok(Self(forwarded_addr(
+            peer.ip(),
+            forwarded_for,
+            trusted_proxies(),
+        )))
+    }
+}
+
+impl aide::OperationInput for ClientAddr {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+
+    #[test]
+    fn header_is_ignored_without_trusted_proxies() {
+        assert_eq!(forwarded_addr(PEER, ["203.0.113.7"].into_iter(), 0), PEER);
+    }
+
+    #[test]
+    fn entries_forged_by_the_client_are_skipped() {
+        let forwarded_for = ["198.51.100.1, 203.0.113.7", "192.0.2.5"];
+
+        assert_eq!(
+            forwarded_addr(PEER, forwarded_for.into_iter(), 1),
+            "192.0.2.5".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            forwarded_addr(PEER, forwarded_for.into_iter(), 2),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn peer_is_used_without_enough_entries() {
+        assert_eq!(forwarded_addr(PEER, ["192.0.2.5"].into_iter(), 2), PEER);
+        assert_eq!(
+            forwarded_addr(PEER, ["not an address"].into_iter(), 1),
+            PEER
+        );
+    }
+}
@@ -51,3 +51,9 @@ impl From<reqwest::Error> for ApiError {
         ServerError { err: value.into() }.into()
     }
 }
+
+impl From<crate::mail::MailError> for ApiError {
+    fn from(value: crate::mail::MailError) -> Self {
+        ServerError { err: value.0 }.into()
+    }
+}
@@ -23,6 +23,21 @@ pub struct ApiError {
     /// Links applicable in given situation
     #[serde(skip_serializing_if = "Option::is_none")]
     pub links: Option<HashMap<&'static str, String>>,
+    /// Seconds after which the client may repeat the request, sent in the
+    /// `Retry-After` header
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
+    /// OAuth 2.0 error code, given by the token endpoint as described in
+    /// RFC 6749 and RFC 8628
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ApiError {
+    pub fn with_oauth_error(mut self, error: &'static str) -> Self {
+        self.error = Some(error.to_owned());
+        self
+    }
 }
 
 impl std::fmt::Display for ApiError {
@@ -37,6 +52,9 @@ pub trait ApiErrorTrait {
     fn status(&self) -> u16;
     fn msg(&self) -> String;
     fn links(&self) -> &Option<HashMap<&'static str, String>>;
+    fn retry_after(&self) -> Option<u64> {
+        None
+    }
 }
 
 impl<T> From<T> for ApiError
@@ -48,16 +66,25 @@ where
             status: value.status(),
             msg: value.msg(),
             links: value.links().to_owned(),
+            retry_after: value.retry_after(),
+            error: None,
         }
     }
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> axum::response::Response {
-        (
+        let retry_after = self.retry_after;
+        let mut response = (
             axum::http::StatusCode::from_u16(self.status).unwrap(),
             Json(self),
         )
-            .into_response()
+            .into_response();
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(axum::http::header::RETRY_AFTER, seconds.into());
+        }
+        response
     }
 }
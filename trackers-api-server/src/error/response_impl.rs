@@ -29,3 +29,9 @@ impl IntoResponse for ForbiddenError {
         (axum::http::StatusCode::FORBIDDEN, Json(self)).into_response()
     }
 }
+
+impl IntoResponse for TooManyRequestsError {
+    fn into_response(self) -> axum::response::Response {
+        ApiError::from(self).into_response()
+    }
+}
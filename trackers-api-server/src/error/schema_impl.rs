@@ -332,3 +332,58 @@ impl AideOperationOutput for ForbiddenError {
         vec![(Some(403), Self::operation_response(ctx, operation).unwrap())]
     }
 }
+
+impl JsonSchema for TooManyRequestsError {
+    fn schema_name() -> String {
+        "too many requests error".to_owned()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use schemars::schema::{InstanceType, Schema, SchemaObject, SingleOrVec};
+
+        let mut schema = gen.subschema_for::<super::ApiError>().into_object();
+        schema.object.as_mut().and_then(|obj| {
+            obj.properties
+                .iter_mut()
+                .find(|prop| prop.0 == "status")
+                .and_then(|prop| {
+                    let mut status_code_const_schema = SchemaObject::default();
+                    status_code_const_schema.const_value =
+                        Some(schemars::_serde_json::value::Number::from(429).into());
+                    status_code_const_schema.instance_type =
+                        Some(SingleOrVec::Single(Box::new(InstanceType::Number)));
+                    *(prop.1) = Schema::Object(status_code_const_schema);
+                    Some(prop)
+                })
+        });
+        schemars::schema::Schema::Object(schema)
+    }
+}
+
+impl AideOperationOutput for TooManyRequestsError {
+    type Inner = Self;
+
+    fn operation_response(
+        ctx: &mut aide::gen::GenContext,
+        _operation: &mut openapi::Operation,
+    ) -> Option<Response> {
+        Some(openapi::Response {
+            description: String::from(
+                "Too many requests have been made. The `Retry-After` header tells when to try again.",
+            ),
+            content: indexmap::indexmap! {
+            "application/json".to_owned() => aide::openapi::MediaType{
+                schema: Some(aide::openapi::SchemaObject{json_schema: ctx.schema.subschema_for::<Self>(), external_docs: None, example: None}),
+                ..aide::openapi::MediaType::default()}
+            },
+            ..openapi::Response::default()
+        })
+    }
+
+    fn inferred_responses(
+        ctx: &mut aide::gen::GenContext,
+        operation: &mut openapi::Operation,
+    ) -> Vec<(Option<u16>, Response)> {
+        vec![(Some(429), Self::operation_response(ctx, operation).unwrap())]
+    }
+}
@@ -37,6 +37,8 @@ impl IntoResponse for ServerError {
                     status: 500,
                     msg: format!("An internal server error has occured!"),
                     links: None,
+                    retry_after: None,
+                    error: None,
                 })
             }
             #[cfg(debug_assertions)]
@@ -45,6 +47,8 @@ impl IntoResponse for ServerError {
                     status: 500,
                     msg: format!("An internal server error has occured: {}", self.err),
                     links: None,
+                    retry_after: None,
+                    error: None,
                 })
             }
         })
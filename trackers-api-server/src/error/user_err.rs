@@ -183,6 +183,46 @@ impl std::fmt::Display for GoneError {
 
 impl std::error::Error for GoneError {}
 
+#[derive(Debug, Clone, Serialize, Default)]
+#[serde(into = "ApiError")]
+pub struct TooManyRequestsError {
+    pub msg: String,
+    pub links: Option<HashMap<&'static str, String>>,
+    /// Seconds after which the client may try again
+    pub retry_after: Option<u64>,
+}
+
+impl TooManyRequestsError {
+    pub fn with_msg(mut self, msg: impl std::fmt::Display) -> Self {
+        self.msg = msg.to_string();
+        self
+    }
+    pub fn with_links(mut self, links: impl IntoIterator<Item = (&'static str, String)>) -> Self {
+        self.links.get_or_insert(HashMap::default()).extend(links);
+        self
+    }
+    pub fn with_docs(mut self) -> Self {
+        self.links
+            .get_or_insert(HashMap::default())
+            .insert("documentation", "/doc".into());
+        self
+    }
+    /// Rounds up to full seconds as required by the `Retry-After` header.
+    pub fn with_retry_after(mut self, wait: std::time::Duration) -> Self {
+        let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+        self.retry_after = Some(seconds);
+        self
+    }
+}
+
+impl std::fmt::Display for TooManyRequestsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for TooManyRequestsError {}
+
 impl crate::error::ApiErrorTrait for BadRequestError {
     fn status(&self) -> u16 {
         400
@@ -254,3 +294,18 @@ impl crate::error::ApiErrorTrait for GoneError {
         &self.links
     }
 }
+
+impl crate::error::ApiErrorTrait for TooManyRequestsError {
+    fn status(&self) -> u16 {
+        429
+    }
+    fn msg(&self) -> String {
+        self.msg.clone()
+    }
+    fn links(&self) -> &Option<HashMap<&'static str, String>> {
+        &self.links
+    }
+    fn retry_after(&self) -> Option<u64> {
+        self.retry_after
+    }
+}
@@ -0,0 +1,53 @@
+use super::*;
+use tokio::io::AsyncWriteExt;
+
+/// Writes the plain text version of the mail to a file or to the standard
+/// output. Meant for development and for instances which do not need to
+/// reach anyone by mail.
+pub struct FileMailer {
+    path: Option<std::path::PathBuf>,
+}
+
+impl FileMailer {
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+        }
+    }
+
+    pub fn stdout() -> Self {
+        Self { path: None }
+    }
+
+    pub fn from_env() -> Result<Self, MailError> {
+        let path = env_var("MAIL_FILE")
+            .ok_or_else(|| anyhow::anyhow!("MAIL_FILE environment variable must be set"))?;
+        Ok(Self::new(path))
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let formatted = format!(
+            "Date: {}\nTo: {}\nSubject: {}\n\n{}\n\n",
+            chrono::Utc::now().to_rfc2822(),
+            mail.to,
+            mail.subject,
+            mail.text
+        );
+
+        match &self.path {
+            Some(path) => {
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?;
+                file.write_all(formatted.as_bytes()).await?;
+            }
+            None => print!("{formatted}"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+//! Sending transactional mail
+//!
+//! The mail is rendered locally from the [templates] and handed over to one
+//! of the [`Mailer`] implementations. The implementation used by the server
+//! is selected at startup with the `MAIL_TRANSPORT` environment variable, see
+//! [`from_env`].
+
+use crate::env_var;
+use axum::async_trait;
+use std::sync::Arc;
+
+pub mod file;
+pub mod sendgrid;
+pub mod smtp;
+pub mod templates;
+
+pub use file::FileMailer;
+pub use sendgrid::SendGridMailer;
+pub use smtp::SmtpMailer;
+
+/// A rendered message ready to be sent.
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: trackers_models::types::Email,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// Any failure of configuring the transport or delivering the mail.
+#[derive(Debug)]
+pub struct MailError(pub anyhow::Error);
+
+impl std::fmt::Display for MailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to send mail: {}", self.0)
+    }
+}
+
+/// Makes conversion into MailError possible with `?` for any type that
+/// can also convert into [`anyhow::Error`].
+impl<E> From<E> for MailError
+where
+    E: Into<anyhow::Error>,
+{
+    fn from(err: E) -> Self {
+        Self(err.into())
+    }
+}
+
+/// A transport delivering the rendered mail.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+/// Builds the mailer selected with the `MAIL_TRANSPORT` environment variable.
+///
+/// The accepted values are:
+/// - `sendgrid` - requires `SENDGRID_API_KEY` and `SENDGRID_MAIL_SENDER`,
+/// - `smtp` - requires `SMTP_HOST` and `MAIL_SENDER`, optionally `SMTP_PORT`,
+/// `SMTP_TLS` (`none`, `starttls` or `tls`), `SMTP_USERNAME` and
+/// `SMTP_PASSWORD`,
+/// - `file` - appends the messages to the file at `MAIL_FILE`,
+/// - `stdout` - prints the messages, which is also the default when the
+/// variable is missing and the `local-dev` feature is enabled.
+pub fn from_env() -> Result<Arc<dyn Mailer>, MailError> {
+    #[cfg(feature = "local-dev")]
+    let transport = env_var("MAIL_TRANSPORT").unwrap_or_else(|| {
+        println!("MAIL_TRANSPORT environment variable is not set, mail will be printed to stdout");
+        "stdout".to_owned()
+    });
+    #[cfg(not(feature = "local-dev"))]
+    let transport = env_var("MAIL_TRANSPORT")
+        .ok_or_else(|| anyhow::anyhow!("MAIL_TRANSPORT environment variable must be set"))?;
+
+    let mailer: Arc<dyn Mailer> = match transport.as_str() {
+        "sendgrid" => Arc::new(SendGridMailer::from_env()?),
+        "smtp" => Arc::new(SmtpMailer::from_env()?),
+        "file" => Arc::new(FileMailer::from_env()?),
+        "stdout" => Arc::new(FileMailer::stdout()),
+        other => {
+            return Err(MailError(anyhow::anyhow!(
+                "unsupported mail transport {other}, expected one of: sendgrid, smtp, file, stdout"
+            )))
+        }
+    };
+    Ok(mailer)
+}
@@ -0,0 +1,54 @@
+use super::*;
+
+/// Sends the mail through the [SendGrid](https://sendgrid.com) web API. The
+/// content is rendered locally so no dynamic templates need to be configured
+/// in SendGrid.
+pub struct SendGridMailer {
+    api_key: String,
+    sender: String,
+    client: reqwest::Client,
+}
+
+impl SendGridMailer {
+    pub fn new(api_key: String, sender: String) -> Self {
+        Self {
+            api_key,
+            sender,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, MailError> {
+        let api_key = env_var("SENDGRID_API_KEY").ok_or_else(|| {
+            anyhow::anyhow!("SENDGRID_API_KEY environment variable must be set")
+        })?;
+        let sender = env_var("SENDGRID_MAIL_SENDER").ok_or_else(|| {
+            anyhow::anyhow!("SENDGRID_MAIL_SENDER environment variable must be set")
+        })?;
+        Ok(Self::new(api_key, sender))
+    }
+}
+
+#[async_trait]
+impl Mailer for SendGridMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let body = serde_json::json!({
+            "from": { "email": self.sender },
+            "personalizations": [{ "to": [{ "email": mail.to.to_string() }] }],
+            "subject": mail.subject,
+            "content": [
+                { "type": "text/plain", "value": mail.text },
+                { "type": "text/html", "value": mail.html },
+            ],
+        });
+
+        self.client
+            .post("https://api.sendgrid.com/v3/mail/send")
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
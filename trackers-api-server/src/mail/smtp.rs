@@ -0,0 +1,144 @@
+use super::*;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+
+/// Sends the mail to an SMTP server. Any SMTP sink started locally can be
+/// used to inspect the messages during development when `SMTP_TLS` is set
+/// to `none`.
+pub struct SmtpMailer {
+    sender: lettre::message::Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn new(
+        sender: lettre::message::Mailbox,
+        transport: AsyncSmtpTransport<Tokio1Executor>,
+    ) -> Self {
+        Self { sender, transport }
+    }
+
+    pub fn from_env() -> Result<Self, MailError> {
+        let host = env_var("SMTP_HOST")
+            .ok_or_else(|| anyhow::anyhow!("SMTP_HOST environment variable must be set"))?;
+        let sender: lettre::message::Mailbox = env_var("MAIL_SENDER")
+            .ok_or_else(|| anyhow::anyhow!("MAIL_SENDER environment variable must be set"))?
+            .parse()?;
+
+        let mut builder = match env_var("SMTP_TLS").as_deref().unwrap_or("starttls") {
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?,
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+            other => {
+                return Err(MailError(anyhow::anyhow!(
+                    "unsupported SMTP_TLS value {other}, expected one of: none, starttls, tls"
+                )))
+            }
+        };
+        if let Some(port) = env_var("SMTP_PORT") {
+            builder = builder.port(port.parse()?);
+        }
+        if let (Some(username), Some(password)) =
+            (env_var("SMTP_USERNAME"), env_var("SMTP_PASSWORD"))
+        {
+            builder = builder.credentials(
+                lettre::transport::smtp::authentication::Credentials::new(username, password),
+            );
+        }
+
+        Ok(Self::new(sender, builder.build()))
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let message = lettre::Message::builder()
+            .from(self.sender.clone())
+            .to(mail.to.to_string().parse()?)
+            .subject(mail.subject)
+            .multipart(lettre::message::MultiPart::alternative_plain_html(
+                mail.text, mail.html,
+            ))?;
+
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    /// Accepts a single SMTP session on the listener and returns the content
+    /// of the message sent with the `DATA` command.
+    async fn smtp_sink(listener: tokio::net::TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+        let mut message = String::new();
+        let mut in_data = false;
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if in_data {
+                if line == "." {
+                    in_data = false;
+                    writer.write_all(b"250 queued\r\n").await.unwrap();
+                } else {
+                    message.push_str(&line);
+                    message.push('\n');
+                }
+                continue;
+            }
+            let command = line.to_ascii_uppercase();
+            if command.starts_with("EHLO") {
+                writer
+                    .write_all(b"250-sink\r\n250 8BITMIME\r\n")
+                    .await
+                    .unwrap();
+            } else if command.starts_with("DATA") {
+                in_data = true;
+                writer.write_all(b"354 go ahead\r\n").await.unwrap();
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 bye\r\n").await.unwrap();
+                break;
+            } else {
+                writer.write_all(b"250 ok\r\n").await.unwrap();
+            }
+        }
+        message
+    }
+
+    #[tokio::test]
+    async fn delivers_mail_to_smtp_sink() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+
+        let mailer = SmtpMailer::new(
+            "tracke.rs <noreply@tracke.rs>".parse().unwrap(),
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous("127.0.0.1")
+                .port(port)
+                .build(),
+        );
+        mailer
+            .send(Mail {
+                to: "test@tracke.rs".try_into().unwrap(),
+                subject: "Confirmation code".to_owned(),
+                html: "<p>Your code is ABC123</p>".to_owned(),
+                text: "Your code is ABC123".to_owned(),
+            })
+            .await
+            .unwrap();
+        // lettre keeps the connection in its pool, drop it to end the session
+        drop(mailer);
+
+        let message = sink.await.unwrap();
+        assert!(message.contains("To: test@tracke.rs"));
+        assert!(message.contains("From: tracke.rs <noreply@tracke.rs>"));
+        assert!(message.contains("Subject: Confirmation code"));
+        assert!(message.contains("Your code is ABC123"));
+        assert!(message.contains("<p>Your code is ABC123</p>"));
+    }
+}
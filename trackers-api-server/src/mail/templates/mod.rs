@@ -0,0 +1,76 @@
+//! Templates of the transactional mail sent by the server
+//!
+//! Each template has an HTML and a plain text version stored next to this
+//! module. Variables are written as `{{name}}` and are replaced with the
+//! values given to [`Template::render`]. The values are escaped when inserted
+//! into the HTML version.
+
+use super::Mail;
+
+pub struct Template {
+    pub subject: &'static str,
+    pub html: &'static str,
+    pub text: &'static str,
+}
+
+pub const REGISTRATION_CODE: Template = Template {
+    subject: "Your tracke.rs registration code",
+    html: include_str!("registration_code.html"),
+    text: include_str!("registration_code.txt"),
+};
+
+pub const PASSWORD_RESET_CODE: Template = Template {
+    subject: "Reset your tracke.rs password",
+    html: include_str!("password_reset_code.html"),
+    text: include_str!("password_reset_code.txt"),
+};
+
+pub const EMAIL_CHANGE_CODE: Template = Template {
+    subject: "Confirm your new tracke.rs email address",
+    html: include_str!("email_change_code.html"),
+    text: include_str!("email_change_code.txt"),
+};
+
+pub const MAGIC_LINK_CODE: Template = Template {
+    subject: "Your tracke.rs login code",
+    html: include_str!("magic_link_code.html"),
+    text: include_str!("magic_link_code.txt"),
+};
+
+impl Template {
+    pub fn render(
+        &self,
+        to: &trackers_models::types::Email,
+        vars: &[(&str, &(dyn std::fmt::Display + Sync))],
+    ) -> Mail {
+        let mut html = self.html.to_owned();
+        let mut text = self.text.to_owned();
+        for (name, value) in vars {
+            let placeholder = format!("{{{{{name}}}}}");
+            let value = value.to_string();
+            html = html.replace(&placeholder, &escape_html(&value));
+            text = text.replace(&placeholder, &value);
+        }
+        Mail {
+            to: to.clone(),
+            subject: self.subject.to_owned(),
+            html,
+            text,
+        }
+    }
+}
+
+pub(crate) fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
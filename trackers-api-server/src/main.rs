@@ -1,8 +1,12 @@
 pub mod auth;
+pub mod client_addr;
 pub mod error;
 pub mod json;
+pub mod mail;
 pub mod prelude;
+pub mod rate_limit;
 pub mod response;
+pub mod scheduler;
 pub mod services;
 pub mod query_param;
 
@@ -13,6 +17,19 @@ use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConne
 #[derive(Clone)]
 pub struct AppState {
     pub db: deadpool::managed::Pool<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    pub mailer: std::sync::Arc<dyn mail::Mailer>,
+    /// Limits how many confirmation code mails can be requested from a single
+    /// address
+    pub code_mail_limiter: rate_limit::RateLimiter<std::net::IpAddr>,
+    /// Limits how many login codes can be mailed to a single address
+    pub magic_link_mail_limiter: rate_limit::RateLimiter<trackers_models::types::Email>,
+    /// Delays the password logins to an account after it has seen failed ones
+    pub login_account_backoff: rate_limit::FailureBackoff<trackers_models::types::Email>,
+    /// Delays the password logins from an address after it has sent failed
+    /// ones
+    pub login_address_backoff: rate_limit::FailureBackoff<std::net::IpAddr>,
+    pub revoked_tokens: auth::revocation::RevokedTokens,
+    pub email_redirects: services::email_change::EmailRedirects,
 }
 
 #[tokio::main]
@@ -40,11 +57,73 @@ pub async fn main() {
         pool
     };
 
+    let revoked_tokens = {
+        let mut db_conn = database_connection_pool
+            .get()
+            .await
+            .expect("failed to connect to the database");
+        auth::revocation::RevokedTokens::load(&mut db_conn)
+            .await
+            .expect("failed to load the revoked tokens")
+    };
+
+    let email_redirects = {
+        let mut db_conn = database_connection_pool
+            .get()
+            .await
+            .expect("failed to connect to the database");
+        services::email_change::EmailRedirects::load(&mut db_conn)
+            .await
+            .expect("failed to load the email redirects")
+    };
+
+    let mailer = mail::from_env().expect("failed to configure the mail transport");
+
     let state = AppState {
         db: database_connection_pool,
+        mailer,
+        code_mail_limiter: rate_limit::RateLimiter::new(
+            services::registration::CODE_MAILS_PER_ADDRESS,
+            std::time::Duration::from_secs(60 * 60),
+        ),
+        magic_link_mail_limiter: rate_limit::RateLimiter::new(
+            services::magic_link::CODE_MAILS_PER_ACCOUNT,
+            std::time::Duration::from_secs(60 * 60),
+        ),
+        login_account_backoff: rate_limit::FailureBackoff::new(
+            services::login_protection::FREE_ATTEMPTS_PER_ACCOUNT,
+            services::login_protection::LOCKOUT_AFTER_PER_ACCOUNT,
+            std::time::Duration::from_secs(services::login_protection::MAX_DELAY_SECONDS),
+            std::time::Duration::from_secs(services::login_protection::LOCKOUT_MINUTES * 60),
+        ),
+        login_address_backoff: rate_limit::FailureBackoff::new(
+            services::login_protection::FREE_ATTEMPTS_PER_ADDRESS,
+            services::login_protection::LOCKOUT_AFTER_PER_ADDRESS,
+            std::time::Duration::from_secs(services::login_protection::MAX_DELAY_SECONDS),
+            std::time::Duration::from_secs(services::login_protection::LOCKOUT_MINUTES * 60),
+        ),
+        revoked_tokens,
+        email_redirects,
     };
 
+    let scheduler = services::app_jobs().start(state.clone());
+
     let app = services::app_services()
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            services::user::redirect_changed_email,
+        ))
+        .layer(axum::middleware::from_fn(
+            crate::auth::layer::accept_ed25519_tokens,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::auth::layer::reject_revoked_tokens,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            crate::auth::layer::accept_personal_access_tokens,
+        ))
         .layer(axum::middleware::from_fn(crate::auth::layer::require_jwt))
         .layer(
             tower_http::cors::CorsLayer::new()
@@ -72,10 +151,36 @@ pub async fn main() {
     println!("launching the server at: {server_address}");
 
     axum::Server::bind(&server_address)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr>())
         .with_graceful_shutdown(shutdown_handler())
         .await
         .expect("failed to start the http server");
+
+    println!("  stopping the scheduled jobs");
+    scheduler.stop().await;
+}
+
+/// Reads an optional variable from an appropriate source depending on the
+/// target environment.
+pub(crate) fn env_var(name: &str) -> Option<String> {
+    #[cfg(feature = "local-dev")]
+    let var = dotenvy::var(name).ok();
+    #[cfg(not(feature = "local-dev"))]
+    let var = std::env::var(name).ok();
+    var
+}
+
+/// The URL at which the server is reachable for the users, used to build
+/// absolute links. Taken from `PUBLIC_URL` or guessed from the `Host` header of
+/// the request when the variable is not set.
+pub(crate) fn public_url(host: Option<&axum::headers::Host>) -> String {
+    match env_var("PUBLIC_URL") {
+        Some(url) => url.trim_end_matches('/').to_owned(),
+        None => format!(
+            "http://{}",
+            host.map_or_else(|| String::from("localhost"), |host| host.to_string())
+        ),
+    }
 }
 
 pub async fn shutdown_handler() {
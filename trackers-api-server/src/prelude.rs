@@ -15,7 +15,7 @@ pub use crate::response::{CreatedResource, DeletedResource, ModifiedResource, Re
 pub use crate::{
     error::{
         ApiError, BadRequestError, ConflictError, ForbiddenError, GoneError, NotFoundError,
-        ServerError, UnathorizedError,
+        ServerError, TooManyRequestsError, UnathorizedError,
     },
     json::JsonExtract,
     services, AppState,
@@ -3,7 +3,7 @@ use diesel::NullableExpressionMethods;
 use crate::prelude::*;
 extern crate serde_regex;
 
-#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
 pub struct TasksQuery {
     /// Limits results to either completed (checkmarked) tasks if true or 
     /// uncompleted if false.
@@ -33,12 +33,15 @@ pub struct TasksQuery {
     /// regex variant implemented for the [`SIMILAR TO`](https://www.postgresql.org/docs/current/functions-matching.html#FUNCTIONS-SIMILARTO-REGEXP)
     /// operation in PostgreSQL.
     pub regex: Option<RegexStr>,
+    /// Includes the archived tasks in the results if true. When listing all
+    /// the tasks of a user it includes the tasks of the archived trackers too.
+    pub archived: Option<bool>,
 }
 
-impl TasksQuery {
-    pub fn is_empty(&self) -> bool {
-        self.checkmarked.is_none() && self.tags.is_none() && self.title.is_none() && self.regex.is_none()
-    }
+#[derive(Deserialize, JsonSchema, Debug, Clone, Default)]
+pub struct TrackersQuery {
+    /// Includes the archived trackers in the results if true.
+    pub archived: Option<bool>,
 }
 
 #[derive(Deserialize, Debug, Clone, diesel::expression::AsExpression,diesel::deserialize::FromSqlRow)]
@@ -147,16 +150,16 @@ impl TasksQuery {
                     });
         }
         
-        boxed_filters.unwrap_or_else(||
-            // We shouldn't ever get here. If we got here it means either the exterior business logic
-            // was implemented poorly or the implementation of this function did not consider all the
-            // cases. Either way, it is simpler to return anything and avoid difficult to track panicks.
-            {
-                #[cfg(debug_assertions)]
-                dbg!("We shouldn't be executing this part.");
-                  
-                Box::new(db_schema::tasks::task_id.is_not_null()
-            )})
+        // without any other filters only the trash and the archive are left out
+        let filters = boxed_filters
+            .unwrap_or_else(|| Box::new(db_schema::tasks::task_id.is_not_null()));
+        // the tasks in the trash are never a match
+        let filters: BoxedFilters = Box::new(filters.and(db_schema::tasks::deleted_at.is_null()));
+        if self.archived.unwrap_or(false) {
+            filters
+        } else {
+            Box::new(filters.and(db_schema::tasks::archived_at.is_null()))
+        }
     }
     pub fn into_join_filters<OTHER>(&self) -> BoxedFiltersJoin<OTHER>
     where
@@ -237,15 +240,14 @@ impl TasksQuery {
                         Some(part)
                     });
         }
-        boxed_filters.unwrap_or_else(|| 
-            // We shouldn't ever get here. If we got here it means either the exterior business logic
-            // was implemented poorly or the implementation of this function did not consider all the
-            // cases. Either way, it is simpler to return anything and avoid difficult to track panicks.
-            {
-                #[cfg(debug_assertions)]
-                dbg!("We shouldn't be executing this part.");
-                  
-                Box::new(db_schema::tasks::task_id.is_not_null()
-            )})
+        let filters = boxed_filters
+            .unwrap_or_else(|| Box::new(db_schema::tasks::task_id.is_not_null()));
+        let filters: BoxedFiltersJoin<OTHER> =
+            Box::new(filters.and(db_schema::tasks::deleted_at.is_null()));
+        if self.archived.unwrap_or(false) {
+            filters
+        } else {
+            Box::new(filters.and(db_schema::tasks::archived_at.is_null()))
+        }
     }
 }
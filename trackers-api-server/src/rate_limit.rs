@@ -0,0 +1,188 @@
+//! In-memory limits on how often an action can be performed.
+//!
+//! The limits are kept by each instance of the server separately and are lost
+//! on restart. They are meant to slow down abuse of the endpoints that cause
+//! mail to be sent or that accept secrets, not to enforce quotas.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Allows at most `limit` hits per key within a sliding `window`.
+#[derive(Debug, Clone)]
+pub struct RateLimiter<K> {
+    hits: Arc<Mutex<HashMap<K, VecDeque<Instant>>>>,
+    limit: usize,
+    window: Duration,
+}
+
+impl<K: Eq + Hash> RateLimiter<K> {
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            hits: Arc::new(Mutex::new(HashMap::new())),
+            limit,
+            window,
+        }
+    }
+
+    /// Records a hit for the key if it is still within the limit. Otherwise
+    /// returns how long the caller has to wait until the next hit is allowed.
+    pub fn hit(&self, key: K) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap();
+        let key_hits = hits.entry(key).or_default();
+
+        while key_hits
+            .front()
+            .map_or(false, |hit| now.duration_since(*hit) >= self.window)
+        {
+            key_hits.pop_front();
+        }
+
+        if key_hits.len() >= self.limit {
+            let oldest = *key_hits.front().unwrap();
+            return Err(self.window - now.duration_since(oldest));
+        }
+
+        key_hits.push_back(now);
+        Ok(())
+    }
+
+    /// Forgets the keys which have no hits within the window anymore.
+    pub fn purge(&self) {
+        let now = Instant::now();
+        self.hits.lock().unwrap().retain(|_, key_hits| {
+            key_hits
+                .back()
+                .map_or(false, |hit| now.duration_since(*hit) < self.window)
+        });
+    }
+}
+
+/// Makes the callers wait longer and longer after each consecutive failure for
+/// a key. The first `free_attempts` failures cause no delay, the following
+/// ones double the delay up to `max_delay`, and after `lockout_after` failures
+/// the key is locked out for the whole `lockout`. The failures are forgotten
+/// once `lockout` passes since the last one.
+///
+/// Every allowed attempt is reserved and counted as a failure until it is
+/// resolved, so that many attempts made at once cannot all pass the check
+/// before any of them fails. The reservations which are never resolved are
+/// forgotten along with the failures.
+#[derive(Debug, Clone)]
+pub struct FailureBackoff<K> {
+    failures: Arc<Mutex<HashMap<K, Failures>>>,
+    free_attempts: u32,
+    lockout_after: u32,
+    max_delay: Duration,
+    lockout: Duration,
+}
+
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    /// Attempts allowed by [`FailureBackoff::check`] and not resolved yet
+    in_flight: u32,
+    last: Instant,
+}
+
+impl<K: Eq + Hash> FailureBackoff<K> {
+    pub fn new(
+        free_attempts: u32,
+        lockout_after: u32,
+        max_delay: Duration,
+        lockout: Duration,
+    ) -> Self {
+        Self {
+            failures: Arc::new(Mutex::new(HashMap::new())),
+            free_attempts,
+            lockout_after,
+            max_delay,
+            lockout,
+        }
+    }
+
+    /// How long after the last of `count` failures the next attempt is allowed.
+    fn delay_after(&self, count: u32) -> Duration {
+        if count >= self.lockout_after {
+            self.lockout
+        } else if count < self.free_attempts {
+            Duration::ZERO
+        } else {
+            let exponent = (count - self.free_attempts).min(31);
+            Duration::from_secs(1 << exponent).min(self.max_delay)
+        }
+    }
+
+    /// Reserves an attempt for the key, or returns how long the caller has to
+    /// wait if the key is not allowed another attempt yet. The attempt has to
+    /// be resolved with [`fail`](Self::fail), [`succeed`](Self::succeed) or
+    /// [`release`](Self::release).
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+        let key_failures = failures.entry(key).or_insert(Failures {
+            count: 0,
+            in_flight: 0,
+            last: now,
+        });
+
+        let elapsed = now.duration_since(key_failures.last);
+        if elapsed >= self.lockout {
+            key_failures.count = 0;
+            key_failures.in_flight = 0;
+        } else {
+            let pending = key_failures.count + key_failures.in_flight;
+            match self.delay_after(pending).checked_sub(elapsed) {
+                Some(wait) if !wait.is_zero() => return Err(wait),
+                _ => {}
+            }
+        }
+        key_failures.in_flight += 1;
+        Ok(())
+    }
+
+    /// Records a failure of a reserved attempt for the key, returning the
+    /// number of consecutive failures so far.
+    pub fn fail(&self, key: K) -> u32 {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+        let key_failures = failures.entry(key).or_insert(Failures {
+            count: 0,
+            in_flight: 0,
+            last: now,
+        });
+
+        if now.duration_since(key_failures.last) >= self.lockout {
+            key_failures.count = 0;
+        }
+        key_failures.count += 1;
+        key_failures.in_flight = key_failures.in_flight.saturating_sub(1);
+        key_failures.last = now;
+        key_failures.count
+    }
+
+    /// Forgets the failures of the key after a successful attempt, along with
+    /// the reserved attempts.
+    pub fn succeed(&self, key: &K) {
+        self.failures.lock().unwrap().remove(key);
+    }
+
+    /// Gives back a reserved attempt which has not failed without forgetting
+    /// the failures of the key.
+    pub fn release(&self, key: &K) {
+        if let Some(key_failures) = self.failures.lock().unwrap().get_mut(key) {
+            key_failures.in_flight = key_failures.in_flight.saturating_sub(1);
+        }
+    }
+
+    /// Forgets the keys whose failures are too old to matter.
+    pub fn purge(&self) {
+        let now = Instant::now();
+        self.failures
+            .lock()
+            .unwrap()
+            .retain(|_, key_failures| now.duration_since(key_failures.last) < self.lockout);
+    }
+}
@@ -0,0 +1,94 @@
+//! Periodic background jobs.
+//!
+//! The jobs run within the server process and share the [`AppState`] with the
+//! request handlers. Each job is registered with a name and a period and is
+//! then run in its own task until the scheduler is stopped. The jobs of the
+//! services are collected in [`services::app_jobs`](crate::services::app_jobs).
+
+use std::{future::Future, pin::Pin, time::Duration};
+
+use crate::AppState;
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send>>;
+
+struct Job {
+    name: &'static str,
+    period: Duration,
+    run: Box<dyn Fn(AppState) -> JobFuture + Send + Sync>,
+}
+
+/// Collects the jobs before they are started.
+#[derive(Default)]
+pub struct Scheduler {
+    jobs: Vec<Job>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a job which will run once every `period`, starting right
+    /// after the scheduler is started. A failed run is reported and the job
+    /// is tried again after the next period.
+    pub fn job<F, Fut>(mut self, name: &'static str, period: Duration, job: F) -> Self
+    where
+        F: Fn(AppState) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    {
+        self.jobs.push(Job {
+            name,
+            period,
+            run: Box::new(move |state| Box::pin(job(state))),
+        });
+        self
+    }
+
+    /// Spawns the registered jobs. The jobs keep running until
+    /// [`SchedulerHandle::stop`] is called.
+    pub fn start(self, state: AppState) -> SchedulerHandle {
+        let (stop_tx, stop_rx) = tokio::sync::watch::channel(false);
+
+        let tasks = self
+            .jobs
+            .into_iter()
+            .map(|job| {
+                let state = state.clone();
+                let mut stop_rx = stop_rx.clone();
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(job.period);
+                    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                    loop {
+                        tokio::select! {
+                            _ = interval.tick() => {
+                                if let Err(err) = (job.run)(state.clone()).await {
+                                    eprintln!("scheduled job \"{}\" failed: {err}", job.name);
+                                }
+                            }
+                            _ = stop_rx.changed() => break,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        SchedulerHandle { stop_tx, tasks }
+    }
+}
+
+/// Allows stopping the started jobs.
+pub struct SchedulerHandle {
+    stop_tx: tokio::sync::watch::Sender<bool>,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl SchedulerHandle {
+    /// Stops the jobs and waits for the runs which are already in progress to
+    /// finish.
+    pub async fn stop(self) {
+        let _ = self.stop_tx.send(true);
+        for task in self.tasks {
+            let _ = task.await;
+        }
+    }
+}
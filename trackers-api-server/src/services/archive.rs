@@ -0,0 +1,347 @@
+//! Archive of the finished tasks and of the trackers no longer in use.
+//!
+//! The archived tasks and trackers are left out of the listings unless they
+//! are asked for with `archived=true`. Only the checkmarked tasks can be
+//! archived and uncheckmarking a task takes it out of the archive. The users
+//! can have their tasks archived automatically some days after they have been
+//! checkmarked, see [`services::settings`].
+
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::prelude::*;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/task/:task_id/archive",
+            routing::post_with(archive_task, |op| {
+                op.summary("Archive the task")
+                    .description("Only the checkmarked tasks can be archived.")
+            })
+            .put_with(archive_task, |op| {
+                op.summary("Archive the task")
+                    .description("Only the checkmarked tasks can be archived.")
+            })
+            .delete_with(unarchive_task, |op| op.summary("Take the task out of the archive"))
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::TASKS_READ,
+                    crate::auth::scope::permission::TASKS_WRITE,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Task Management"),
+        )
+        .api_route_with(
+            "/tracker/:tracker_id/archive",
+            routing::post_with(archive_tracker, |op| {
+                op.summary("Archive the tracker")
+                    .description("The tasks of an archived tracker are left out of the listing of all the tasks of the user. The default tracker cannot be archived.")
+            })
+            .put_with(archive_tracker, |op| {
+                op.summary("Archive the tracker")
+                    .description("The tasks of an archived tracker are left out of the listing of all the tasks of the user. The default tracker cannot be archived.")
+            })
+            .delete_with(unarchive_tracker, |op| {
+                op.summary("Take the tracker out of the archive")
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::TASKS_READ,
+                    crate::auth::scope::permission::TRACKERS_ADMIN,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Task Management"),
+        )
+}
+
+async fn find_owned_task(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    task_id: &Base62Uuid,
+    user_id: &models::types::Uuid,
+) -> Result<models::db::Task, ApiError> {
+    let (task, owned_by): (models::db::Task, models::types::Uuid) = db_schema::tasks::table
+        .inner_join(db_schema::trackers::table)
+        .filter(db_schema::tasks::task_id.eq(task_id))
+        .filter(db_schema::tasks::deleted_at.is_null())
+        .filter(db_schema::trackers::deleted_at.is_null())
+        .select((db_schema::tasks::all_columns, db_schema::trackers::user_id))
+        .first(db_conn)
+        .await?;
+    if owned_by != *user_id {
+        Err(ForbiddenError::default().with_msg("no access to the selected task"))?;
+    }
+    Ok(task)
+}
+
+async fn find_owned_tracker(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    tracker_id: &Base62Uuid,
+    user_id: &models::types::Uuid,
+) -> Result<models::Tracker, ApiError> {
+    let tracker: models::Tracker = db_schema::trackers::table
+        .filter(db_schema::trackers::deleted_at.is_null())
+        .find(tracker_id)
+        .get_result(db_conn)
+        .await?;
+    if tracker.user_id != *user_id {
+        Err(ForbiddenError::default().with_msg("no access to the selected task tracker"))?;
+    }
+    Ok(tracker)
+}
+
+async fn archive_task(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(task_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
+) -> Result<ModifiedResource<models::Task>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let mut task = find_owned_task(&mut db_conn, &task_id, &user_id.0).await?;
+    if task.completed_at.is_none() {
+        return Err(ConflictError::default()
+            .with_msg("only the checkmarked tasks can be archived")
+            .with_docs()
+            .with_links([("checkmark", format!("/api/task/{task_id}/checkmark"))])
+            .into());
+    }
+
+    if task.archived_at.is_none() {
+        let task_id = &task_id;
+        task = db_conn
+            .transaction::<_, ApiError, _>(|tx| {
+                async move {
+                    let task = diesel::update(db_schema::tasks::table)
+                        .filter(db_schema::tasks::task_id.eq(task_id))
+                        .set(db_schema::tasks::archived_at.eq(chrono::Utc::now()))
+                        .get_result(tx)
+                        .await?;
+                    services::audit::Event::new(
+                        &user_id.0,
+                        models::types::AuditAction::TaskUpdated,
+                    )
+                    .resource(format!("/api/task/{task_id}"))
+                    .details("archived")
+                    .record(tx, &actor)
+                    .await?;
+                    Ok(task)
+                }
+                .scope_boxed()
+            })
+            .await?;
+    }
+
+    let links = services::task::task_links(&task);
+
+    Ok(ModifiedResource {
+        location: Some(format!("/api/task/{task_id}")),
+        resource: Resource::new(task.into()).with_links(links),
+    })
+}
+
+async fn unarchive_task(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(task_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
+) -> Result<ModifiedResource<models::Task>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let mut task = find_owned_task(&mut db_conn, &task_id, &user_id.0).await?;
+    if task.archived_at.is_some() {
+        let task_id = &task_id;
+        task = db_conn
+            .transaction::<_, ApiError, _>(|tx| {
+                async move {
+                    let task = diesel::update(db_schema::tasks::table)
+                        .filter(db_schema::tasks::task_id.eq(task_id))
+                        .set(
+                            db_schema::tasks::archived_at
+                                .eq(Option::<chrono::DateTime<chrono::Utc>>::None),
+                        )
+                        .get_result(tx)
+                        .await?;
+                    services::audit::Event::new(
+                        &user_id.0,
+                        models::types::AuditAction::TaskUpdated,
+                    )
+                    .resource(format!("/api/task/{task_id}"))
+                    .details("taken out of the archive")
+                    .record(tx, &actor)
+                    .await?;
+                    Ok(task)
+                }
+                .scope_boxed()
+            })
+            .await?;
+    }
+
+    let links = services::task::task_links(&task);
+
+    Ok(ModifiedResource {
+        location: Some(format!("/api/task/{task_id}")),
+        resource: Resource::new(task.into()).with_links(links),
+    })
+}
+
+async fn archive_tracker(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(tracker_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
+) -> Result<ModifiedResource<models::Tracker>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let mut tracker = find_owned_tracker(&mut db_conn, &tracker_id, &user_id.0).await?;
+    if tracker.is_default.into() {
+        return Err(ForbiddenError::default()
+            .with_msg("the specified task tracker is considered the default task tracker for the user and as such it cannot be archived")
+            .with_docs()
+            .into());
+    }
+
+    if tracker.archived_at.is_none() {
+        let tracker_id = &tracker_id;
+        tracker = db_conn
+            .transaction::<_, ApiError, _>(|tx| {
+                async move {
+                    let tracker = diesel::update(db_schema::trackers::table)
+                        .filter(db_schema::trackers::tracker_id.eq(tracker_id))
+                        .set(db_schema::trackers::archived_at.eq(chrono::Utc::now()))
+                        .get_result(tx)
+                        .await?;
+                    services::audit::Event::new(
+                        &user_id.0,
+                        models::types::AuditAction::TrackerUpdated,
+                    )
+                    .resource(format!("/api/tracker/{tracker_id}"))
+                    .details("archived")
+                    .record(tx, &actor)
+                    .await?;
+                    Ok(tracker)
+                }
+                .scope_boxed()
+            })
+            .await?;
+    }
+
+    Ok(ModifiedResource {
+        location: Some(format!("/api/tracker/{tracker_id}")),
+        resource: Resource::new(tracker).with_links([
+            ("unarchive", format!("/api/tracker/{tracker_id}/archive")),
+            ("tasks", format!("/api/tracker/{tracker_id}/tasks")),
+            ("self", format!("/api/tracker/{tracker_id}")),
+        ]),
+    })
+}
+
+async fn unarchive_tracker(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(tracker_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
+) -> Result<ModifiedResource<models::Tracker>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let mut tracker = find_owned_tracker(&mut db_conn, &tracker_id, &user_id.0).await?;
+    if tracker.archived_at.is_some() {
+        let tracker_id = &tracker_id;
+        tracker = db_conn
+            .transaction::<_, ApiError, _>(|tx| {
+                async move {
+                    let tracker = diesel::update(db_schema::trackers::table)
+                        .filter(db_schema::trackers::tracker_id.eq(tracker_id))
+                        .set(
+                            db_schema::trackers::archived_at
+                                .eq(Option::<chrono::DateTime<chrono::Utc>>::None),
+                        )
+                        .get_result(tx)
+                        .await?;
+                    services::audit::Event::new(
+                        &user_id.0,
+                        models::types::AuditAction::TrackerUpdated,
+                    )
+                    .resource(format!("/api/tracker/{tracker_id}"))
+                    .details("taken out of the archive")
+                    .record(tx, &actor)
+                    .await?;
+                    Ok(tracker)
+                }
+                .scope_boxed()
+            })
+            .await?;
+    }
+
+    Ok(ModifiedResource {
+        location: Some(format!("/api/tracker/{tracker_id}")),
+        resource: Resource::new(tracker).with_links([
+            ("archive", format!("/api/tracker/{tracker_id}/archive")),
+            ("tasks", format!("/api/tracker/{tracker_id}/tasks")),
+            ("self", format!("/api/tracker/{tracker_id}")),
+        ]),
+    })
+}
+
+/// Archives the tasks of the users who have chosen to archive them
+/// automatically once they have been checkmarked for long enough.
+pub(crate) async fn archive_finished_tasks(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+
+    let settings: Vec<models::db::UserSettings> = db_schema::user_settings::table
+        .filter(db_schema::user_settings::auto_archive_after_days.is_not_null())
+        .load(&mut db_conn)
+        .await?;
+
+    let now = chrono::Utc::now();
+    for settings in settings {
+        let Some(days) = settings.auto_archive_after_days else {
+            continue;
+        };
+        diesel::update(db_schema::tasks::table)
+            .filter(
+                db_schema::tasks::completed_at
+                    .lt(now - chrono::Duration::days(days.into()))
+                    .and(db_schema::tasks::archived_at.is_null())
+                    .and(db_schema::tasks::deleted_at.is_null())
+                    .and(
+                        db_schema::tasks::tracker_id.eq_any(
+                            db_schema::trackers::table
+                                .filter(db_schema::trackers::user_id.eq(&settings.user_id))
+                                .select(db_schema::trackers::tracker_id),
+                        ),
+                    ),
+            )
+            .set(db_schema::tasks::archived_at.eq(now))
+            .execute(&mut db_conn)
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,228 @@
+//! Audit log of the security-relevant and data-changing events of an account.
+//!
+//! The handlers record the events with [`Event`] giving the [`Actor`] which
+//! made the request, so that it can be told later which client application
+//! or personal access token has changed something.
+
+use axum::async_trait;
+use models::types::AuditAction;
+
+use crate::prelude::*;
+
+/// How long the events are kept.
+const RETENTION_DAYS: i64 = 365;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+const MAX_PAGE_SIZE: i64 = 200;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/user/:email/audit",
+            routing::get_with(get_audit_log, |op| {
+                op.summary("Fetch the audit log of the account")
+                    .description("Lists the logins, the issued and revoked tokens, the changes of the authorised clients and the changes of the tasks and trackers, the most recent first. The events are kept for a year.")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            crate::auth::scope::RequiredScope::new(
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+            ),
+            crate::auth::scope::require_scope,
+        ))
+        .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()))
+}
+
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone)]
+pub struct AuditQuery {
+    /// Limits the results to the events of the given kind
+    pub action: Option<AuditAction>,
+    /// Limits the results to the events caused by the client application or
+    /// the personal access token with the given name
+    pub client: Option<String>,
+    /// Limits the results to the events which happened at or after the time
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Limits the results to the events which happened before the time
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Number of the events to return, 50 by default and 200 at most
+    pub limit: Option<i64>,
+    /// Number of the most recent matching events to skip
+    pub offset: Option<i64>,
+}
+
+/// Who made the request, saved along with the recorded events.
+#[derive(Debug, Clone, Default)]
+pub struct Actor {
+    /// Name of the client application or of the personal access token, empty
+    /// for the sessions started by the user directly
+    pub client: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl Actor {
+    /// The same actor acting through the given client.
+    pub fn with_client(&self, client: Option<String>) -> Self {
+        Self {
+            client,
+            ip_address: self.ip_address.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl axum::extract::FromRequestParts<AppState> for Actor {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ip_address = crate::client_addr::ClientAddr::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|crate::client_addr::ClientAddr(addr)| addr.to_string());
+
+        // the claims are there once the JWT layer has verified the token
+        let client = parts
+            .extensions
+            .get::<crate::auth::UserClaims>()
+            .and_then(|claims| claims.client().map(ToOwned::to_owned));
+
+        Ok(Self { client, ip_address })
+    }
+}
+
+impl aide::OperationInput for Actor {}
+
+/// An event about to be recorded in the audit log of a user.
+pub struct Event {
+    user_id: models::types::Uuid,
+    action: AuditAction,
+    resource: Option<String>,
+    details: Option<String>,
+}
+
+impl Event {
+    pub fn new(user_id: &models::types::Uuid, action: AuditAction) -> Self {
+        Self {
+            user_id: user_id.clone(),
+            action,
+            resource: None,
+            details: None,
+        }
+    }
+    /// Path of the affected resource.
+    pub fn resource(mut self, resource: impl Display) -> Self {
+        self.resource = Some(resource.to_string());
+        self
+    }
+    pub fn details(mut self, details: impl Display) -> Self {
+        self.details = Some(details.to_string());
+        self
+    }
+    pub async fn record(
+        self,
+        db_conn: &mut diesel_async::AsyncPgConnection,
+        actor: &Actor,
+    ) -> Result<(), ApiError> {
+        diesel::insert_into(db_schema::audit_events::table)
+            .values(models::db::AuditEvent {
+                event_id: models::types::Uuid::new(),
+                user_id: self.user_id,
+                action: self.action,
+                resource: self.resource,
+                client: actor.client.clone(),
+                ip_address: actor.ip_address.clone(),
+                details: self.details,
+                occurred_at: chrono::Utc::now(),
+            })
+            .execute(db_conn)
+            .await?;
+        Ok(())
+    }
+}
+
+async fn get_audit_log(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    QsQuery(query): QsQuery<AuditQuery>,
+) -> Result<Resource<Vec<models::AuditEvent>>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let owner: models::types::Uuid = db_schema::users::table
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email)),
+        )
+        .select(db_schema::users::user_id)
+        .first(&mut db_conn)
+        .await?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) || offset < 0 {
+        return Err(BadRequestError::default()
+            .with_msg(format!(
+                "the limit must be between 1 and {MAX_PAGE_SIZE} and the offset cannot be negative"
+            ))
+            .with_docs()
+            .into());
+    }
+
+    let mut events_query = db_schema::audit_events::table
+        .filter(db_schema::audit_events::user_id.eq(owner))
+        .into_boxed();
+    if let Some(action) = query.action {
+        events_query = events_query.filter(db_schema::audit_events::action.eq(action));
+    }
+    if let Some(client) = query.client.clone() {
+        events_query = events_query.filter(db_schema::audit_events::client.eq(client));
+    }
+    if let Some(since) = query.since {
+        events_query = events_query.filter(db_schema::audit_events::occurred_at.ge(since));
+    }
+    if let Some(until) = query.until {
+        events_query = events_query.filter(db_schema::audit_events::occurred_at.lt(until));
+    }
+
+    let events: Vec<models::db::AuditEvent> = events_query
+        .order(db_schema::audit_events::occurred_at.desc())
+        .limit(limit)
+        .offset(offset)
+        .load(&mut db_conn)
+        .await?;
+
+    let mut links = vec![("self", format!("/api/user/{email}/audit"))];
+    if events.len() as i64 == limit {
+        let next_page = AuditQuery {
+            limit: Some(limit),
+            offset: Some(offset + limit),
+            ..query
+        };
+        if let Ok(next_page) = qs::to_string(&next_page) {
+            links.push(("next", format!("/api/user/{email}/audit?{next_page}")));
+        }
+    }
+
+    Ok(Resource::new(events.into_iter().map(|event| event.into()).collect()).with_links(links))
+}
+
+pub(crate) async fn purge_old_events(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::audit_events::table)
+        .filter(
+            db_schema::audit_events::occurred_at
+                .lt(chrono::Utc::now() - chrono::Duration::days(RETENTION_DAYS)),
+        )
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
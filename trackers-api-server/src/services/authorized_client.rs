@@ -1,7 +1,12 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
 use models::{AuthorisedClient, AuthorisedClientFull};
 
 use crate::prelude::*;
 
+/// How long the replaced secret keeps working after a rotation so that the
+/// client can be updated without downtime.
+pub const SECRET_ROTATION_OVERLAP_HOURS: i64 = 24;
+
 pub fn router() -> ApiRouter<AppState> {
     ApiRouter::new()
         .api_route_with(
@@ -14,29 +19,68 @@ pub fn router() -> ApiRouter<AppState> {
             }),
             |op| op.tag("Authorizing Client Applications"),
         )
-        .route(
-            "/user/:email/authorised_client/:client_id",
-            routing::get(use_post_instead),
-        )
         .api_route_with(
             "/user/:email/authorised_client/:client_id",
-            routing::post_with(get_full_athorised_client, |op| {
+            routing::get_with(get_authorised_client, |op| {
                 op.summary("Fetch authorised client details")
-                    .description("Note that issueing this request with GET method will result in an error. This is because we do not want to have that request to be stored in cache of any browser software.")
+                    .description("The secret of the client is shown only when it is generated.")
             })
             .delete_with(remove_authorisation, |op| op.summary("Unauthorise application")),
             |op| op.tag("Authorizing Client Applications"),
         )
+        .api_route_with(
+            "/user/:email/authorised_client/:client_id/secret",
+            routing::post_with(rotate_client_secret, |op| {
+                op.summary("Generate a new client secret")
+                    .description("The previous secret keeps working for 24 hours so that the client can be updated in the meantime. Rotating the secret again ends that period early.")
+            }),
+            |op| op.tag("Authorizing Client Applications"),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            crate::auth::scope::RequiredScope::new(
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+            ),
+            crate::auth::scope::require_scope,
+        ))
         .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()))
 }
 
+/// Whether the URL can be safely linked to, which rules out the schemes like
+/// `javascript:` or `data:`.
+pub(crate) fn is_web_url(url: &str) -> bool {
+    reqwest::Url::parse(url).map_or(false, |url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// The codes must not travel over plain HTTP, except to the native
+/// applications listening on the loopback interface (RFC 8252).
+fn is_allowed_redirect_uri(uri: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(uri) else {
+        return false;
+    };
+    match (url.scheme(), url.host_str()) {
+        ("https", Some(_)) => true,
+        ("http", Some(host)) => {
+            host == "localhost"
+                || host
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse::<std::net::IpAddr>()
+                    .map_or(false, |ip| ip.is_loopback())
+        }
+        _ => false,
+    }
+}
+
 async fn authorize_new_client(
     State(state): State<AppState>,
     crate::auth::VariableScope(user_id): crate::auth::VariableScope<
         crate::auth::scope::UserIdScope,
         crate::auth::UserClaims,
     >,
+    caller: crate::auth::UserClaims,
     axum::extract::Path(email): axum::extract::Path<EmailAddress>, // whacky thing here, that email is not checked for validity with the user_id
+    actor: services::audit::Actor,
     json: JsonExtract<models::ClientCredentialsRequest>,
 ) -> Result<CreatedResource<models::AuthorisedClientFull>, ApiError> {
     let mut db_conn = state.db.get().await?;
@@ -50,10 +94,66 @@ async fn authorize_new_client(
             .into());
     }
 
+    if !is_web_url(&client_request.website) {
+        return Err(BadRequestError::default()
+            .with_msg("the website must be an http or https URL")
+            .with_docs()
+            .into());
+    }
+
+    if let Some(invalid_uri) = client_request
+        .redirect_uris
+        .iter()
+        .find(|uri| !is_allowed_redirect_uri(uri))
+    {
+        return Err(BadRequestError::default()
+            .with_msg(format!(
+                "{invalid_uri} is not a valid redirect URI, it must be an absolute https URI or an http URI of the loopback interface"
+            ))
+            .with_docs()
+            .into());
+    }
+
+    let Some(scope) = crate::auth::scope::parse_requested(client_request.scope.as_deref()) else {
+        return Err(BadRequestError::default()
+            .with_msg("the scope contains unknown scope names")
+            .with_docs()
+            .into());
+    };
+    if let Some(not_held) = crate::auth::scope::first_not_held(&scope, &caller.scope_list()) {
+        return Err(ForbiddenError::default()
+            .with_msg(format!(
+                "the authorization token does not grant the {not_held} scope so it cannot be given to the client"
+            ))
+            .with_docs()
+            .into());
+    }
+
     let authorised_client = client_request.to_authorised_client();
-    diesel::insert_into(db_schema::authorised_clients::table)
-        .values(&authorised_client)
-        .execute(&mut db_conn)
+    let new_client = models::db::AuthorisedClient::new(&authorised_client);
+    let event = services::audit::Event::new(
+        &authorised_client.user_id,
+        models::types::AuditAction::ClientAuthorised,
+    )
+    .resource(format!(
+        "/api/user/{email}/authorised_client/{}",
+        authorised_client.client_id
+    ))
+    .details(format!(
+        "client \"{}\", scope: {}",
+        authorised_client.name, authorised_client.scope
+    ));
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                diesel::insert_into(db_schema::authorised_clients::table)
+                    .values(new_client)
+                    .execute(tx)
+                    .await?;
+                event.record(tx, &actor).await
+            }
+            .scope_boxed()
+        })
         .await?;
 
     Ok(CreatedResource {
@@ -65,44 +165,100 @@ async fn authorize_new_client(
     })
 }
 
-async fn use_post_instead(
+async fn get_authorised_client(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
     axum::extract::Path((email, client_id)): axum::extract::Path<(EmailAddress, String)>,
-) -> ApiError {
-    BadRequestError::default()
-        .with_msg(
-            "POST request should be used to avoid accidentially saving sensitive details in cache",
+) -> Result<Resource<AuthorisedClient>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let authorised_client: models::db::AuthorisedClient = db_schema::authorised_clients::table
+        .inner_join(db_schema::users::table)
+        .filter(
+            db_schema::users::user_id
+                .eq(user_id.0)
+                .and(db_schema::users::email.eq(email))
+                .and(db_schema::authorised_clients::client_id.eq(client_id)),
         )
-        .with_docs()
-        .with_links([(
-            "authorised client details",
-            format!("/api/user/{email}/authorised_client/{client_id}"),
-        )])
-        .into()
+        .select(db_schema::authorised_clients::all_columns)
+        .get_result(&mut db_conn)
+        .await?;
+
+    Ok(Resource::new(authorised_client.into()))
 }
 
-async fn get_full_athorised_client(
+async fn rotate_client_secret(
     State(state): State<AppState>,
     crate::auth::VariableScope(user_id): crate::auth::VariableScope<
         crate::auth::scope::UserIdScope,
         crate::auth::UserClaims,
     >,
     axum::extract::Path((email, client_id)): axum::extract::Path<(EmailAddress, String)>,
+    actor: services::audit::Actor,
 ) -> Result<Resource<AuthorisedClientFull>, ApiError> {
     let mut db_conn = state.db.get().await?;
 
-    let authorised_client = db_schema::authorised_clients::table
+    let client: models::db::AuthorisedClient = db_schema::authorised_clients::table
         .inner_join(db_schema::users::table)
         .filter(
             db_schema::users::user_id
                 .eq(user_id.0)
-                .and(db_schema::users::email.eq(email))
+                .and(db_schema::users::email.eq(&email))
                 .and(db_schema::authorised_clients::client_id.eq(client_id)),
         )
         .select(db_schema::authorised_clients::all_columns)
         .get_result(&mut db_conn)
         .await?;
 
-    Ok(Resource::new(authorised_client))
+    let new_secret = models::types::ClientSecretStr::new();
+    let previous_secret_valid_until =
+        chrono::Utc::now() + chrono::Duration::hours(SECRET_ROTATION_OVERLAP_HOURS);
+
+    let rotated_client_id = client.client_id.clone();
+    let client_secret_hash = new_secret.to_storeable();
+    let previous_secret_hash = client.client_secret_hash.clone();
+    let event = services::audit::Event::new(
+        &client.user_id,
+        models::types::AuditAction::ClientSecretRotated,
+    )
+    .resource(format!(
+        "/api/user/{email}/authorised_client/{}",
+        client.client_id
+    ))
+    .details(format!("client \"{}\"", client.name));
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                diesel::update(db_schema::authorised_clients::table)
+                    .filter(db_schema::authorised_clients::client_id.eq(rotated_client_id))
+                    .set((
+                        db_schema::authorised_clients::client_secret_hash.eq(client_secret_hash),
+                        db_schema::authorised_clients::previous_secret_hash
+                            .eq(previous_secret_hash),
+                        db_schema::authorised_clients::previous_secret_valid_until
+                            .eq(previous_secret_valid_until),
+                    ))
+                    .execute(tx)
+                    .await?;
+                event.record(tx, &actor).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(Resource::new(AuthorisedClientFull {
+        user_id: client.user_id,
+        name: client.name,
+        website: client.website,
+        client_id: client.client_id,
+        client_secret: new_secret,
+        redirect_uris: client.redirect_uris,
+        scope: client.scope,
+        previous_secret_valid_until: Some(previous_secret_valid_until),
+    }))
 }
 
 async fn remove_authorisation(
@@ -112,19 +268,41 @@ async fn remove_authorisation(
         crate::auth::UserClaims,
     >,
     axum::extract::Path((email, client_id)): axum::extract::Path<(EmailAddress, String)>,
+    actor: services::audit::Actor,
 ) -> Result<DeletedResource, ApiError> {
     let mut db_conn = state.db.get().await?;
 
-    diesel::delete(db_schema::authorised_clients::table)
-        .filter(
-            db_schema::users::table
-                .filter(db_schema::users::email.eq(email))
-                .select(db_schema::users::user_id)
-                .single_value()
-                .eq(user_id.0),
-        )
-        .filter(db_schema::authorised_clients::client_id.eq(client_id))
-        .execute(&mut db_conn)
+    let (user_id, email, client_id, actor) = (&user_id.0, &email, &client_id, &actor);
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let removed: Vec<String> = diesel::delete(db_schema::authorised_clients::table)
+                    .filter(
+                        db_schema::users::table
+                            .filter(db_schema::users::email.eq(email))
+                            .select(db_schema::users::user_id)
+                            .single_value()
+                            .eq(user_id),
+                    )
+                    .filter(db_schema::authorised_clients::client_id.eq(client_id))
+                    .returning(db_schema::authorised_clients::name)
+                    .get_results(tx)
+                    .await?;
+
+                for name in removed {
+                    services::audit::Event::new(
+                        user_id,
+                        models::types::AuditAction::ClientUnauthorised,
+                    )
+                    .resource(format!("/api/user/{email}/authorised_client/{client_id}"))
+                    .details(format!("client \"{name}\""))
+                    .record(tx, actor)
+                    .await?;
+                }
+                Ok(())
+            }
+            .scope_boxed()
+        })
         .await?;
 
     Ok(DeletedResource::default())
@@ -140,7 +318,7 @@ async fn get_authorised_clients(
 ) -> Result<Resource<Vec<models::AuthorisedClient>>, ApiError> {
     let mut db_conn = state.db.get().await?;
 
-    let authorised_clients: Vec<models::AuthorisedClientFull> =
+    let authorised_clients: Vec<models::db::AuthorisedClient> =
         db_schema::authorised_clients::table
             .inner_join(db_schema::users::table)
             .filter(
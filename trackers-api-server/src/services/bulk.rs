@@ -0,0 +1,338 @@
+//! Bulk changes of the tasks of a user.
+//!
+//! The tasks are selected either by their IDs or with the same query as the
+//! one accepted by the listing of all the tasks of the user. All the changes
+//! are made in a single transaction, with the selected tasks locked, and
+//! reported task by task in the order the tasks have been requested.
+
+use std::collections::HashSet;
+
+use diesel_async::scoped_futures::ScopedFutureExt;
+use models::{BulkAction, BulkItemResult, BulkOutcome};
+
+use crate::prelude::*;
+
+/// The most tasks which can be changed with a single request.
+const MAX_TASKS: usize = 1000;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/user/:email/tasks/bulk",
+            routing::post_with(bulk_change_tasks, |op| {
+                op.summary("Change many tasks at once")
+                    .description("Applies the action to each of the selected tasks in a single transaction. The tasks which have nothing to change are skipped and the requested tasks which cannot be found are reported as failed without stopping the others. At most 1000 tasks can be changed with a single request.")
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::TASKS_READ,
+                    crate::auth::scope::permission::TASKS_WRITE,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Task Management"),
+        )
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkSelection {
+    /// IDs of the tasks to change
+    TaskIds(Vec<Base62Uuid>),
+    /// Changes the tasks matching the query, just like the ones listed with
+    /// the same query by `GET /api/user/:email/tasks`
+    Query(crate::query_param::TasksQuery),
+}
+
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+pub struct BulkRequest {
+    #[serde(flatten)]
+    pub selection: BulkSelection,
+    pub action: BulkAction,
+}
+
+/// What has happened to a single task.
+enum Applied {
+    /// The task has been changed, holds the details for the audit log
+    Changed(String),
+    /// There was nothing to change, holds the reason
+    Unchanged(&'static str),
+}
+
+async fn bulk_change_tasks(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    actor: services::audit::Actor,
+    json: JsonExtract<BulkRequest>,
+) -> Result<Resource<models::BulkReport>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let request = json.extract();
+
+    let owner: models::types::Uuid = db_schema::users::table
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email)),
+        )
+        .select(db_schema::users::user_id)
+        .first(&mut db_conn)
+        .await?;
+
+    let selection = match request.selection {
+        BulkSelection::TaskIds(mut task_ids) => {
+            let mut seen = HashSet::new();
+            task_ids.retain(|task_id| seen.insert(task_id.clone()));
+            if task_ids.len() > MAX_TASKS {
+                return Err(BadRequestError::default()
+                    .with_msg(format!("at most {MAX_TASKS} tasks can be changed at once"))
+                    .with_docs()
+                    .into());
+            }
+            BulkSelection::TaskIds(task_ids)
+        }
+        query => query,
+    };
+
+    let action = request.action;
+    let results = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                if let BulkAction::Move { tracker_id } = &action {
+                    // kept from being deleted until the tasks are moved
+                    let destination_owned = db_schema::trackers::table
+                        .filter(
+                            db_schema::trackers::tracker_id
+                                .eq(tracker_id)
+                                .and(db_schema::trackers::user_id.eq(&owner))
+                                .and(db_schema::trackers::deleted_at.is_null()),
+                        )
+                        .for_share()
+                        .execute(tx)
+                        .await?;
+                    if destination_owned < 1 {
+                        Err(ForbiddenError::default()
+                            .with_msg("no access to the destination tracker")
+                            .with_docs())?;
+                    }
+                }
+
+                let task_ids = match selection {
+                    BulkSelection::TaskIds(task_ids) => task_ids,
+                    BulkSelection::Query(query) => {
+                        let mut tasks_query = db_schema::trackers::table
+                            .filter(db_schema::trackers::user_id.eq(&owner))
+                            .filter(db_schema::trackers::deleted_at.is_null())
+                            .inner_join(db_schema::tasks::table)
+                            .select(db_schema::tasks::task_id)
+                            .into_boxed();
+                        if !query.archived.unwrap_or(false) {
+                            tasks_query =
+                                tasks_query.filter(db_schema::trackers::archived_at.is_null());
+                        }
+                        tasks_query = tasks_query.filter(query.into_join_filters());
+
+                        let task_ids: Vec<Base62Uuid> =
+                            tasks_query.limit(MAX_TASKS as i64 + 1).load(tx).await?;
+                        if task_ids.len() > MAX_TASKS {
+                            return Err(BadRequestError::default()
+                                .with_msg(format!(
+                                    "the query matches more than {MAX_TASKS} tasks, narrow it down"
+                                ))
+                                .with_docs()
+                                .into());
+                        }
+                        task_ids
+                    }
+                };
+
+                // locked so that the tasks cannot change between the checks
+                // of the actions and the updates
+                let owned_trackers = db_schema::trackers::table
+                    .filter(db_schema::trackers::user_id.eq(&owner))
+                    .filter(db_schema::trackers::deleted_at.is_null())
+                    .select(db_schema::trackers::tracker_id);
+                let found: Vec<models::db::Task> = db_schema::tasks::table
+                    .filter(db_schema::tasks::task_id.eq_any(&task_ids))
+                    .filter(db_schema::tasks::tracker_id.eq_any(owned_trackers))
+                    .filter(db_schema::tasks::deleted_at.is_null())
+                    .for_update()
+                    .load(tx)
+                    .await?;
+
+                let mut results = Vec::with_capacity(task_ids.len());
+                for task_id in task_ids {
+                    // the tasks of the other users are reported just like the
+                    // missing ones so that it cannot be told whether they exist
+                    let Some(task) = found.iter().find(|task| task.task_id == task_id) else {
+                        results.push(BulkItemResult {
+                            task_id,
+                            outcome: BulkOutcome::Failed,
+                            reason: Some("no such task".to_owned()),
+                        });
+                        continue;
+                    };
+                    let result = match apply_action(tx, task, &action).await? {
+                        Applied::Changed(details) => {
+                            let audit_action = match action {
+                                BulkAction::Delete => models::types::AuditAction::TaskDeleted,
+                                _ => models::types::AuditAction::TaskUpdated,
+                            };
+                            services::audit::Event::new(&owner, audit_action)
+                                .resource(format!("/api/task/{task_id}"))
+                                .details(details)
+                                .record(tx, &actor)
+                                .await?;
+                            BulkItemResult {
+                                task_id,
+                                outcome: BulkOutcome::Updated,
+                                reason: None,
+                            }
+                        }
+                        Applied::Unchanged(reason) => BulkItemResult {
+                            task_id,
+                            outcome: BulkOutcome::Skipped,
+                            reason: Some(reason.to_owned()),
+                        },
+                    };
+                    results.push(result);
+                }
+                Ok(results)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    let count = |outcome| {
+        results
+            .iter()
+            .filter(|result| result.outcome == outcome)
+            .count()
+    };
+    let report = models::BulkReport {
+        updated: count(BulkOutcome::Updated),
+        skipped: count(BulkOutcome::Skipped),
+        failed: count(BulkOutcome::Failed),
+        results,
+    };
+
+    Ok(Resource::new(report).with_links([("tasks", format!("/api/user/{email}/tasks"))]))
+}
+
+async fn apply_action(
+    tx: &mut diesel_async::AsyncPgConnection,
+    task: &models::db::Task,
+    action: &BulkAction,
+) -> Result<Applied, ApiError> {
+    let the_task =
+        diesel::update(db_schema::tasks::table).filter(db_schema::tasks::task_id.eq(&task.task_id));
+
+    let details = match action {
+        BulkAction::Checkmark => {
+            if task.completed_at.is_some() {
+                return Ok(Applied::Unchanged("already checkmarked"));
+            }
+            the_task
+                .set(db_schema::tasks::completed_at.eq(chrono::Utc::now()))
+                .execute(tx)
+                .await?;
+            "marked done in bulk".to_owned()
+        }
+        BulkAction::Uncheckmark => {
+            if task.completed_at.is_none() {
+                return Ok(Applied::Unchanged("not checkmarked"));
+            }
+            the_task
+                .set(
+                    db_schema::tasks::completed_at
+                        .eq(Option::<chrono::DateTime<chrono::Utc>>::None),
+                )
+                .execute(tx)
+                .await?;
+            "unmarked as done in bulk".to_owned()
+        }
+        BulkAction::AddTags(added) => {
+            let mut tags = task.tags.clone().unwrap_or_default();
+            let before = tags.0.len();
+            for tag in &added.0 {
+                if !tags.0.contains(tag) {
+                    tags.0.push(tag.clone());
+                }
+            }
+            if tags.0.len() == before {
+                return Ok(Applied::Unchanged("already has all the tags"));
+            }
+            the_task
+                .set(db_schema::tasks::tags.eq(Some(tags)))
+                .execute(tx)
+                .await?;
+            format!("tags {} added in bulk", added.to_string())
+        }
+        BulkAction::RemoveTags(removed) => {
+            let mut tags = task.tags.clone().unwrap_or_default();
+            let before = tags.0.len();
+            tags.0.retain(|tag| !removed.0.contains(tag));
+            if tags.0.len() == before {
+                return Ok(Applied::Unchanged("has none of the tags"));
+            }
+            let tags = if tags.0.is_empty() { None } else { Some(tags) };
+            the_task
+                .set(db_schema::tasks::tags.eq(tags))
+                .execute(tx)
+                .await?;
+            format!("tags {} removed in bulk", removed.to_string())
+        }
+        BulkAction::Move { tracker_id } => {
+            if task.tracker_id == *tracker_id {
+                return Ok(Applied::Unchanged("already in the tracker"));
+            }
+            the_task
+                .set(db_schema::tasks::tracker_id.eq(tracker_id))
+                .execute(tx)
+                .await?;
+            format!(
+                "moved from the tracker {} to {tracker_id} in bulk",
+                task.tracker_id
+            )
+        }
+        BulkAction::SetDeadline { kind, deadline } => {
+            let current = match kind {
+                models::DeadlineKind::Soft => task.soft_deadline,
+                models::DeadlineKind::Hard => task.hard_deadline,
+            };
+            if current == *deadline {
+                return Ok(Applied::Unchanged("the deadline is already set"));
+            }
+            match kind {
+                models::DeadlineKind::Soft => {
+                    the_task
+                        .set(db_schema::tasks::soft_deadline.eq(deadline))
+                        .execute(tx)
+                        .await?
+                }
+                models::DeadlineKind::Hard => {
+                    the_task
+                        .set(db_schema::tasks::hard_deadline.eq(deadline))
+                        .execute(tx)
+                        .await?
+                }
+            };
+            "deadline changed in bulk".to_owned()
+        }
+        BulkAction::Delete => {
+            the_task
+                .set(db_schema::tasks::deleted_at.eq(chrono::Utc::now()))
+                .execute(tx)
+                .await?;
+            "moved to the trash in bulk".to_owned()
+        }
+    };
+
+    Ok(Applied::Changed(details))
+}
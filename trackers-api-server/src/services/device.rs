@@ -0,0 +1,394 @@
+//! OAuth2 device authorization grant (RFC 8628).
+//!
+//! A device without a convenient way to type in the password starts the
+//! authorization at `/api/session/device` and shows the returned user code to
+//! the user. The user approves the code on another device, either on the
+//! `/oauth/device` page or through the API while logged in. Meanwhile the
+//! device polls `/api/session/token` with the device code until the
+//! authorization is approved, denied or expires.
+
+use rand::Rng;
+
+use crate::prelude::*;
+
+/// How long the user has to approve the device.
+const DEVICE_CODE_LIFETIME_SECONDS: i64 = 10 * 60;
+
+const DEVICE_CODE_LENGTH: usize = 48;
+
+/// How long the device has to wait between the polls at first. Polling too
+/// often makes the interval longer by the same amount.
+const POLL_INTERVAL_SECONDS: i32 = 5;
+
+/// Consonants only, so that the codes are hard to mistype and never spell
+/// a word.
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+
+const USER_CODE_LENGTH: usize = 8;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/session/device",
+            routing::post_with(start_device_authorization, |op| {
+                op.summary("Start device authorization")
+                    .description("Works as described in RFC 8628. The device shows the user code to the user and polls `/api/session/token` with the `urn:ietf:params:oauth:grant-type:device_code` grant until the user approves it. While waiting the polls fail with `authorization_pending` or `slow_down` given in the `error` field. Without a `client_id` the device may ask only for the permissions, `tasks:read` by default.")
+            }),
+            |op| op.tag("Log-in"),
+        )
+        .api_route_with(
+            "/user/:email/device_authorizations",
+            routing::post_with(decide_device_authorization, |op| {
+                op.summary("Approve or deny device authorization")
+                    .description("Lets a logged-in user approve a device showing the given user code.")
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::ACCOUNT_ADMIN,
+                    crate::auth::scope::permission::ACCOUNT_ADMIN,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Account Management"),
+        )
+}
+
+/// Device authorization request as described in RFC 8628.
+#[derive(Deserialize, JsonSchema)]
+pub struct DeviceAuthorizationRequest {
+    /// ID of a registered client, the session started by the device is named
+    /// after the client
+    pub client_id: Option<models::types::ClientSecretStr>,
+    /// Space separated list of the requested scopes. All the scopes of the
+    /// client by default, or just `tasks:read` without a client.
+    pub scope: Option<String>,
+}
+
+/// Device authorization response as described in RFC 8628.
+#[derive(Serialize, JsonSchema)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    /// The code which the user has to enter at the `verification_uri`
+    pub user_code: String,
+    pub verification_uri: String,
+    /// The `verification_uri` with the `user_code` already filled in
+    pub verification_uri_complete: String,
+    /// Time until the codes expire in seconds
+    pub expires_in: u32,
+    /// Minimal number of seconds between the polls
+    pub interval: u32,
+}
+
+/// The decision of the user about a device showing the `user_code`.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeviceDecision {
+    pub user_code: String,
+    pub approve: bool,
+}
+
+async fn start_device_authorization(
+    State(state): State<AppState>,
+    host: Option<axum::TypedHeader<axum::headers::Host>>,
+    axum::extract::Form(request): axum::extract::Form<DeviceAuthorizationRequest>,
+) -> Result<Json<DeviceAuthorization>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let (client_scope, default_scope) = match &request.client_id {
+        Some(client_id) => {
+            let client_search_res: Result<models::db::AuthorisedClient, _> =
+                db_schema::authorised_clients::table
+                    .filter(db_schema::authorised_clients::client_id.eq(client_id))
+                    .first(&mut db_conn)
+                    .await;
+            match client_search_res {
+                Ok(client) => (client.scope.clone(), client.scope),
+                Err(diesel::result::Error::NotFound) => {
+                    return Err(oauth_error(
+                        "invalid_client",
+                        "the client is not registered",
+                    ))
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        // Anyone can start the authorization without a client and trick a
+        // user into approving it, so the full access is never handed out
+        None => (
+            crate::auth::scope::permission::ALL.join(" "),
+            crate::auth::scope::permission::TASKS_READ.to_owned(),
+        ),
+    };
+
+    let requested = request
+        .scope
+        .as_deref()
+        .filter(|scope| !scope.trim().is_empty())
+        .unwrap_or(&default_scope);
+    let scope = match crate::auth::scope::parse_requested(Some(requested)) {
+        Some(scope)
+            if scope
+                .iter()
+                .all(|name| crate::auth::scope::is_covered(name, &client_scope)) =>
+        {
+            scope
+        }
+        _ => {
+            return Err(oauth_error(
+                "invalid_scope",
+                "the requested scope is unknown or exceeds the scope of the client",
+            ))
+        }
+    };
+
+    let device_code = crate::auth::opaque::generate(DEVICE_CODE_LENGTH);
+    let mut attempts = 0;
+    let user_code = loop {
+        let user_code = generate_user_code();
+        let insert_res = diesel::insert_into(db_schema::device_authorizations::table)
+            .values(models::db::DeviceAuthorization {
+                device_code_hash: crate::auth::opaque::hash(&device_code),
+                user_code: user_code.clone(),
+                client_id: request.client_id.clone(),
+                scope: scope.join(" "),
+                user_id: None,
+                denied: false,
+                poll_interval: POLL_INTERVAL_SECONDS,
+                last_polled_at: None,
+                expires_at: chrono::Utc::now()
+                    .checked_add_signed(chrono::Duration::seconds(DEVICE_CODE_LIFETIME_SECONDS))
+                    .unwrap(),
+            })
+            .execute(&mut db_conn)
+            .await;
+        match insert_res {
+            Ok(_) => break user_code,
+            // Another device has been given the same user code
+            Err(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            )) if attempts < 3 => attempts += 1,
+            Err(err) => return Err(err.into()),
+        }
+    };
+
+    let host = host.map(|axum::TypedHeader(host)| host);
+    let verification_uri = format!("{}/oauth/device", crate::public_url(host.as_ref()));
+    let user_code = display_user_code(&user_code);
+    Ok(Json(DeviceAuthorization {
+        device_code,
+        verification_uri_complete: format!("{verification_uri}?user_code={user_code}"),
+        verification_uri,
+        user_code,
+        expires_in: DEVICE_CODE_LIFETIME_SECONDS as u32,
+        interval: POLL_INTERVAL_SECONDS as u32,
+    }))
+}
+
+async fn decide_device_authorization(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    json: JsonExtract<DeviceDecision>,
+) -> Result<Resource<DeviceDecision>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let decision = json.extract();
+
+    let Some(pending) = find_pending(&mut db_conn, &decision.user_code).await? else {
+        return Err(NotFoundError::default()
+            .with_msg("the user code is not valid or has expired")
+            .into());
+    };
+    decide(&mut db_conn, &pending, &user_id.0, decision.approve).await?;
+
+    Ok(Resource::new(decision).with_links([("sessions", format!("/api/user/{email}/sessions"))]))
+}
+
+fn generate_user_code() -> String {
+    let mut rng = rand::rngs::OsRng;
+    (0..USER_CODE_LENGTH)
+        .map(|_| USER_CODE_ALPHABET[rng.gen_range(0..USER_CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Splits the code in two halves to make it easier to read.
+pub(crate) fn display_user_code(user_code: &str) -> String {
+    let (first, second) = user_code.split_at(user_code.len() / 2);
+    format!("{first}-{second}")
+}
+
+/// Users may type in the code in lowercase or without the separator.
+fn normalize_user_code(user_code: &str) -> String {
+    user_code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|ch| ch.to_ascii_uppercase())
+        .collect()
+}
+
+/// Finds the authorization waiting for the decision of the user.
+pub(crate) async fn find_pending(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    user_code: &str,
+) -> Result<Option<models::db::DeviceAuthorization>, ApiError> {
+    let search_res: Result<models::db::DeviceAuthorization, _> =
+        db_schema::device_authorizations::table
+            .filter(
+                db_schema::device_authorizations::user_code
+                    .eq(normalize_user_code(user_code))
+                    .and(db_schema::device_authorizations::expires_at.gt(chrono::Utc::now()))
+                    .and(db_schema::device_authorizations::user_id.is_null())
+                    .and(db_schema::device_authorizations::denied.eq(false)),
+            )
+            .first(db_conn)
+            .await;
+    match search_res {
+        Ok(pending) => Ok(Some(pending)),
+        Err(diesel::result::Error::NotFound) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Saves the decision of the user, the device finds out about it with its
+/// next poll.
+pub(crate) async fn decide(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    pending: &models::db::DeviceAuthorization,
+    user_id: &models::types::Uuid,
+    approve: bool,
+) -> Result<(), ApiError> {
+    let authorization = db_schema::device_authorizations::table.find(&pending.device_code_hash);
+    if approve {
+        diesel::update(authorization)
+            .set(db_schema::device_authorizations::user_id.eq(user_id))
+            .execute(db_conn)
+            .await?;
+    } else {
+        diesel::update(authorization)
+            .set(db_schema::device_authorizations::denied.eq(true))
+            .execute(db_conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// The error reported to the device with the error code of RFC 8628 in the
+/// `error` field.
+fn oauth_error(code: &'static str, description: &str) -> ApiError {
+    ApiError::from(BadRequestError::default().with_msg(description).with_docs())
+        .with_oauth_error(code)
+}
+
+/// Checks the state of the authorization polled by the device. Once the
+/// authorization is approved it is removed and returned so that a session
+/// can be started.
+pub(crate) async fn poll(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    device_code: &str,
+    client_id: Option<&models::types::ClientSecretStr>,
+) -> Result<(models::types::Uuid, models::db::DeviceAuthorization), ApiError> {
+    let device_code_hash = crate::auth::opaque::hash(device_code);
+    let search_res: Result<models::db::DeviceAuthorization, _> =
+        db_schema::device_authorizations::table
+            .find(&device_code_hash)
+            .first(db_conn)
+            .await;
+    let authorization = match search_res {
+        Ok(authorization) => authorization,
+        Err(diesel::result::Error::NotFound) => {
+            return Err(oauth_error("invalid_grant", "the device code is not valid"))
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let authorization_row = db_schema::device_authorizations::table.find(&device_code_hash);
+
+    if authorization.client_id.as_ref().map(ToString::to_string)
+        != client_id.map(ToString::to_string)
+    {
+        return Err(oauth_error(
+            "invalid_client",
+            "the client_id is not the one the authorization has been started with",
+        ));
+    }
+
+    let now = chrono::Utc::now();
+    if authorization.expires_at <= now {
+        diesel::delete(authorization_row).execute(db_conn).await?;
+        return Err(oauth_error(
+            "expired_token",
+            "the device code has expired, start the authorization again",
+        ));
+    }
+    if authorization.denied {
+        diesel::delete(authorization_row).execute(db_conn).await?;
+        return Err(oauth_error(
+            "access_denied",
+            "the user has denied the device",
+        ));
+    }
+
+    let too_early = authorization
+        .last_polled_at
+        .map_or(false, |last_polled_at| {
+            now < last_polled_at + chrono::Duration::seconds(authorization.poll_interval as i64)
+        });
+    if too_early {
+        diesel::update(authorization_row)
+            .set((
+                db_schema::device_authorizations::poll_interval
+                    .eq(authorization.poll_interval + POLL_INTERVAL_SECONDS),
+                db_schema::device_authorizations::last_polled_at.eq(now),
+            ))
+            .execute(db_conn)
+            .await?;
+        return Err(oauth_error(
+            "slow_down",
+            "the device polls too often, wait longer between the polls",
+        ));
+    }
+
+    let Some(user_id) = authorization.user_id.clone() else {
+        diesel::update(authorization_row)
+            .set(db_schema::device_authorizations::last_polled_at.eq(now))
+            .execute(db_conn)
+            .await?;
+        return Err(oauth_error(
+            "authorization_pending",
+            "the user has not approved the device yet",
+        ));
+    };
+
+    // The device code can be exchanged only once
+    let removed = diesel::delete(authorization_row).execute(db_conn).await?;
+    if removed == 0 {
+        return Err(oauth_error("invalid_grant", "the device code is not valid"));
+    }
+    Ok((user_id, authorization))
+}
+
+/// Removes the authorizations which have not been completed in time.
+pub(crate) async fn purge_expired_authorizations(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::device_authorizations::table)
+        .filter(db_schema::device_authorizations::expires_at.lt(chrono::Utc::now()))
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
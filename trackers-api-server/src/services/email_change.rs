@@ -0,0 +1,428 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use diesel_async::scoped_futures::ScopedFutureExt;
+use models::{EmailChangeInit, EmailChangeRequest};
+
+use crate::prelude::*;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/user/:email/email-change",
+            routing::get_with(get_email_change, |op| {
+                op.summary("Fetch details of the pending email change")
+            })
+            .post_with(start_email_change, |op| {
+                op.summary("Start changing the email address of the account")
+                    .description("A confirmation code will be sent to the new email address. The address is changed once the code is confirmed.")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .api_route_with(
+            "/user/:email/email-change/code",
+            routing::post_with(confirm_email_change, |op| {
+                op.summary("Confirm the email change with code")
+                    .description("The code should have been sent to the new email address. After the change the paths using the old address are redirected to the new ones for a grace period.")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            crate::auth::scope::RequiredScope::new(
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+            ),
+            crate::auth::scope::require_scope,
+        ))
+        .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()))
+}
+
+/// How long an email change code can be used after it has been sent.
+const CHANGE_CODE_LIFETIME_MINUTES: i64 = 15;
+/// How long the paths using the old email address are redirected.
+const REDIRECT_GRACE_PERIOD_DAYS: i64 = 30;
+/// How many incorrect codes are accepted before the request is cancelled.
+const MAX_FAILED_ATTEMPTS: i32 = 5;
+
+/// The redirected old email addresses along with the accounts they belong to
+/// and the end of their grace periods. Cached in the [`AppState`] so that the
+/// requests to the `/api/user/:email` paths do not have to wait for the
+/// database to find out that there is nothing to redirect. The cache is
+/// reloaded periodically to pick up the changes made through other instances
+/// of the server.
+#[derive(Debug, Clone, Default)]
+pub struct EmailRedirects {
+    redirects: Arc<RwLock<HashMap<models::types::Email, Redirect>>>,
+}
+
+/// The account and the end of the grace period of a redirect.
+type Redirect = (models::types::Uuid, chrono::DateTime<chrono::Utc>);
+
+impl EmailRedirects {
+    /// The account which used the address, if it is still redirected.
+    pub fn user_of(&self, old_email: &models::types::Email) -> Option<models::types::Uuid> {
+        self.redirects
+            .read()
+            .unwrap()
+            .get(old_email)
+            .filter(|(_, valid_until)| *valid_until > chrono::Utc::now())
+            .map(|(user_id, _)| user_id.clone())
+    }
+
+    /// Loads all the redirects which have not expired.
+    pub async fn load(
+        db_conn: &mut deadpool::managed::Object<
+            diesel_async::pooled_connection::AsyncDieselConnectionManager<
+                diesel_async::AsyncPgConnection,
+            >,
+        >,
+    ) -> Result<Self, diesel::result::Error> {
+        let redirects = Self::default();
+        redirects.reload(db_conn).await?;
+        Ok(redirects)
+    }
+
+    async fn reload(
+        &self,
+        db_conn: &mut deadpool::managed::Object<
+            diesel_async::pooled_connection::AsyncDieselConnectionManager<
+                diesel_async::AsyncPgConnection,
+            >,
+        >,
+    ) -> Result<(), diesel::result::Error> {
+        let redirects: Vec<models::db::EmailRedirect> = db_schema::email_redirects::table
+            .filter(db_schema::email_redirects::valid_until.gt(chrono::Utc::now()))
+            .load(db_conn)
+            .await?;
+        *self.redirects.write().unwrap() = redirects
+            .into_iter()
+            .map(|redirect| (redirect.old_email, (redirect.user_id, redirect.valid_until)))
+            .collect();
+        Ok(())
+    }
+
+    /// Picks up an email change committed by this instance of the server
+    /// without waiting for the next reload.
+    fn remember_change(
+        &self,
+        old_email: models::types::Email,
+        new_email: &models::types::Email,
+        user_id: models::types::Uuid,
+        valid_until: chrono::DateTime<chrono::Utc>,
+    ) {
+        let mut redirects = self.redirects.write().unwrap();
+        redirects.remove(new_email);
+        redirects.insert(old_email, (user_id, valid_until));
+    }
+}
+
+async fn get_email_change(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+) -> Result<Resource<EmailChangeRequest>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let req: models::db::EmailChangeRequest = db_schema::email_change_requests::table
+        .inner_join(db_schema::users::table)
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email)),
+        )
+        .select(db_schema::email_change_requests::all_columns)
+        .first(&mut db_conn)
+        .await?;
+
+    Ok(Resource::new(EmailChangeRequest {
+        issued_at: req.issued_at,
+        valid_until: req.valid_until,
+        old_email: email.clone(),
+        new_email: req.new_email,
+        confirmed_with_code: false,
+    })
+    .with_links([
+        ("self", format!("/api/user/{email}/email-change")),
+        ("confirm", format!("/api/user/{email}/email-change/code")),
+    ]))
+}
+
+async fn start_email_change(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    json: JsonExtract<EmailChangeInit>,
+) -> Result<CreatedResource<EmailChangeRequest>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let input = json.extract();
+
+    let user_search_res: Result<models::db::User, _> = db_schema::users::table
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email)),
+        )
+        .first(&mut db_conn)
+        .await;
+    let user = match user_search_res {
+        Ok(user) => user,
+        Err(diesel::result::Error::NotFound) => {
+            return Err(ForbiddenError::default()
+                .with_msg("cannot change the email of such user from current session")
+                .into())
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    if !input.password.match_with(user.password) {
+        Err(BadRequestError::default()
+            .with_msg("password not correct")
+            .with_docs())?;
+    }
+
+    if !crate::services::user::is_email_available(&mut db_conn, &input.new_email).await? {
+        Err(ConflictError::default().with_msg("email already taken by another account"))?;
+    }
+
+    // Generate confirmation code
+    let code = models::ConfirmationCode::new();
+    #[cfg(debug_assertions)]
+    println!("email change code {code} has been generated");
+
+    let issued_at = chrono::Utc::now();
+    let valid_until = issued_at
+        .checked_add_signed(chrono::Duration::minutes(CHANGE_CODE_LIFETIME_MINUTES))
+        .unwrap();
+
+    let code_hash = code.to_storeable();
+    let request = models::db::EmailChangeRequest {
+        user_id: user.user_id,
+        new_email: input.new_email.clone(),
+        issued_at,
+        valid_until,
+        code_hash,
+        failed_attempts: 0,
+    };
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                // An expired request of another account holds on to the
+                // address until it gets purged
+                diesel::delete(db_schema::email_change_requests::table)
+                    .filter(
+                        db_schema::email_change_requests::new_email
+                            .eq(&request.new_email)
+                            .and(db_schema::email_change_requests::valid_until.le(issued_at)),
+                    )
+                    .execute(tx)
+                    .await?;
+                // A new request replaces any request issued earlier
+                diesel::insert_into(db_schema::email_change_requests::table)
+                    .values(&request)
+                    .on_conflict(db_schema::email_change_requests::user_id)
+                    .do_update()
+                    .set((
+                        db_schema::email_change_requests::new_email.eq(&request.new_email),
+                        db_schema::email_change_requests::issued_at.eq(issued_at),
+                        db_schema::email_change_requests::valid_until.eq(valid_until),
+                        db_schema::email_change_requests::code_hash.eq(request.code_hash.clone()),
+                        db_schema::email_change_requests::failed_attempts.eq(0),
+                    ))
+                    .execute(tx)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    // The code goes to the new address to prove it belongs to the user
+    state
+        .mailer
+        .send(crate::mail::templates::EMAIL_CHANGE_CODE.render(&input.new_email, &[("code", &code)]))
+        .await?;
+
+    Ok(CreatedResource {
+        location: format!("/api/user/{email}/email-change"),
+        resource: Resource::new(EmailChangeRequest {
+            issued_at,
+            valid_until,
+            old_email: email.clone(),
+            new_email: input.new_email,
+            confirmed_with_code: false,
+        })
+        .with_links([
+            ("self", format!("/api/user/{email}/email-change")),
+            ("confirm", format!("/api/user/{email}/email-change/code")),
+        ]),
+    })
+}
+
+async fn confirm_email_change(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    json: JsonExtract<models::ConfirmationCode>,
+) -> Result<ModifiedResource<EmailChangeRequest>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let code = json.extract();
+
+    let req: models::db::EmailChangeRequest = db_schema::email_change_requests::table
+        .inner_join(db_schema::users::table)
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email)),
+        )
+        .select(db_schema::email_change_requests::all_columns)
+        .first(&mut db_conn)
+        .await?;
+
+    // Request no longer valid
+    if req.valid_until < chrono::Utc::now() {
+        diesel::delete(db_schema::email_change_requests::table)
+            .filter(db_schema::email_change_requests::user_id.eq(&req.user_id))
+            .execute(&mut db_conn)
+            .await?;
+        return Err(GoneError::default()
+            .with_docs()
+            .with_msg("the request is no longer valid")
+            .with_links([("new request", format!("/api/user/{email}/email-change"))])
+            .into());
+    }
+
+    // Not the code we expected
+    if !code.match_with(req.code_hash.clone()) {
+        // The counter is incremented in the database so that concurrent
+        // guesses cannot slip past the limit
+        let failed_attempts: i32 = diesel::update(db_schema::email_change_requests::table)
+            .filter(db_schema::email_change_requests::user_id.eq(&req.user_id))
+            .set(
+                db_schema::email_change_requests::failed_attempts
+                    .eq(db_schema::email_change_requests::failed_attempts + 1),
+            )
+            .returning(db_schema::email_change_requests::failed_attempts)
+            .get_result(&mut db_conn)
+            .await?;
+        if failed_attempts >= MAX_FAILED_ATTEMPTS {
+            diesel::delete(db_schema::email_change_requests::table)
+                .filter(db_schema::email_change_requests::user_id.eq(&req.user_id))
+                .execute(&mut db_conn)
+                .await?;
+            return Err(GoneError::default()
+                .with_docs()
+                .with_msg("too many incorrect codes, the request has been cancelled")
+                .with_links([("new request", format!("/api/user/{email}/email-change"))])
+                .into());
+        }
+        return Err(BadRequestError::default()
+            .with_docs()
+            .with_msg("incorrect code")
+            .into());
+    }
+
+    let old_email = email.clone();
+    let new_email = req.new_email.clone();
+    let changed_user_id = req.user_id.clone();
+    let redirect_valid_until = chrono::Utc::now()
+        .checked_add_signed(chrono::Duration::days(REDIRECT_GRACE_PERIOD_DAYS))
+        .unwrap();
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                // The new address might have been taken since the request was
+                // issued, the unique constraint on the users table will stop
+                // the update in such case.
+                diesel::update(db_schema::users::table)
+                    .filter(db_schema::users::user_id.eq(&changed_user_id))
+                    .set(db_schema::users::email.eq(&new_email))
+                    .execute(tx)
+                    .await?;
+                diesel::insert_into(db_schema::email_redirects::table)
+                    .values(models::db::EmailRedirect {
+                        old_email: old_email.clone(),
+                        user_id: changed_user_id.clone(),
+                        valid_until: redirect_valid_until,
+                    })
+                    .on_conflict(db_schema::email_redirects::old_email)
+                    .do_update()
+                    .set((
+                        db_schema::email_redirects::user_id.eq(&changed_user_id),
+                        db_schema::email_redirects::valid_until.eq(redirect_valid_until),
+                    ))
+                    .execute(tx)
+                    .await?;
+                // The account might be coming back to an address it used
+                // before, there is nothing to redirect from it anymore.
+                diesel::delete(db_schema::email_redirects::table)
+                    .filter(db_schema::email_redirects::old_email.eq(&new_email))
+                    .execute(tx)
+                    .await?;
+                diesel::delete(db_schema::email_change_requests::table)
+                    .filter(db_schema::email_change_requests::user_id.eq(&changed_user_id))
+                    .execute(tx)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    state.email_redirects.remember_change(
+        email.clone(),
+        &req.new_email,
+        req.user_id,
+        redirect_valid_until,
+    );
+
+    let new_email = req.new_email;
+    Ok(ModifiedResource {
+        location: Some(format!("/api/user/{new_email}")),
+        resource: Resource::new(EmailChangeRequest {
+            issued_at: req.issued_at,
+            valid_until: req.valid_until,
+            old_email: email,
+            new_email: new_email.clone(),
+            confirmed_with_code: true,
+        })
+        .with_links([
+            ("trackers", format!("/api/user/{new_email}/trackers")),
+            ("tasks", format!("/api/user/{new_email}/tasks")),
+        ]),
+    })
+}
+
+/// Removes the email change requests which have not been confirmed in time
+/// along with the redirects past their grace period.
+pub(crate) async fn purge_expired(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    let now = chrono::Utc::now();
+    diesel::delete(db_schema::email_change_requests::table)
+        .filter(db_schema::email_change_requests::valid_until.lt(now))
+        .execute(&mut db_conn)
+        .await?;
+    diesel::delete(db_schema::email_redirects::table)
+        .filter(db_schema::email_redirects::valid_until.lt(now))
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
+
+/// Picks up the redirects added by other instances of the server and forgets
+/// the expired ones.
+pub(crate) async fn refresh_redirects(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    state.email_redirects.reload(&mut db_conn).await?;
+    Ok(())
+}
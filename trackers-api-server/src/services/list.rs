@@ -1,4 +1,5 @@
 use axum::extract::Path;
+use diesel_async::scoped_futures::ScopedFutureExt;
 use models::types::ListItems;
 
 use crate::{
@@ -53,6 +54,13 @@ pub fn router() -> ApiRouter<AppState> {
             }),
             |op| op.tag("Task Management"),
         )
+        .layer(axum::middleware::from_fn_with_state(
+            crate::auth::scope::RequiredScope::new(
+                crate::auth::scope::permission::TASKS_READ,
+                crate::auth::scope::permission::TASKS_WRITE,
+            ),
+            crate::auth::scope::require_scope,
+        ))
         .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()))
 }
 
@@ -63,11 +71,12 @@ async fn modify_item_checkmark(
             diesel_async::AsyncPgConnection,
         >,
     >,
+    actor: &services::audit::Actor,
     task_id: uuid::Uuid,
     item_idx: usize,
     user_id: models::types::Uuid,
 ) -> Result<ModifiedResource<models::types::ListItem>, ApiError> {
-    let Some(mut list) = try_get_securely_just_the_list(db_conn, task_id, user_id).await? else {
+    let Some(mut list) = try_get_securely_just_the_list(db_conn, task_id, user_id.clone()).await? else {
         return Err(NotFoundError::default()
             .with_docs()
             .with_msg("no list found for selected task")
@@ -92,11 +101,20 @@ async fn modify_item_checkmark(
 
     item.is_completed = set_value;
 
-    diesel::update(db_schema::tasks::table)
-        .filter(db_schema::tasks::task_id.eq(task_id))
-        .set(db_schema::tasks::list.eq(&list))
-        .execute(db_conn)
-        .await?;
+    let change = if set_value {
+        format!("item {item_idx} of the list marked done")
+    } else {
+        format!("item {item_idx} of the list unmarked as done")
+    };
+    save_list(
+        db_conn,
+        actor,
+        &user_id,
+        &Base62Uuid::from(task_id),
+        Some(&list),
+        change,
+    )
+    .await?;
 
     Ok(ModifiedResource {
         location: Some(format!("/api/task/{task_id}/list")),
@@ -108,31 +126,51 @@ async fn remove_item_checkmark(
     State(state): State<AppState>,
     VariableScope(user_id): VariableScope<UserIdScope, UserClaims>,
     Path((task_id, item_idx)): Path<(Base62Uuid, usize)>,
+    actor: services::audit::Actor,
 ) -> Result<ModifiedResource<models::types::ListItem>, ApiError> {
     let mut db_conn = state.db.get().await?;
-    modify_item_checkmark(false, &mut db_conn, task_id.into(), item_idx, user_id.0).await
+    modify_item_checkmark(
+        false,
+        &mut db_conn,
+        &actor,
+        task_id.into(),
+        item_idx,
+        user_id.0,
+    )
+    .await
 }
 
 async fn mark_item_done(
     State(state): State<AppState>,
     VariableScope(user_id): VariableScope<UserIdScope, UserClaims>,
     Path((task_id, item_idx)): Path<(Base62Uuid, usize)>,
+    actor: services::audit::Actor,
 ) -> Result<ModifiedResource<models::types::ListItem>, ApiError> {
     let mut db_conn = state.db.get().await?;
-    modify_item_checkmark(true, &mut db_conn, task_id.into(), item_idx, user_id.0).await
+    modify_item_checkmark(
+        true,
+        &mut db_conn,
+        &actor,
+        task_id.into(),
+        item_idx,
+        user_id.0,
+    )
+    .await
 }
 
 async fn create_list_item(
     State(state): State<AppState>,
     VariableScope(user_id): VariableScope<UserIdScope, UserClaims>,
     Path(task_id): Path<Base62Uuid>,
+    actor: services::audit::Actor,
     json: JsonExtract<models::types::ListItem>,
 ) -> Result<ModifiedResource<models::types::ListItems>, ApiError> {
     let mut db_conn = state.db.get().await?;
     let new_item = json.extract();
 
     let maybe_list =
-        try_get_securely_just_the_list(&mut db_conn, task_id.clone().into(), user_id.0).await?;
+        try_get_securely_just_the_list(&mut db_conn, task_id.clone().into(), user_id.0.clone())
+            .await?;
 
     // create a new list with the item if there was none before or add
     let list = match maybe_list {
@@ -143,11 +181,15 @@ async fn create_list_item(
         None => ListItems(vec![new_item]),
     };
 
-    diesel::update(db_schema::tasks::table)
-        .filter(db_schema::tasks::task_id.eq(&task_id))
-        .set(db_schema::tasks::list.eq(&list))
-        .execute(&mut db_conn)
-        .await?;
+    save_list(
+        &mut db_conn,
+        &actor,
+        &user_id.0,
+        &task_id,
+        Some(&list),
+        "item added to the list",
+    )
+    .await?;
 
     Ok(ModifiedResource {
         location: Some(format!("/api/task/{task_id}/list")),
@@ -159,12 +201,13 @@ async fn create_list_item_with_idx(
     State(state): State<AppState>,
     VariableScope(user_id): VariableScope<UserIdScope, UserClaims>,
     Path((task_id, item_idx)): Path<(Base62Uuid, usize)>,
+    actor: services::audit::Actor,
     json: JsonExtract<models::types::ListItem>,
 ) -> Result<ModifiedResource<models::types::ListItems>, ApiError> {
     let mut db_conn = state.db.get().await?;
     let new_item = json.extract();
 
-    let Some(mut list) = try_get_securely_just_the_list(&mut db_conn, task_id.clone().into(), user_id.0).await? else {
+    let Some(mut list) = try_get_securely_just_the_list(&mut db_conn, task_id.clone().into(), user_id.0.clone()).await? else {
         return Err(NotFoundError::default()
             .with_docs()
             .with_msg("no list found for selected task")
@@ -196,11 +239,15 @@ async fn create_list_item_with_idx(
     } else {
         list_inner.push(new_item);
 
-        diesel::update(db_schema::tasks::table)
-            .filter(db_schema::tasks::task_id.eq(&task_id))
-            .set(db_schema::tasks::list.eq(&list))
-            .execute(&mut db_conn)
-            .await?;
+        save_list(
+            &mut db_conn,
+            &actor,
+            &user_id.0,
+            &task_id,
+            Some(&list),
+            "item added to the list",
+        )
+        .await?;
 
         Ok(ModifiedResource {
             location: Some(format!("/api/task/{task_id}/list")),
@@ -213,12 +260,13 @@ async fn replace_list_item(
     State(state): State<AppState>,
     VariableScope(user_id): VariableScope<UserIdScope, UserClaims>,
     Path((task_id, item_idx)): Path<(Base62Uuid, usize)>,
+    actor: services::audit::Actor,
     json: JsonExtract<models::types::ListItem>,
 ) -> Result<ModifiedResource<models::types::ListItems>, ApiError> {
     let mut db_conn = state.db.get().await?;
     let new_item = json.extract();
 
-    let Some(mut list) = try_get_securely_just_the_list(&mut db_conn, task_id.clone().into(), user_id.0).await? else {
+    let Some(mut list) = try_get_securely_just_the_list(&mut db_conn, task_id.clone().into(), user_id.0.clone()).await? else {
         return Err(NotFoundError::default()
             .with_docs()
             .with_msg("no list found for selected task")
@@ -252,11 +300,15 @@ async fn replace_list_item(
         }
     }
 
-    diesel::update(db_schema::tasks::table)
-        .filter(db_schema::tasks::task_id.eq(&task_id))
-        .set(db_schema::tasks::list.eq(&list))
-        .execute(&mut db_conn)
-        .await?;
+    save_list(
+        &mut db_conn,
+        &actor,
+        &user_id.0,
+        &task_id,
+        Some(&list),
+        format!("item {item_idx} of the list replaced"),
+    )
+    .await?;
 
     Ok(ModifiedResource {
         location: Some(format!("/api/task/{task_id}/list")),
@@ -268,10 +320,11 @@ async fn delete_list_item(
     State(state): State<AppState>,
     VariableScope(user_id): VariableScope<UserIdScope, UserClaims>,
     Path((task_id, item_idx)): Path<(Base62Uuid, usize)>,
+    actor: services::audit::Actor,
 ) -> Result<ModifiedResource<models::types::ListItems>, ApiError> {
     let mut db_conn = state.db.get().await?;
 
-    let Some(mut list) = try_get_securely_just_the_list(&mut db_conn, task_id.clone().into(), user_id.0).await? else {
+    let Some(mut list) = try_get_securely_just_the_list(&mut db_conn, task_id.clone().into(), user_id.0.clone()).await? else {
         return Err(NotFoundError::default()
             .with_docs()
             .with_msg("no list found for selected task")
@@ -294,11 +347,15 @@ async fn delete_list_item(
     list_inner.remove(item_idx - 1);
 
     // save in the database
-    diesel::update(db_schema::tasks::table)
-        .filter(db_schema::tasks::task_id.eq(task_id))
-        .set(db_schema::tasks::list.eq(&list))
-        .execute(&mut db_conn)
-        .await?;
+    save_list(
+        &mut db_conn,
+        &actor,
+        &user_id.0,
+        &task_id,
+        Some(&list),
+        format!("item {item_idx} removed from the list"),
+    )
+    .await?;
 
     Ok(ModifiedResource {
         location: None,
@@ -351,6 +408,8 @@ async fn try_get_securely_just_the_list(
     let (list, task_user_id) = db_schema::tasks::table
         .inner_join(db_schema::trackers::table)
         .filter(db_schema::tasks::task_id.eq(&task_id))
+        .filter(db_schema::tasks::deleted_at.is_null())
+        .filter(db_schema::trackers::deleted_at.is_null())
         .select((db_schema::tasks::list, db_schema::trackers::user_id))
         .first::<(Option<models::types::ListItems>, models::types::Uuid)>(db_conn)
         .await?;
@@ -364,6 +423,36 @@ async fn try_get_securely_just_the_list(
     Ok(list)
 }
 
+/// Saves the list of the task and records the change as an update of the
+/// task in the audit log, both in one transaction.
+async fn save_list(
+    db_conn: &mut diesel_async::AsyncPgConnection,
+    actor: &services::audit::Actor,
+    user_id: &models::types::Uuid,
+    task_id: &Base62Uuid,
+    list: Option<&ListItems>,
+    change: impl Display,
+) -> Result<(), ApiError> {
+    let change = change.to_string();
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                diesel::update(db_schema::tasks::table)
+                    .filter(db_schema::tasks::task_id.eq(task_id))
+                    .set(db_schema::tasks::list.eq(list))
+                    .execute(tx)
+                    .await?;
+                services::audit::Event::new(user_id, models::types::AuditAction::TaskUpdated)
+                    .resource(format!("/api/task/{task_id}"))
+                    .details(change)
+                    .record(tx, actor)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+}
+
 async fn get_just_list(
     State(state): State<AppState>,
     VariableScope(user_id): VariableScope<UserIdScope, UserClaims>,
@@ -395,6 +484,7 @@ async fn add_list(
     State(state): State<AppState>,
     VariableScope(user_id): VariableScope<UserIdScope, UserClaims>,
     Path(task_id): Path<Base62Uuid>,
+    actor: services::audit::Actor,
     json: JsonExtract<models::types::ListItems>,
 ) -> Result<CreatedResource<models::types::ListItems>, ApiError> {
     let mut db_conn = state.db.get().await?;
@@ -404,6 +494,8 @@ async fn add_list(
     let (list, task_user_id) = db_schema::tasks::table
         .inner_join(db_schema::trackers::table)
         .filter(db_schema::tasks::task_id.eq(&task_id))
+        .filter(db_schema::tasks::deleted_at.is_null())
+        .filter(db_schema::trackers::deleted_at.is_null())
         .select((db_schema::tasks::list, db_schema::trackers::user_id))
         .first::<(Option<models::types::ListItems>, models::types::Uuid)>(&mut db_conn)
         .await?;
@@ -425,11 +517,15 @@ async fn add_list(
     }
 
     // Run the task update when everything checks out
-    diesel::update(db_schema::tasks::table)
-        .filter(db_schema::tasks::task_id.eq(&task_id))
-        .set(db_schema::tasks::list.eq(&new_list))
-        .execute(&mut db_conn)
-        .await?;
+    save_list(
+        &mut db_conn,
+        &actor,
+        &user_id.0,
+        &task_id,
+        Some(&new_list),
+        "list added",
+    )
+    .await?;
 
     Ok(CreatedResource {
         location: format!("/api/task/{task_id}/list"),
@@ -441,6 +537,7 @@ async fn replace_list(
     State(state): State<AppState>,
     VariableScope(user_id): VariableScope<UserIdScope, UserClaims>,
     Path(task_id): Path<Base62Uuid>,
+    actor: services::audit::Actor,
     json: JsonExtract<Option<models::types::ListItems>>,
 ) -> Result<DeletedOrModified<ListItems>, ApiError> {
     use DeletedOrModified::*;
@@ -451,6 +548,8 @@ async fn replace_list(
         .inner_join(db_schema::trackers::table)
         .select(db_schema::trackers::user_id)
         .filter(db_schema::tasks::task_id.eq(&task_id))
+        .filter(db_schema::tasks::deleted_at.is_null())
+        .filter(db_schema::trackers::deleted_at.is_null())
         .get_result(&mut db_conn)
         .await?;
     if owned_by != user_id.0 {
@@ -458,21 +557,29 @@ async fn replace_list(
     }
 
     if let Some(list) = maybe_list {
-        let _: usize = diesel::update(db_schema::tasks::table)
-            .filter(db_schema::tasks::task_id.eq(&task_id))
-            .set(db_schema::tasks::list.eq(&list))
-            .execute(&mut db_conn)
-            .await?;
+        save_list(
+            &mut db_conn,
+            &actor,
+            &user_id.0,
+            &task_id,
+            Some(&list),
+            "list replaced",
+        )
+        .await?;
         Ok(Modified(ModifiedResource {
             location: None,
             resource: Resource::new(list),
         }))
     } else {
-        let _: usize = diesel::update(db_schema::tasks::table)
-            .filter(db_schema::tasks::task_id.eq(&task_id))
-            .set(db_schema::tasks::list.eq(Option::<ListItems>::None))
-            .execute(&mut db_conn)
-            .await?;
+        save_list(
+            &mut db_conn,
+            &actor,
+            &user_id.0,
+            &task_id,
+            None,
+            "list deleted",
+        )
+        .await?;
 
         Ok(Deleted(DeletedResource::default()))
     }
@@ -482,6 +589,7 @@ async fn delete_list(
     State(state): State<AppState>,
     VariableScope(user_id): VariableScope<UserIdScope, UserClaims>,
     Path(task_id): Path<Base62Uuid>,
+    actor: services::audit::Actor,
 ) -> Result<DeletedResource, ApiError> {
     let mut db_conn = state.db.get().await?;
 
@@ -489,17 +597,23 @@ async fn delete_list(
         .inner_join(db_schema::trackers::table)
         .select(db_schema::trackers::user_id)
         .filter(db_schema::tasks::task_id.eq(&task_id))
+        .filter(db_schema::tasks::deleted_at.is_null())
+        .filter(db_schema::trackers::deleted_at.is_null())
         .get_result(&mut db_conn)
         .await?;
     if owned_by != user_id.0 {
         Err(ForbiddenError::default().with_msg("no access to the selected task"))?;
     }
 
-    let _: usize = diesel::update(db_schema::tasks::table)
-        .filter(db_schema::tasks::task_id.eq(&task_id))
-        .set(db_schema::tasks::list.eq(Option::<ListItems>::None))
-        .execute(&mut db_conn)
-        .await?;
+    save_list(
+        &mut db_conn,
+        &actor,
+        &user_id.0,
+        &task_id,
+        None,
+        "list deleted",
+    )
+    .await?;
 
     Ok(DeletedResource::default())
 }
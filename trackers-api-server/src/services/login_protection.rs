@@ -0,0 +1,124 @@
+//! Protection of the password logins against guessing.
+//!
+//! The failed logins are counted per account and per client address. After a
+//! few of them every next attempt has to wait twice as long as the previous
+//! one and eventually the account or the address is locked out for a while.
+//! The attempts are refused before the password is checked so that guessing
+//! does not keep the server busy with bcrypt. Every failed login is also
+//! recorded in the database.
+
+use std::net::IpAddr;
+
+use crate::prelude::*;
+
+/// Failed logins for an account which do not delay the next attempt.
+pub(crate) const FREE_ATTEMPTS_PER_ACCOUNT: u32 = 3;
+/// Failed logins after which the account is locked out.
+pub(crate) const LOCKOUT_AFTER_PER_ACCOUNT: u32 = 10;
+/// Failed logins from an address which do not delay the next attempt. The
+/// address can be shared by many users so it gets more of them.
+pub(crate) const FREE_ATTEMPTS_PER_ADDRESS: u32 = 10;
+/// Failed logins after which the address is locked out.
+pub(crate) const LOCKOUT_AFTER_PER_ADDRESS: u32 = 50;
+/// The longest delay between two attempts before the lockout.
+pub(crate) const MAX_DELAY_SECONDS: u64 = 60;
+/// How long the lockout lasts. The failures are forgotten after the same time
+/// passes since the last one.
+pub(crate) const LOCKOUT_MINUTES: u64 = 15;
+/// How long the records of the failed logins are kept.
+const RECORD_RETENTION_DAYS: i64 = 90;
+
+/// Checks if the client at the address may try to log in to the account now
+/// and reserves the attempt, which has to be followed by [`record_failure`],
+/// [`record_success`] or [`release_attempt`].
+pub(crate) fn check_allowed(
+    state: &AppState,
+    email: &EmailAddress,
+    client_addr: IpAddr,
+) -> Result<(), ApiError> {
+    let account_wait = state.login_account_backoff.check(email.clone()).err();
+    let address_wait = state.login_address_backoff.check(client_addr).err();
+
+    // the attempt is reserved for both or for none of them
+    match (account_wait, address_wait) {
+        (None, None) => return Ok(()),
+        (None, Some(_)) => state.login_account_backoff.release(email),
+        (Some(_), None) => state.login_address_backoff.release(&client_addr),
+        (Some(_), Some(_)) => {}
+    }
+    let wait = account_wait.max(address_wait).unwrap_or_default();
+    Err(TooManyRequestsError::default()
+        .with_msg("too many failed login attempts, try again later")
+        .with_retry_after(wait)
+        .into())
+}
+
+/// Counts the failure towards the delays and records it in the database.
+pub(crate) async fn record_failure(
+    state: &AppState,
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    email: &EmailAddress,
+    user_id: Option<models::types::Uuid>,
+    client_addr: IpAddr,
+    user_agent: Option<&str>,
+) -> Result<(), ApiError> {
+    state.login_account_backoff.fail(email.clone());
+    state.login_address_backoff.fail(client_addr);
+
+    // the owner of the account can see the attempts in the audit log
+    if let Some(user_id) = &user_id {
+        let actor = services::audit::Actor {
+            client: None,
+            ip_address: Some(client_addr.to_string()),
+        };
+        services::audit::Event::new(user_id, models::types::AuditAction::LoginFailed)
+            .record(db_conn, &actor)
+            .await?;
+    }
+
+    diesel::insert_into(db_schema::failed_logins::table)
+        .values(models::db::FailedLogin {
+            attempt_id: models::types::Uuid::new(),
+            email: email.clone(),
+            user_id,
+            ip_address: client_addr.to_string(),
+            user_agent: user_agent.map(ToOwned::to_owned),
+            attempted_at: chrono::Utc::now(),
+        })
+        .execute(db_conn)
+        .await?;
+    Ok(())
+}
+
+/// Forgets the failures of the account once the right password and the
+/// second factor, if it is turned on, are given. The
+/// failures of the address are kept as the address may be guessing passwords
+/// of many accounts, only the attempt reserved for it is given back.
+pub(crate) fn record_success(state: &AppState, email: &EmailAddress, client_addr: IpAddr) {
+    state.login_account_backoff.succeed(email);
+    state.login_address_backoff.release(&client_addr);
+}
+
+/// Gives back the reserved attempt without forgetting any failures, when the
+/// password is right but the second factor has yet to be given. Answering
+/// the challenge is an attempt of its own.
+pub(crate) fn release_attempt(state: &AppState, email: &EmailAddress, client_addr: IpAddr) {
+    state.login_account_backoff.release(email);
+    state.login_address_backoff.release(&client_addr);
+}
+
+pub(crate) async fn purge_old_records(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::failed_logins::table)
+        .filter(
+            db_schema::failed_logins::attempted_at
+                .lt(chrono::Utc::now() - chrono::Duration::days(RECORD_RETENTION_DAYS)),
+        )
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use diesel::OptionalExtension;
+use models::{MagicLinkInit, MagicLinkRequest};
+
+use crate::prelude::*;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new().api_route_with(
+        "/session/magic-link",
+        routing::post_with(request_magic_link, |op| {
+            op.summary("Request a login code by email")
+                .description("If an account with the provided email address exists a single-use code is sent to that address. The code can be exchanged for the tokens at `/api/session/token` with the `magic_link` grant. The response is the same whether the account exists or not.")
+        }),
+        |op| op.tag("Log-in"),
+    )
+}
+
+/// How long the code can be used after it has been sent.
+const CODE_LIFETIME_MINUTES: i64 = 15;
+
+const CODE_LENGTH: usize = 32;
+
+/// How many codes can be mailed to a single address within an hour.
+pub(crate) const CODE_MAILS_PER_ACCOUNT: usize = 3;
+
+async fn request_magic_link(
+    State(state): State<AppState>,
+    crate::client_addr::ClientAddr(client_addr): crate::client_addr::ClientAddr,
+    json: JsonExtract<MagicLinkInit>,
+) -> Result<Resource<MagicLinkRequest>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let input = json.extract();
+
+    // Both limited even if the account does not exist so that the limits do
+    // not reveal it
+    crate::services::registration::limit_code_mails(&state, client_addr)?;
+    limit_account_mails(&state, &input.email)?;
+
+    let issued_at = chrono::Utc::now();
+    let valid_until = issued_at
+        .checked_add_signed(chrono::Duration::minutes(CODE_LIFETIME_MINUTES))
+        .unwrap();
+
+    let user_search_res: Result<models::types::Uuid, _> = db_schema::users::table
+        .filter(db_schema::users::email.eq(&input.email))
+        .select(db_schema::users::user_id)
+        .first(&mut db_conn)
+        .await;
+
+    match user_search_res {
+        Ok(user_id) => {
+            // The code is stored and mailed in the background so that the
+            // response comes as fast as for the addresses without an account
+            let (state, email) = (state.clone(), input.email.clone());
+            tokio::spawn(async move {
+                if let Err(err) = send_code(&state, user_id, &email, issued_at, valid_until).await {
+                    eprintln!("failed to send the login code: {err}");
+                }
+            });
+        }
+        // Do not reveal which email addresses are used by the accounts
+        Err(diesel::result::Error::NotFound) => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    Ok(Resource::new(MagicLinkRequest {
+        issued_at,
+        valid_until,
+        user_email: input.email,
+    })
+    .with_links([("log in", "/api/session/token".to_owned())]))
+}
+
+/// Checks if the address is still allowed to receive the login codes.
+fn limit_account_mails(state: &AppState, email: &models::types::Email) -> Result<(), ApiError> {
+    state
+        .magic_link_mail_limiter
+        .hit(email.clone())
+        .map_err(|wait| {
+            TooManyRequestsError::default()
+                .with_msg("too many login codes have been sent to the address, try again later")
+                .with_retry_after(wait)
+                .into()
+        })
+}
+
+/// Stores the new code of the user, replacing any code sent earlier, and
+/// mails it.
+async fn send_code(
+    state: &AppState,
+    user_id: models::types::Uuid,
+    email: &models::types::Email,
+    issued_at: chrono::DateTime<chrono::Utc>,
+    valid_until: chrono::DateTime<chrono::Utc>,
+) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    let code = crate::auth::opaque::generate(CODE_LENGTH);
+    let code_hash = crate::auth::opaque::hash(&code);
+
+    diesel::insert_into(db_schema::magic_links::table)
+        .values(models::db::MagicLink {
+            user_id,
+            code_hash: code_hash.clone(),
+            issued_at,
+            valid_until,
+        })
+        .on_conflict(db_schema::magic_links::user_id)
+        .do_update()
+        .set((
+            db_schema::magic_links::code_hash.eq(code_hash),
+            db_schema::magic_links::issued_at.eq(issued_at),
+            db_schema::magic_links::valid_until.eq(valid_until),
+        ))
+        .execute(&mut db_conn)
+        .await?;
+
+    state
+        .mailer
+        .send(crate::mail::templates::MAGIC_LINK_CODE.render(email, &[("code", &code)]))
+        .await
+        .map_err(|err| err.0)?;
+    Ok(())
+}
+
+/// Finds the user the code has been sent to. The code is removed so that it
+/// cannot be used again.
+pub(crate) async fn use_code(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    code: &str,
+) -> Result<Option<models::types::Uuid>, ApiError> {
+    let user_id = diesel::delete(db_schema::magic_links::table)
+        .filter(db_schema::magic_links::code_hash.eq(crate::auth::opaque::hash(code)))
+        .filter(db_schema::magic_links::valid_until.gt(chrono::Utc::now()))
+        .returning(db_schema::magic_links::user_id)
+        .get_result(db_conn)
+        .await
+        .optional()?;
+    Ok(user_id)
+}
+
+/// Removes the codes which have not been used in time.
+pub(crate) async fn purge_expired_codes(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::magic_links::table)
+        .filter(db_schema::magic_links::valid_until.lt(chrono::Utc::now()))
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
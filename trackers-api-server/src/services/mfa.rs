@@ -0,0 +1,511 @@
+use diesel::OptionalExtension;
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::prelude::*;
+
+const RECOVERY_CODES_COUNT: usize = 10;
+
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+const MFA_TOKEN_LENGTH: usize = 32;
+
+/// How long the client has to answer the challenge after the password has
+/// been accepted.
+pub const MFA_CHALLENGE_LIFETIME_SECONDS: i64 = 5 * 60;
+
+/// After this many incorrect codes the password has to be given again.
+const MFA_CHALLENGE_MAX_ATTEMPTS: i32 = 5;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/user/:email/mfa",
+            routing::get_with(get_mfa_status, |op| {
+                op.summary("Check the state of the two-factor authentication")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .api_route_with(
+            "/user/:email/mfa/totp",
+            routing::post_with(enrol_totp, |op| {
+                op.summary("Start adding an authenticator app")
+                    .description("Returns the secret to be added to an authenticator app. The codes are not required to log in until the enrolment is confirmed at `/api/user/:email/mfa/totp/confirm`.")
+            })
+            .delete_with(disable_totp, |op| {
+                op.summary("Turn off the two-factor authentication")
+                    .description("The current password must be provided in the body of the request. The recovery codes are removed as well.")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .api_route_with(
+            "/user/:email/mfa/totp/confirm",
+            routing::post_with(confirm_totp, |op| {
+                op.summary("Confirm the authenticator app")
+                    .description("Once confirmed with a code generated by the app the codes are required to log in. The response holds the recovery codes which are shown only once.")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .api_route_with(
+            "/user/:email/mfa/recovery_codes",
+            routing::post_with(regenerate_recovery_codes, |op| {
+                op.summary("Replace the recovery codes")
+                    .description("The codes generated before stop working.")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            crate::auth::scope::RequiredScope::new(
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+            ),
+            crate::auth::scope::require_scope,
+        ))
+        .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()))
+}
+
+/// The response to a password grant for an account with the two-factor
+/// authentication turned on. The `mfa_token` must be sent back with a code
+/// using the `mfa_otp` grant.
+#[derive(Serialize, JsonSchema)]
+pub struct MfaRequired {
+    /// Always `mfa_required`
+    pub error: &'static str,
+    pub mfa_token: String,
+    /// Number of seconds in which the challenge must be answered
+    pub expires_in: i64,
+}
+
+async fn find_user(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    user_id: &models::types::Uuid,
+    email: &EmailAddress,
+) -> Result<models::db::User, ApiError> {
+    let user_search_res: Result<models::db::User, _> = db_schema::users::table
+        .filter(
+            db_schema::users::user_id
+                .eq(user_id)
+                .and(db_schema::users::email.eq(email)),
+        )
+        .first(db_conn)
+        .await;
+    match user_search_res {
+        Ok(user) => Ok(user),
+        Err(diesel::result::Error::NotFound) => Err(ForbiddenError::default()
+            .with_msg("cannot manage such user from current session")
+            .into()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn find_credential(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    user_id: &models::types::Uuid,
+) -> Result<Option<models::db::TotpCredential>, ApiError> {
+    Ok(db_schema::totp_credentials::table
+        .filter(db_schema::totp_credentials::user_id.eq(user_id))
+        .first(db_conn)
+        .await
+        .optional()?)
+}
+
+/// Replaces the recovery codes of the user with new ones.
+async fn new_recovery_codes(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    user_id: &models::types::Uuid,
+) -> Result<models::RecoveryCodes, ApiError> {
+    let codes: Vec<String> = (0..RECOVERY_CODES_COUNT)
+        .map(|_| crate::auth::opaque::generate(RECOVERY_CODE_LENGTH))
+        .collect();
+    let rows: Vec<models::db::RecoveryCode> = codes
+        .iter()
+        .map(|code| models::db::RecoveryCode {
+            code_hash: crate::auth::opaque::hash(code),
+            user_id: user_id.clone(),
+        })
+        .collect();
+
+    let user_id = user_id.clone();
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                diesel::delete(db_schema::recovery_codes::table)
+                    .filter(db_schema::recovery_codes::user_id.eq(&user_id))
+                    .execute(tx)
+                    .await?;
+                diesel::insert_into(db_schema::recovery_codes::table)
+                    .values(&rows)
+                    .execute(tx)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(models::RecoveryCodes { codes })
+}
+
+async fn get_mfa_status(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+) -> Result<Resource<models::MfaStatus>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let user = find_user(&mut db_conn, &user_id.0, &email).await?;
+
+    let totp_enabled = is_enabled(&mut db_conn, &user.user_id).await?;
+    let recovery_codes_left: i64 = db_schema::recovery_codes::table
+        .filter(db_schema::recovery_codes::user_id.eq(&user.user_id))
+        .count()
+        .get_result(&mut db_conn)
+        .await?;
+
+    Ok(Resource::new(models::MfaStatus {
+        totp_enabled,
+        recovery_codes_left,
+    })
+    .with_links([("self", format!("/api/user/{email}/mfa"))]))
+}
+
+async fn enrol_totp(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+) -> Result<CreatedResource<models::TotpEnrolment>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let user = find_user(&mut db_conn, &user_id.0, &email).await?;
+
+    if is_enabled(&mut db_conn, &user.user_id).await? {
+        return Err(ConflictError::default()
+            .with_msg("two-factor authentication is already turned on, turn it off first to use a different app")
+            .with_docs()
+            .into());
+    }
+
+    // an unconfirmed enrolment is simply started over
+    let secret = crate::auth::totp::generate_secret();
+    diesel::insert_into(db_schema::totp_credentials::table)
+        .values(models::db::TotpCredential {
+            user_id: user.user_id.clone(),
+            secret: secret.clone(),
+            confirmed_at: None,
+            last_used_step: None,
+        })
+        .on_conflict(db_schema::totp_credentials::user_id)
+        .do_update()
+        .set(db_schema::totp_credentials::secret.eq(&secret))
+        .execute(&mut db_conn)
+        .await?;
+
+    Ok(CreatedResource {
+        location: format!("/api/user/{email}/mfa"),
+        resource: Resource::new(models::TotpEnrolment {
+            secret: crate::auth::totp::encode_secret(&secret),
+            otpauth_uri: crate::auth::totp::otpauth_uri(&secret, &user.email.to_string()),
+        })
+        .with_links([("confirm", format!("/api/user/{email}/mfa/totp/confirm"))]),
+    })
+}
+
+async fn confirm_totp(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    json: JsonExtract<models::TotpConfirmation>,
+) -> Result<Resource<models::RecoveryCodes>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let user = find_user(&mut db_conn, &user_id.0, &email).await?;
+    let confirmation = json.extract();
+
+    let credential = match find_credential(&mut db_conn, &user.user_id).await? {
+        Some(credential) if credential.confirmed_at.is_none() => credential,
+        Some(_) => {
+            return Err(ConflictError::default()
+                .with_msg("two-factor authentication is already turned on")
+                .with_docs()
+                .into())
+        }
+        None => {
+            return Err(NotFoundError::default()
+                .with_msg("no authenticator app is waiting for confirmation")
+                .with_docs()
+                .with_links([("enrol", format!("/api/user/{email}/mfa/totp"))])
+                .into())
+        }
+    };
+
+    let Some(step) = crate::auth::totp::verify(&credential.secret, &confirmation.code) else {
+        return Err(BadRequestError::default()
+            .with_msg("the code is not correct, check the time settings of the device with the app")
+            .with_docs()
+            .into());
+    };
+
+    diesel::update(db_schema::totp_credentials::table)
+        .filter(db_schema::totp_credentials::user_id.eq(&user.user_id))
+        .set((
+            db_schema::totp_credentials::confirmed_at.eq(chrono::Utc::now()),
+            db_schema::totp_credentials::last_used_step.eq(step),
+        ))
+        .execute(&mut db_conn)
+        .await?;
+
+    let codes = new_recovery_codes(&mut db_conn, &user.user_id).await?;
+
+    Ok(Resource::new(codes).with_links([("mfa", format!("/api/user/{email}/mfa"))]))
+}
+
+async fn disable_totp(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    json: JsonExtract<models::MfaDisable>,
+) -> Result<DeletedResource, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let user = find_user(&mut db_conn, &user_id.0, &email).await?;
+    let confirmation = json.extract();
+
+    if !confirmation.password.match_with(user.password) {
+        Err(BadRequestError::default()
+            .with_msg("password not correct")
+            .with_docs())?;
+    }
+
+    let disabled_user_id = user.user_id;
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                diesel::delete(db_schema::totp_credentials::table)
+                    .filter(db_schema::totp_credentials::user_id.eq(&disabled_user_id))
+                    .execute(tx)
+                    .await?;
+                diesel::delete(db_schema::recovery_codes::table)
+                    .filter(db_schema::recovery_codes::user_id.eq(&disabled_user_id))
+                    .execute(tx)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(DeletedResource {
+        links: std::collections::HashMap::from([("mfa", format!("/api/user/{email}/mfa"))]),
+    })
+}
+
+async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+) -> Result<Resource<models::RecoveryCodes>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let user = find_user(&mut db_conn, &user_id.0, &email).await?;
+
+    if !is_enabled(&mut db_conn, &user.user_id).await? {
+        return Err(BadRequestError::default()
+            .with_msg(
+                "the recovery codes are available once two-factor authentication is turned on",
+            )
+            .with_docs()
+            .with_links([("enrol", format!("/api/user/{email}/mfa/totp"))])
+            .into());
+    }
+
+    let codes = new_recovery_codes(&mut db_conn, &user.user_id).await?;
+
+    Ok(Resource::new(codes).with_links([("mfa", format!("/api/user/{email}/mfa"))]))
+}
+
+/// Checks if the user has to give a second factor to log in.
+pub(crate) async fn is_enabled(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    user_id: &models::types::Uuid,
+) -> Result<bool, ApiError> {
+    Ok(find_credential(db_conn, user_id)
+        .await?
+        .map_or(false, |credential| credential.confirmed_at.is_some()))
+}
+
+/// Checks a code from the authenticator app or one of the recovery codes.
+/// Neither can be used twice.
+pub(crate) async fn verify_code(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    user_id: &models::types::Uuid,
+    code: &str,
+) -> Result<bool, ApiError> {
+    let code = code.trim();
+
+    if code.len() < RECOVERY_CODE_LENGTH && code.chars().all(|ch| ch.is_ascii_digit()) {
+        let Some(credential) = find_credential(db_conn, user_id).await? else {
+            return Ok(false);
+        };
+        let step = match crate::auth::totp::verify(&credential.secret, code) {
+            Some(step) if credential.last_used_step.map_or(true, |last| step > last) => step,
+            _ => return Ok(false),
+        };
+        // The step is checked again by the update so that only one of the
+        // requests racing with the same code can use it
+        let updated = diesel::update(db_schema::totp_credentials::table)
+            .filter(db_schema::totp_credentials::user_id.eq(user_id))
+            .filter(db_schema::totp_credentials::confirmed_at.is_not_null())
+            .filter(
+                db_schema::totp_credentials::last_used_step
+                    .is_null()
+                    .or(db_schema::totp_credentials::last_used_step.lt(step)),
+            )
+            .set(db_schema::totp_credentials::last_used_step.eq(step))
+            .execute(db_conn)
+            .await?;
+        return Ok(updated == 1);
+    }
+
+    let used = diesel::delete(db_schema::recovery_codes::table)
+        .filter(db_schema::recovery_codes::user_id.eq(user_id))
+        .filter(db_schema::recovery_codes::code_hash.eq(crate::auth::opaque::hash(code)))
+        .execute(db_conn)
+        .await?;
+    Ok(used == 1)
+}
+
+/// Starts a challenge if the user has to give a second factor to log in.
+pub(crate) async fn start_challenge(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    user_id: &models::types::Uuid,
+) -> Result<Option<MfaRequired>, ApiError> {
+    if !is_enabled(db_conn, user_id).await? {
+        return Ok(None);
+    }
+
+    let mfa_token = crate::auth::opaque::generate(MFA_TOKEN_LENGTH);
+    diesel::insert_into(db_schema::mfa_challenges::table)
+        .values(models::db::MfaChallenge {
+            challenge_hash: crate::auth::opaque::hash(&mfa_token),
+            user_id: user_id.clone(),
+            attempts: 0,
+            expires_at: chrono::Utc::now()
+                .checked_add_signed(chrono::Duration::seconds(MFA_CHALLENGE_LIFETIME_SECONDS))
+                .unwrap(),
+        })
+        .execute(db_conn)
+        .await?;
+
+    Ok(Some(MfaRequired {
+        error: "mfa_required",
+        mfa_token,
+        expires_in: MFA_CHALLENGE_LIFETIME_SECONDS,
+    }))
+}
+
+/// Finds the user who has to answer the challenge started with the password.
+pub(crate) async fn challenged_user(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    mfa_token: &str,
+) -> Result<models::types::Uuid, ApiError> {
+    db_schema::mfa_challenges::table
+        .filter(db_schema::mfa_challenges::challenge_hash.eq(crate::auth::opaque::hash(mfa_token)))
+        .filter(db_schema::mfa_challenges::expires_at.gt(chrono::Utc::now()))
+        .select(db_schema::mfa_challenges::user_id)
+        .first(db_conn)
+        .await
+        .optional()?
+        .ok_or_else(invalid_challenge)
+}
+
+/// Answers the challenge found with [`challenged_user`], returning if the code
+/// is correct. The challenge ends once it is answered or when it runs out of
+/// attempts.
+pub(crate) async fn answer_challenge(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    mfa_token: &str,
+    code: &str,
+) -> Result<bool, ApiError> {
+    let challenge_hash = crate::auth::opaque::hash(mfa_token);
+    // The attempt is counted before the code is checked so that the requests
+    // racing with the same challenge cannot try more codes than allowed
+    let challenge: Option<models::db::MfaChallenge> =
+        diesel::update(db_schema::mfa_challenges::table)
+            .filter(db_schema::mfa_challenges::challenge_hash.eq(&challenge_hash))
+            .filter(db_schema::mfa_challenges::expires_at.gt(chrono::Utc::now()))
+            .filter(db_schema::mfa_challenges::attempts.lt(MFA_CHALLENGE_MAX_ATTEMPTS))
+            .set(db_schema::mfa_challenges::attempts.eq(db_schema::mfa_challenges::attempts + 1))
+            .get_result(db_conn)
+            .await
+            .optional()?;
+    let Some(challenge) = challenge else {
+        return Err(invalid_challenge());
+    };
+
+    let answered = verify_code(db_conn, &challenge.user_id, code).await?;
+    if answered || challenge.attempts >= MFA_CHALLENGE_MAX_ATTEMPTS {
+        diesel::delete(db_schema::mfa_challenges::table)
+            .filter(db_schema::mfa_challenges::challenge_hash.eq(&challenge_hash))
+            .execute(db_conn)
+            .await?;
+    }
+    Ok(answered)
+}
+
+fn invalid_challenge() -> ApiError {
+    BadRequestError::default()
+        .with_msg("the mfa_token is not valid, log in with the password again")
+        .with_docs()
+        .with_links([("new session", "/api/session/token".to_owned())])
+        .into()
+}
+
+pub(crate) async fn purge_expired_challenges(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::mfa_challenges::table)
+        .filter(db_schema::mfa_challenges::expires_at.lt(chrono::Utc::now()))
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
@@ -1,11 +1,26 @@
 use crate::prelude::*;
 
+pub mod archive;
+pub mod audit;
 pub mod authorized_client;
+pub mod bulk;
+pub mod device;
+pub mod email_change;
 pub mod list;
+pub mod login_protection;
+pub mod magic_link;
+pub mod mfa;
+pub mod oauth;
+pub mod oidc;
+pub mod password_reset;
+pub mod personal_access_token;
 pub mod registration;
 pub mod session;
+pub mod settings;
 pub mod task;
+pub mod task_history;
 pub mod tracker;
+pub mod trash;
 pub mod user;
 pub mod view;
 
@@ -20,8 +35,21 @@ pub fn app_services() -> axum::Router<crate::AppState> {
             .merge(tracker::router())
             .merge(user::router())
             .merge(registration::router())
+            .merge(password_reset::router())
+            .merge(email_change::router())
             .merge(list::router())
+            .merge(task_history::router())
+            .merge(trash::router())
+            .merge(archive::router())
+            .merge(bulk::router())
             .merge(session::router())
+            .merge(magic_link::router())
+            .merge(oidc::router())
+            .merge(audit::router())
+            .merge(settings::router())
+            .merge(device::router())
+            .merge(personal_access_token::router())
+            .merge(mfa::router())
             .merge(authorized_client::router()), // .merge(view::router()),
     );
 
@@ -65,6 +93,13 @@ pub fn app_services() -> axum::Router<crate::AppState> {
                 .to_owned(),
         ),
         ..openapi::Tag::default()
+    }, openapi::Tag {
+        name: "Account Management".to_owned(),
+        description: Some(
+            "The account management endpoints allow the user to modify or remove their account. Actions with irreversible consequences require the current password to be confirmed."
+                .to_owned(),
+        ),
+        ..openapi::Tag::default()
     }, openapi::Tag {
         name: "Task Management".to_owned(),
         description: Some(
@@ -84,6 +119,105 @@ pub fn app_services() -> axum::Router<crate::AppState> {
             "/doc",
             aide::redoc::Redoc::new("/openapi.json").axum_route().into(),
         )
+        .route("/.well-known/jwks.json", axum::routing::get(serve_jwks))
+        .route(
+            "/.well-known/openid-configuration",
+            axum::routing::get(oidc::serve_configuration),
+        )
+        .merge(oauth::router())
+}
+
+/// Collects the maintenance jobs of all the services.
+pub fn app_jobs() -> crate::scheduler::Scheduler {
+    use std::time::Duration;
+
+    crate::scheduler::Scheduler::new()
+        .job(
+            "purge expired registration requests",
+            Duration::from_secs(10 * 60),
+            registration::purge_expired_requests,
+        )
+        .job(
+            "purge expired sessions",
+            Duration::from_secs(60 * 60),
+            session::purge_expired_sessions,
+        )
+        .job(
+            "purge expired password reset requests",
+            Duration::from_secs(15 * 60),
+            password_reset::purge_expired_requests,
+        )
+        .job(
+            "purge expired email changes",
+            Duration::from_secs(15 * 60),
+            email_change::purge_expired,
+        )
+        .job(
+            "purge expired authorization codes",
+            Duration::from_secs(10 * 60),
+            oauth::purge_expired_codes,
+        )
+        .job(
+            "purge expired device authorizations",
+            Duration::from_secs(10 * 60),
+            device::purge_expired_authorizations,
+        )
+        .job(
+            "purge expired magic link codes",
+            Duration::from_secs(10 * 60),
+            magic_link::purge_expired_codes,
+        )
+        .job(
+            "purge expired mfa challenges",
+            Duration::from_secs(10 * 60),
+            mfa::purge_expired_challenges,
+        )
+        .job(
+            "purge expired personal access tokens",
+            Duration::from_secs(60 * 60),
+            personal_access_token::purge_expired_tokens,
+        )
+        .job(
+            "purge old audit events",
+            Duration::from_secs(24 * 60 * 60),
+            audit::purge_old_events,
+        )
+        .job(
+            "archive finished tasks",
+            Duration::from_secs(60 * 60),
+            archive::archive_finished_tasks,
+        )
+        .job(
+            "purge expired trash",
+            Duration::from_secs(24 * 60 * 60),
+            trash::purge_expired,
+        )
+        .job(
+            "purge old failed login records",
+            Duration::from_secs(24 * 60 * 60),
+            login_protection::purge_old_records,
+        )
+        .job(
+            "refresh revoked tokens",
+            Duration::from_secs(60),
+            crate::auth::revocation::refresh_revoked_tokens,
+        )
+        .job(
+            "refresh email redirects",
+            Duration::from_secs(60),
+            email_change::refresh_redirects,
+        )
+        .job(
+            "purge stale rate limits",
+            Duration::from_secs(60 * 60),
+            |state: crate::AppState| async move {
+                state.code_mail_limiter.purge();
+                state.magic_link_mail_limiter.purge();
+                state.login_account_backoff.purge();
+                state.login_address_backoff.purge();
+                Ok::<_, anyhow::Error>(())
+            },
+        )
 }
 
 async fn serve_oas(
@@ -91,3 +225,9 @@ async fn serve_oas(
 ) -> Json<openapi::OpenApi> {
     Json(oas)
 }
+
+/// Publishes the keys which can be used to verify the tokens issued by the
+/// server.
+async fn serve_jwks() -> Json<serde_json::Value> {
+    Json(crate::auth::keys::keys().public_keys().clone())
+}
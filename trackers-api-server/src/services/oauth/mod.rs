@@ -0,0 +1,525 @@
+//! OAuth2 authorization code flow with PKCE (RFC 6749 and RFC 7636).
+//!
+//! A third-party application sends the user to `/oauth/authorize` where the
+//! user logs in and agrees to the requested scopes. The user is then
+//! redirected back to one of the redirect URIs registered for the
+//! [`AuthorisedClient`](models::AuthorisedClient) with a short-lived code. The
+//! application exchanges the code for the tokens at `/api/session/token` using
+//! the `authorization_code` grant and the code verifier it has generated.
+//!
+//! The devices using the device authorization grant send the user to the
+//! `/oauth/device` page to approve them, see [`services::device`].
+//!
+//! The endpoints serve HTML for the browser so they are not a part of the API
+//! documentation.
+
+use base64::Engine;
+use sha2::Digest;
+
+use crate::{mail::templates::escape_html, prelude::*};
+
+/// How long the authorization code can be exchanged for the tokens.
+const AUTHORIZATION_CODE_LIFETIME_SECONDS: i64 = 2 * 60;
+
+const AUTHORIZATION_CODE_LENGTH: usize = 32;
+
+pub fn router() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route(
+            "/oauth/authorize",
+            axum::routing::get(authorization_page).post(authorize),
+        )
+        .route(
+            "/oauth/device",
+            axum::routing::get(device_page).post(approve_device),
+        )
+        .layer(axum::middleware::from_fn(forbid_framing))
+}
+
+/// Keeps the pages out of the frames of other sites, where the users could be
+/// tricked into approving a client.
+async fn forbid_framing<B: Send>(
+    request: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> axum::response::Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(
+        axum::http::header::X_FRAME_OPTIONS,
+        axum::http::HeaderValue::from_static("DENY"),
+    );
+    headers.insert(
+        axum::http::header::CONTENT_SECURITY_POLICY,
+        axum::http::HeaderValue::from_static("frame-ancestors 'none'"),
+    );
+    response
+}
+
+/// Parameters of the authorization request sent by the client application.
+#[derive(Deserialize)]
+pub struct AuthorizationRequest {
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    /// Space separated list of the requested scopes
+    pub scope: Option<String>,
+    /// Passed back to the client unchanged
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    /// Only `S256` is supported
+    pub code_challenge_method: Option<String>,
+    /// Put into the ID token unchanged when the `openid` scope is requested
+    pub nonce: Option<String>,
+}
+
+/// Submitted consent page.
+#[derive(Deserialize)]
+pub struct ConsentForm {
+    #[serde(flatten)]
+    pub request: AuthorizationRequest,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default)]
+    pub password: String,
+    /// Code from the authenticator app, required only if two-factor
+    /// authentication is turned on
+    #[serde(default)]
+    pub otp: String,
+    /// Either `allow` or `deny`
+    pub decision: String,
+}
+
+/// The user code shown on the device, filled in when the user follows the
+/// complete verification URI.
+#[derive(Deserialize)]
+pub struct DeviceQuery {
+    pub user_code: Option<String>,
+}
+
+/// Submitted device approval page.
+#[derive(Deserialize)]
+pub struct DeviceForm {
+    pub user_code: String,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default)]
+    pub password: String,
+    /// Code from the authenticator app, required only if two-factor
+    /// authentication is turned on
+    #[serde(default)]
+    pub otp: String,
+    /// Either `allow` or `deny`
+    pub decision: String,
+}
+
+/// An authorization request which has been checked and can be presented to
+/// the user.
+struct ValidRequest {
+    client: models::db::AuthorisedClient,
+    redirect_uri: String,
+    scope: Vec<String>,
+    state: Option<String>,
+    code_challenge: String,
+    nonce: Option<String>,
+}
+
+async fn authorization_page(
+    State(state): State<AppState>,
+    axum::extract::Query(request): axum::extract::Query<AuthorizationRequest>,
+) -> Result<axum::response::Response, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    Ok(match validate_request(&mut db_conn, request).await? {
+        Ok(request) => consent_page(&request, None).into_response(),
+        Err(redirect) => redirect.into_response(),
+    })
+}
+
+async fn authorize(
+    State(state): State<AppState>,
+    crate::client_addr::ClientAddr(client_addr): crate::client_addr::ClientAddr,
+    user_agent: Option<axum::TypedHeader<axum::headers::UserAgent>>,
+    axum::extract::Form(form): axum::extract::Form<ConsentForm>,
+) -> Result<axum::response::Response, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let request = match validate_request(&mut db_conn, form.request).await? {
+        Ok(request) => request,
+        Err(redirect) => return Ok(redirect.into_response()),
+    };
+
+    if form.decision != "allow" {
+        return Ok(redirect_with(
+            &request.redirect_uri,
+            &[("error", "access_denied")],
+            request.state.as_deref(),
+        )
+        .into_response());
+    }
+
+    // The page cannot tell which of them was wrong either
+    let Some(user) = check_credentials(
+        &state,
+        &mut db_conn,
+        client_addr,
+        user_agent,
+        form.email,
+        form.password,
+        &form.otp,
+    )
+    .await?
+    else {
+        return Ok(
+            consent_page(&request, Some("email, password or code not correct")).into_response(),
+        );
+    };
+
+    let code = crate::auth::opaque::generate(AUTHORIZATION_CODE_LENGTH);
+    diesel::insert_into(db_schema::authorization_codes::table)
+        .values(models::db::AuthorizationCode {
+            code_hash: crate::auth::opaque::hash(&code),
+            client_id: request.client.client_id.clone(),
+            user_id: user.user_id,
+            redirect_uri: request.redirect_uri.clone(),
+            scope: request.scope.join(" "),
+            code_challenge: request.code_challenge.clone(),
+            expires_at: chrono::Utc::now()
+                .checked_add_signed(chrono::Duration::seconds(
+                    AUTHORIZATION_CODE_LIFETIME_SECONDS,
+                ))
+                .unwrap(),
+            nonce: request.nonce.clone(),
+        })
+        .execute(&mut db_conn)
+        .await?;
+
+    Ok(redirect_with(
+        &request.redirect_uri,
+        &[("code", code.as_str())],
+        request.state.as_deref(),
+    )
+    .into_response())
+}
+
+async fn device_page(
+    State(state): State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<DeviceQuery>,
+) -> Result<axum::response::Html<String>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let user_code = query.user_code.unwrap_or_default();
+    let pending = match user_code.is_empty() {
+        true => None,
+        false => services::device::find_pending(&mut db_conn, &user_code).await?,
+    };
+    Ok(match pending {
+        Some(pending) => device_page_with(
+            &user_code,
+            "Check that the code matches the one shown on the device. The device will be allowed to:",
+            &pending.scope.split_whitespace().collect::<Vec<_>>(),
+        ),
+        None => device_page_with(&user_code, "Enter the code shown on the device.", &[]),
+    })
+}
+
+async fn approve_device(
+    State(state): State<AppState>,
+    crate::client_addr::ClientAddr(client_addr): crate::client_addr::ClientAddr,
+    user_agent: Option<axum::TypedHeader<axum::headers::UserAgent>>,
+    axum::extract::Form(form): axum::extract::Form<DeviceForm>,
+) -> Result<axum::response::Html<String>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let Some(pending) = services::device::find_pending(&mut db_conn, &form.user_code).await? else {
+        return Ok(device_page_with(
+            &form.user_code,
+            "The code is not valid or has expired. Start again on the device.",
+            &[],
+        ));
+    };
+    let scope = pending.scope.split_whitespace().collect::<Vec<_>>();
+
+    let Some(user) = check_credentials(
+        &state,
+        &mut db_conn,
+        client_addr,
+        user_agent,
+        form.email,
+        form.password,
+        &form.otp,
+    )
+    .await?
+    else {
+        return Ok(device_page_with(
+            &form.user_code,
+            "Email, password or code not correct. The device will be allowed to:",
+            &scope,
+        ));
+    };
+
+    let approve = form.decision == "allow";
+    services::device::decide(&mut db_conn, &pending, &user.user_id, approve).await?;
+
+    let message = match approve {
+        true => "The device is connected to your account, you can return to it now.",
+        false => "The device has not been connected to your account.",
+    };
+    Ok(axum::response::Html(
+        include_str!("device.html")
+            .replace("{{scopes}}", "")
+            .replace("{{message}}", message)
+            .replace("{{user_code}}", ""),
+    ))
+}
+
+fn device_page_with(
+    user_code: &str,
+    message: &str,
+    scope: &[&str],
+) -> axum::response::Html<String> {
+    axum::response::Html(
+        include_str!("device.html")
+            .replace("{{scopes}}", &scope_descriptions(scope))
+            .replace("{{message}}", &escape_html(message))
+            .replace("{{user_code}}", &escape_html(user_code)),
+    )
+}
+
+/// Checks the authorization request. An unknown client or redirect URI
+/// results in an error shown to the user, since the user cannot be safely
+/// redirected then. Other problems are reported back to the client with a
+/// redirect.
+async fn validate_request(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    request: AuthorizationRequest,
+) -> Result<Result<ValidRequest, axum::response::Redirect>, ApiError> {
+    let client_search_res: Result<models::db::AuthorisedClient, _> =
+        db_schema::authorised_clients::table
+            .filter(db_schema::authorised_clients::client_id.eq(&request.client_id))
+            .first(db_conn)
+            .await;
+    let client = match client_search_res {
+        Ok(client) if client.redirect_uris.contains(&request.redirect_uri) => client,
+        Ok(_) | Err(diesel::result::Error::NotFound) => {
+            return Err(BadRequestError::default()
+                .with_msg("unknown client_id or a redirect_uri not registered for the client")
+                .with_docs()
+                .into());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let state = request.state.filter(|state| !state.is_empty());
+    let error = |error: &str, description: &str| {
+        redirect_with(
+            &request.redirect_uri,
+            &[("error", error), ("error_description", description)],
+            state.as_deref(),
+        )
+    };
+
+    if request.response_type != "code" {
+        return Ok(Err(error(
+            "unsupported_response_type",
+            "only the code response type is supported",
+        )));
+    }
+
+    let code_challenge = match (
+        request
+            .code_challenge
+            .filter(|challenge| !challenge.is_empty()),
+        request.code_challenge_method.as_deref(),
+    ) {
+        (Some(challenge), Some("S256")) => challenge,
+        (None, _) => return Ok(Err(error("invalid_request", "code_challenge is required"))),
+        (Some(_), _) => {
+            return Ok(Err(error(
+                "invalid_request",
+                "code_challenge_method must be S256",
+            )))
+        }
+    };
+
+    let Some(scope) = crate::auth::scope::parse_requested(request.scope.as_deref()) else {
+        return Ok(Err(error("invalid_scope", "unknown scope requested")));
+    };
+    if !scope
+        .iter()
+        .all(|name| crate::auth::scope::is_covered(name, &client.scope))
+    {
+        return Ok(Err(error(
+            "invalid_scope",
+            "the requested scope exceeds the scope of the client",
+        )));
+    }
+
+    Ok(Ok(ValidRequest {
+        client,
+        redirect_uri: request.redirect_uri,
+        scope,
+        state,
+        code_challenge,
+        nonce: request.nonce.filter(|nonce| !nonce.is_empty()),
+    }))
+}
+
+/// Describes the scopes in a form which can be presented to the user.
+fn scope_descriptions<S: AsRef<str>>(scope: &[S]) -> String {
+    scope
+        .iter()
+        .map(|name| {
+            format!(
+                "    <li>{}</li>",
+                escape_html(&crate::auth::scope::describe(name.as_ref()))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Checks the credentials given in the forms. The code from the authenticator
+/// app is checked only for the accounts with two-factor authentication on.
+/// Wrong codes count as failed logins just like wrong passwords.
+pub(crate) async fn check_credentials(
+    state: &AppState,
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    client_addr: std::net::IpAddr,
+    user_agent: Option<axum::TypedHeader<axum::headers::UserAgent>>,
+    email: String,
+    password: String,
+    otp: &str,
+) -> Result<Option<models::db::User>, ApiError> {
+    let (Ok(email), Ok(password)) = (
+        EmailAddress::try_from(email),
+        models::types::PasswordInput::try_from(password),
+    ) else {
+        return Ok(None);
+    };
+    services::login_protection::check_allowed(state, &email, client_addr)?;
+
+    let user_search_res: Result<models::db::User, _> = db_schema::users::table
+        .filter(db_schema::users::email.eq(&email))
+        .first(db_conn)
+        .await;
+    let user = match user_search_res {
+        Ok(user) => Some(user),
+        Err(diesel::result::Error::NotFound) => None,
+        Err(err) => return Err(err.into()),
+    };
+
+    let accepted = match &user {
+        Some(user) if password.match_with(user.password.clone()) => {
+            !services::mfa::is_enabled(db_conn, &user.user_id).await?
+                || services::mfa::verify_code(db_conn, &user.user_id, otp).await?
+        }
+        _ => false,
+    };
+    if !accepted {
+        let user_agent = user_agent.map(|axum::TypedHeader(user_agent)| user_agent.to_string());
+        services::login_protection::record_failure(
+            state,
+            db_conn,
+            &email,
+            user.map(|user| user.user_id),
+            client_addr,
+            user_agent.as_deref(),
+        )
+        .await?;
+        return Ok(None);
+    }
+    services::login_protection::record_success(state, &email, client_addr);
+    if let Some(user) = &user {
+        let actor = services::audit::Actor {
+            client: None,
+            ip_address: Some(client_addr.to_string()),
+        };
+        services::audit::Event::new(&user.user_id, models::types::AuditAction::LoginSucceeded)
+            .details("to give access to a client application")
+            .record(db_conn, &actor)
+            .await?;
+    }
+    Ok(user)
+}
+
+fn consent_page(request: &ValidRequest, error: Option<&str>) -> axum::response::Html<String> {
+    let scopes = scope_descriptions(&request.scope);
+    let client_id = request.client.client_id.to_string();
+    let scope = request.scope.join(" ");
+
+    // The clients registered before the website had to be a web URL may
+    // still have a website which cannot be linked to
+    let client_link = if services::authorized_client::is_web_url(&request.client.website) {
+        format!(
+            "<a href=\"{}\">{}</a>",
+            escape_html(&request.client.website),
+            escape_html(&request.client.name)
+        )
+    } else {
+        escape_html(&request.client.name)
+    };
+
+    let mut page = include_str!("consent.html")
+        .replace("{{scopes}}", &scopes)
+        .replace("{{client_link}}", &client_link);
+    for (name, value) in [
+        ("client_name", request.client.name.as_str()),
+        ("error", error.unwrap_or_default()),
+        ("response_type", "code"),
+        ("client_id", client_id.as_str()),
+        ("redirect_uri", request.redirect_uri.as_str()),
+        ("scope", scope.as_str()),
+        ("state", request.state.as_deref().unwrap_or_default()),
+        ("code_challenge", request.code_challenge.as_str()),
+        ("code_challenge_method", "S256"),
+        ("nonce", request.nonce.as_deref().unwrap_or_default()),
+    ] {
+        page = page.replace(&format!("{{{{{name}}}}}"), &escape_html(value));
+    }
+    axum::response::Html(page)
+}
+
+fn redirect_with(
+    redirect_uri: &str,
+    params: &[(&str, &str)],
+    state: Option<&str>,
+) -> axum::response::Redirect {
+    // The URI has been checked to be one of the registered ones which are
+    // validated when the client is authorised
+    let mut url = reqwest::Url::parse(redirect_uri).expect("registered redirect URI is invalid");
+    {
+        let mut query = url.query_pairs_mut();
+        query.extend_pairs(params);
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+    }
+    axum::response::Redirect::to(url.as_str())
+}
+
+/// Checks the PKCE code verifier against the challenge stored with the code.
+pub(crate) fn verify_code_challenge(code_verifier: &str, code_challenge: &str) -> bool {
+    // RFC 7636 requires the verifier to be between 43 and 128 characters long
+    (43..=128).contains(&code_verifier.len())
+        && base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(sha2::Sha256::digest(code_verifier.as_bytes()))
+            == code_challenge
+}
+
+/// Removes the authorization codes which have not been exchanged in time.
+pub(crate) async fn purge_expired_codes(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::authorization_codes::table)
+        .filter(db_schema::authorization_codes::expires_at.lt(chrono::Utc::now()))
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
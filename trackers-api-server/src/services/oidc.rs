@@ -0,0 +1,132 @@
+//! Minimal OpenID Connect provider built on top of the OAuth2 flows.
+//!
+//! The clients asking for the `openid` scope receive an ID token along with
+//! the access token and can use the access token at `/api/userinfo`. The
+//! configuration of the provider is published at
+//! `/.well-known/openid-configuration`.
+//!
+//! The ID tokens are signed with the same keys as the access tokens. With the
+//! default HS256 signing the key is a secret of the server and the published
+//! JWK set is empty, so the clients cannot verify the signature of the ID
+//! tokens. They can still trust the ID tokens received directly from the
+//! token endpoint over TLS, as allowed by OpenID Connect Core 1.0, or use
+//! `/api/userinfo` instead. Set up `JWT_KEYS_DIR` to issue ID tokens which can
+//! be verified.
+
+use crate::prelude::*;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new().api_route_with(
+        "/userinfo",
+        routing::get_with(get_user_info, |op| {
+            op.summary("Fetch the claims about the user")
+                .description("Works as described in OpenID Connect Core 1.0. The access token must have been granted the `openid` scope.")
+        })
+        .layer(axum::middleware::from_fn_with_state(
+            crate::auth::scope::RequiredScope::new(
+                crate::auth::scope::OPENID,
+                crate::auth::scope::OPENID,
+            ),
+            crate::auth::scope::require_scope,
+        ))
+        .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone())),
+        |op| op.tag("Log-in"),
+    )
+}
+
+/// Claims about the user the access token has been issued for.
+#[derive(Serialize, JsonSchema)]
+pub struct UserInfo {
+    /// ID of the user, the same as in the `sub` claim of the ID token
+    pub sub: String,
+    pub email: EmailAddress,
+    /// Always true, the address is confirmed during the registration
+    pub email_verified: bool,
+}
+
+/// Metadata of the provider as described in OpenID Connect Discovery 1.0.
+#[derive(Serialize)]
+pub struct ProviderConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub device_authorization_endpoint: String,
+    pub revocation_endpoint: String,
+    pub introspection_endpoint: String,
+    pub scopes_supported: Vec<&'static str>,
+    pub response_types_supported: [&'static str; 1],
+    pub grant_types_supported: [&'static str; 4],
+    pub subject_types_supported: [&'static str; 1],
+    pub id_token_signing_alg_values_supported: [String; 1],
+    pub token_endpoint_auth_methods_supported: [&'static str; 1],
+    pub code_challenge_methods_supported: [&'static str; 1],
+    pub claims_supported: [&'static str; 8],
+}
+
+async fn get_user_info(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+) -> Result<Json<UserInfo>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let email: EmailAddress = db_schema::users::table
+        .find(&user_id.0)
+        .select(db_schema::users::email)
+        .first(&mut db_conn)
+        .await?;
+
+    Ok(Json(UserInfo {
+        sub: user_id.to_string(),
+        email,
+        email_verified: true,
+    }))
+}
+
+/// Publishes the configuration of the OpenID Connect provider.
+pub(crate) async fn serve_configuration(
+    host: Option<axum::TypedHeader<axum::headers::Host>>,
+) -> Json<ProviderConfiguration> {
+    let host = host.map(|axum::TypedHeader(host)| host);
+    let public_url = crate::public_url(host.as_ref());
+
+    let mut scopes_supported = vec![crate::auth::scope::OPENID, crate::auth::scope::FULL_ACCESS];
+    scopes_supported.extend(crate::auth::scope::permission::ALL);
+
+    Json(ProviderConfiguration {
+        issuer: crate::auth::layer::issuer(),
+        authorization_endpoint: format!("{public_url}/oauth/authorize"),
+        token_endpoint: format!("{public_url}/api/session/token"),
+        userinfo_endpoint: format!("{public_url}/api/userinfo"),
+        jwks_uri: format!("{public_url}/.well-known/jwks.json"),
+        device_authorization_endpoint: format!("{public_url}/api/session/device"),
+        revocation_endpoint: format!("{public_url}/api/session/revoke"),
+        introspection_endpoint: format!("{public_url}/api/session/introspect"),
+        scopes_supported,
+        response_types_supported: ["code"],
+        grant_types_supported: [
+            "authorization_code",
+            "refresh_token",
+            "client_credentials",
+            "urn:ietf:params:oauth:grant-type:device_code",
+        ],
+        subject_types_supported: ["public"],
+        id_token_signing_alg_values_supported: [crate::auth::keys::keys().published_algorithm()],
+        token_endpoint_auth_methods_supported: ["client_secret_post"],
+        code_challenge_methods_supported: ["S256"],
+        claims_supported: [
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "email",
+            "email_verified",
+            "nonce",
+        ],
+    })
+}
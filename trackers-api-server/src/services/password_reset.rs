@@ -0,0 +1,230 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
+use models::{PasswordResetConfirmation, PasswordResetInit, PasswordResetRequest};
+
+use crate::prelude::*;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/password-reset",
+            routing::post_with(request_password_reset, |op| {
+                op.summary("Request a password reset")
+                    .description("If an account with the provided email address exists a confirmation code will be sent to that address. The response is the same whether the account exists or not.")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .api_route_with(
+            "/password-reset/:email/code",
+            routing::post_with(confirm_password_reset, |op| {
+                op.summary("Set a new password using the confirmation code")
+                    .description("The code can be used only once. All the sessions started before the password change are ended.")
+            }),
+            |op| op.tag("Account Management"),
+        )
+}
+
+/// How long a password reset code can be used after it has been sent.
+const RESET_CODE_LIFETIME_MINUTES: i64 = 15;
+/// How many incorrect codes are accepted before the request is cancelled.
+const MAX_FAILED_ATTEMPTS: i32 = 5;
+
+async fn request_password_reset(
+    State(state): State<AppState>,
+    crate::client_addr::ClientAddr(client_addr): crate::client_addr::ClientAddr,
+    json: JsonExtract<PasswordResetInit>,
+) -> Result<CreatedResource<PasswordResetRequest>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let input = json.extract();
+
+    // Limited even if the account does not exist so that the limit does not
+    // reveal it
+    crate::services::registration::limit_code_mails(&state, client_addr)?;
+
+    let issued_at = chrono::Utc::now();
+    let valid_until = issued_at
+        .checked_add_signed(chrono::Duration::minutes(RESET_CODE_LIFETIME_MINUTES))
+        .unwrap();
+
+    let user_search_res: Result<models::types::Uuid, _> = db_schema::users::table
+        .filter(db_schema::users::email.eq(&input.email))
+        .select(db_schema::users::user_id)
+        .first(&mut db_conn)
+        .await;
+
+    match user_search_res {
+        Ok(user_id) => {
+            let code = models::ConfirmationCode::new();
+            #[cfg(debug_assertions)]
+            println!("password reset code {code} has been generated");
+
+            // A new request replaces any request issued earlier
+            let code_hash = code.to_storeable();
+            diesel::insert_into(db_schema::password_reset_requests::table)
+                .values(models::db::PasswordResetRequest {
+                    user_id,
+                    issued_at,
+                    valid_until,
+                    code_hash: code_hash.clone(),
+                    failed_attempts: 0,
+                })
+                .on_conflict(db_schema::password_reset_requests::user_id)
+                .do_update()
+                .set((
+                    db_schema::password_reset_requests::issued_at.eq(issued_at),
+                    db_schema::password_reset_requests::valid_until.eq(valid_until),
+                    db_schema::password_reset_requests::code_hash.eq(code_hash),
+                    db_schema::password_reset_requests::failed_attempts.eq(0),
+                ))
+                .execute(&mut db_conn)
+                .await?;
+
+            // Mailed in the background so that the response does not wait
+            // for the mail transport, which it does not do for the addresses
+            // without an account
+            let mail = crate::mail::templates::PASSWORD_RESET_CODE
+                .render(&input.email, &[("code", &code)]);
+            let mailer = state.mailer.clone();
+            tokio::spawn(async move {
+                if let Err(err) = mailer.send(mail).await {
+                    eprintln!("failed to send the password reset code: {err}");
+                }
+            });
+        }
+        // Do not reveal which email addresses are used by the accounts. The
+        // code is hashed anyway so that the response takes as long as for the
+        // existing ones.
+        Err(diesel::result::Error::NotFound) => {
+            std::hint::black_box(models::ConfirmationCode::new().to_storeable());
+        }
+        Err(err) => return Err(err.into()),
+    }
+
+    Ok(CreatedResource {
+        location: format!("/api/password-reset/{}/code", &input.email),
+        resource: Resource::new(PasswordResetRequest {
+            issued_at,
+            valid_until,
+            user_email: input.email.clone(),
+            confirmed_with_code: false,
+        })
+        .with_links([(
+            "confirm",
+            format!("/api/password-reset/{}/code", &input.email),
+        )]),
+    })
+}
+
+async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Path(email): Path<models::types::Email>,
+    json: JsonExtract<PasswordResetConfirmation, crate::services::user::UserCreationRejection>,
+) -> Result<ModifiedResource<PasswordResetRequest>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let confirmation = json.extract();
+
+    let search_res: Result<models::db::PasswordResetRequest, _> =
+        db_schema::password_reset_requests::table
+            .inner_join(db_schema::users::table)
+            .filter(db_schema::users::email.eq(&email))
+            .select(db_schema::password_reset_requests::all_columns)
+            .first(&mut db_conn)
+            .await;
+    let req = match search_res {
+        Ok(req) => req,
+        Err(diesel::result::Error::NotFound) => {
+            return Err(NotFoundError::default()
+                .with_docs()
+                .with_msg("no password reset has been requested for the email address")
+                .with_links([("request reset", "/api/password-reset".to_owned())])
+                .into())
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    // Request no longer valid
+    if req.valid_until < chrono::Utc::now() {
+        diesel::delete(db_schema::password_reset_requests::table)
+            .filter(db_schema::password_reset_requests::user_id.eq(&req.user_id))
+            .execute(&mut db_conn)
+            .await?;
+        return Err(GoneError::default()
+            .with_docs()
+            .with_msg("the request is no longer valid")
+            .with_links([("request reset", "/api/password-reset".to_owned())])
+            .into());
+    }
+
+    // Not the code we expected
+    if !confirmation.code.match_with(req.code_hash) {
+        // The counter is incremented in the database so that concurrent
+        // guesses cannot slip past the limit
+        let failed_attempts: i32 = diesel::update(db_schema::password_reset_requests::table)
+            .filter(db_schema::password_reset_requests::user_id.eq(&req.user_id))
+            .set(
+                db_schema::password_reset_requests::failed_attempts
+                    .eq(db_schema::password_reset_requests::failed_attempts + 1),
+            )
+            .returning(db_schema::password_reset_requests::failed_attempts)
+            .get_result(&mut db_conn)
+            .await?;
+        if failed_attempts >= MAX_FAILED_ATTEMPTS {
+            diesel::delete(db_schema::password_reset_requests::table)
+                .filter(db_schema::password_reset_requests::user_id.eq(&req.user_id))
+                .execute(&mut db_conn)
+                .await?;
+            return Err(GoneError::default()
+                .with_docs()
+                .with_msg("too many incorrect codes, the request has been cancelled")
+                .with_links([("request reset", "/api/password-reset".to_owned())])
+                .into());
+        }
+        return Err(BadRequestError::default()
+            .with_docs()
+            .with_msg("incorrect code")
+            .into());
+    }
+
+    let user_id = req.user_id;
+    let new_password = confirmation.new_password.into_storeable();
+    let revoked = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                diesel::update(db_schema::users::table)
+                    .filter(db_schema::users::user_id.eq(&user_id))
+                    .set(db_schema::users::password.eq(new_password))
+                    .execute(tx)
+                    .await?;
+                // the code is single-use
+                diesel::delete(db_schema::password_reset_requests::table)
+                    .filter(db_schema::password_reset_requests::user_id.eq(&user_id))
+                    .execute(tx)
+                    .await?;
+                // whoever knew the old password should no longer be logged in
+                services::session::end_all_sessions(tx, &user_id).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    state.revoked_tokens.remember(revoked);
+
+    Ok(ModifiedResource {
+        location: None,
+        resource: Resource::new(PasswordResetRequest {
+            issued_at: req.issued_at,
+            valid_until: req.valid_until,
+            user_email: email,
+            confirmed_with_code: true,
+        })
+        .with_links([("new session", "/api/session/token".to_owned())]),
+    })
+}
+
+/// Removes the password reset requests which have not been confirmed in time.
+pub(crate) async fn purge_expired_requests(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::password_reset_requests::table)
+        .filter(db_schema::password_reset_requests::valid_until.lt(chrono::Utc::now()))
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
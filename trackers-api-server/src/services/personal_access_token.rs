@@ -0,0 +1,293 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::prelude::*;
+
+/// Prefix which tells the personal access tokens apart from the JWTs.
+pub(crate) const TOKEN_PREFIX: &str = "trk_";
+
+const TOKEN_LENGTH: usize = 40;
+
+/// How far into the future the expiration date can be set.
+const MAX_LIFETIME_DAYS: i64 = 366;
+
+/// Lifetime of the access tokens issued in exchange for a personal access
+/// token. A new one is issued for every request.
+const EXCHANGED_TOKEN_LIFETIME_SECONDS: u64 = 60;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/user/:email/tokens",
+            routing::get_with(get_personal_access_tokens, |op| {
+                op.summary("Fetch the personal access tokens of a user")
+            })
+            .post_with(create_personal_access_token, |op| {
+                op.summary("Create a personal access token")
+                    .description("The token can be used as a bearer token in the `Authorization` header until it expires or gets deleted. It is shown only in the response to this request and cannot be retrieved later.")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .api_route_with(
+            "/user/:email/tokens/:token_id",
+            routing::delete_with(delete_personal_access_token, |op| {
+                op.summary("Delete a personal access token")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            crate::auth::scope::RequiredScope::new(
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+            ),
+            crate::auth::scope::require_scope,
+        ))
+        .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()))
+}
+
+async fn create_personal_access_token(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    caller: crate::auth::UserClaims,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    actor: services::audit::Actor,
+    json: JsonExtract<models::PersonalAccessTokenRequest>,
+) -> Result<CreatedResource<models::NewPersonalAccessToken>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let input = json.extract();
+
+    let Some(scope) = crate::auth::scope::parse_requested(input.scope.as_deref()) else {
+        return Err(BadRequestError::default()
+            .with_msg("the scope contains unknown scope names")
+            .with_docs()
+            .into());
+    };
+    if let Some(not_held) = crate::auth::scope::first_not_held(&scope, &caller.scope_list()) {
+        return Err(ForbiddenError::default()
+            .with_msg(format!(
+                "the authorization token does not grant the {not_held} scope so it cannot be given to the token"
+            ))
+            .with_docs()
+            .into());
+    }
+
+    let now = chrono::Utc::now();
+    if input.expires_at <= now || input.expires_at > now + chrono::Duration::days(MAX_LIFETIME_DAYS)
+    {
+        return Err(BadRequestError::default()
+            .with_msg(format!(
+                "the expiration date must be in the future and within {MAX_LIFETIME_DAYS} days"
+            ))
+            .with_docs()
+            .into());
+    }
+
+    // the token is created only for the user addressed in the path
+    let owner: models::types::Uuid = db_schema::users::table
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email)),
+        )
+        .select(db_schema::users::user_id)
+        .first(&mut db_conn)
+        .await?;
+
+    let token = format!(
+        "{TOKEN_PREFIX}{}",
+        crate::auth::opaque::generate(TOKEN_LENGTH)
+    );
+    let personal_token = models::db::PersonalAccessToken {
+        token_id: models::types::Uuid::new(),
+        user_id: owner,
+        name: input.name,
+        token_hash: crate::auth::opaque::hash(&token),
+        scope: scope.join(" "),
+        created_at: now,
+        expires_at: input.expires_at,
+        last_used_at: None,
+    };
+
+    let (new_token, email) = (&personal_token, &email);
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                diesel::insert_into(db_schema::personal_access_tokens::table)
+                    .values(new_token)
+                    .execute(tx)
+                    .await?;
+                services::audit::Event::new(
+                    &new_token.user_id,
+                    models::types::AuditAction::TokenIssued,
+                )
+                .resource(format!("/api/user/{email}/tokens/{}", new_token.token_id))
+                .details(format!(
+                    "personal access token \"{}\", scope: {}",
+                    new_token.name, new_token.scope
+                ))
+                .record(tx, &actor)
+                .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(CreatedResource {
+        location: format!("/api/user/{email}/tokens"),
+        resource: Resource::new(models::NewPersonalAccessToken {
+            token,
+            details: personal_token.into(),
+        }),
+    })
+}
+
+async fn get_personal_access_tokens(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+) -> Result<Resource<Vec<models::PersonalAccessToken>>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let tokens: Vec<models::db::PersonalAccessToken> = db_schema::personal_access_tokens::table
+        .inner_join(db_schema::users::table)
+        .select(db_schema::personal_access_tokens::all_columns)
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email))
+                .and(db_schema::personal_access_tokens::expires_at.gt(chrono::Utc::now())),
+        )
+        .order(db_schema::personal_access_tokens::created_at.desc())
+        .get_results(&mut db_conn)
+        .await?;
+
+    Ok(
+        Resource::new(tokens.into_iter().map(|token| token.into()).collect())
+            .with_links([("self", format!("/api/user/{email}/tokens"))]),
+    )
+}
+
+async fn delete_personal_access_token(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path((email, token_id)): axum::extract::Path<(
+        EmailAddress,
+        models::types::Uuid,
+    )>,
+    actor: services::audit::Actor,
+) -> Result<DeletedResource, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let (user_id, email, token_id) = (&user_id.0, &email, &token_id);
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let deleted = diesel::delete(db_schema::personal_access_tokens::table)
+                    .filter(
+                        db_schema::users::table
+                            .filter(db_schema::users::email.eq(email))
+                            .select(db_schema::users::user_id)
+                            .single_value()
+                            .eq(user_id),
+                    )
+                    .filter(db_schema::personal_access_tokens::user_id.eq(user_id))
+                    .filter(db_schema::personal_access_tokens::token_id.eq(token_id))
+                    .execute(tx)
+                    .await?;
+
+                if deleted == 0 {
+                    return Err(NotFoundError::default()
+                        .with_msg("no such personal access token")
+                        .with_docs()
+                        .into());
+                }
+                services::audit::Event::new(user_id, models::types::AuditAction::TokenRevoked)
+                    .resource(format!("/api/user/{email}/tokens/{token_id}"))
+                    .record(tx, &actor)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(DeletedResource {
+        links: std::collections::HashMap::from([("tokens", format!("/api/user/{email}/tokens"))]),
+    })
+}
+
+/// Finds the user and the scope of a personal access token which has not
+/// expired yet, marking it as used.
+pub(crate) async fn use_token(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    token: &str,
+) -> Result<Option<models::db::PersonalAccessToken>, ApiError> {
+    use diesel::OptionalExtension;
+
+    let now = chrono::Utc::now();
+    let personal_token = diesel::update(db_schema::personal_access_tokens::table)
+        .filter(db_schema::personal_access_tokens::token_hash.eq(crate::auth::opaque::hash(token)))
+        .filter(db_schema::personal_access_tokens::expires_at.gt(now))
+        .set(db_schema::personal_access_tokens::last_used_at.eq(now))
+        .get_result(db_conn)
+        .await
+        .optional()?;
+
+    Ok(personal_token)
+}
+
+/// Issues a short-lived access token carrying the scopes of the personal
+/// access token so that the requests can be authorized by the JWT layer.
+pub(crate) fn exchange(personal_token: &models::db::PersonalAccessToken) -> aliri::Jwt {
+    crate::auth::layer::new_token_with_exp_and_scopes(
+        EXCHANGED_TOKEN_LIFETIME_SECONDS,
+        &personal_token.user_id,
+        crate::auth::scope::granted(&personal_token.scope, &personal_token.user_id),
+        Some(&format!(
+            "personal access token \"{}\"",
+            personal_token.name
+        )),
+    )
+}
+
+/// Deletes a personal access token given in place of a JWT to the revocation
+/// endpoint, returning it if it has existed.
+pub(crate) async fn revoke(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    token: &str,
+) -> Result<Option<models::db::PersonalAccessToken>, ApiError> {
+    use diesel::OptionalExtension;
+
+    let personal_token = diesel::delete(db_schema::personal_access_tokens::table)
+        .filter(db_schema::personal_access_tokens::token_hash.eq(crate::auth::opaque::hash(token)))
+        .get_result(db_conn)
+        .await
+        .optional()?;
+    Ok(personal_token)
+}
+
+pub(crate) async fn purge_expired_tokens(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::personal_access_tokens::table)
+        .filter(db_schema::personal_access_tokens::expires_at.lt(chrono::Utc::now()))
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
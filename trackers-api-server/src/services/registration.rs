@@ -1,3 +1,4 @@
+use diesel::OptionalExtension;
 use diesel_async::scoped_futures::ScopedFutureExt;
 use models::RegistrationRequest;
 
@@ -16,12 +17,43 @@ pub fn router() -> ApiRouter<AppState> {
             "/registration-request/:email/code",
             routing::post_with(confirm_request, |op| {
                 op.summary("Confirm request with code")
-                    .description("The code should have been sent to the selected email address.")
+                    .description("The code should have been sent to the selected email address. After too many incorrect codes the request is cancelled and the registration must be started again.")
+            })
+            .put_with(resend_code, |op| {
+                op.summary("Send a new confirmation code")
+                    .description("Replaces the confirmation code with a new one and extends the validity of the request. The codes can be resent only a few times and not too often.")
             }),
             |op| op.tag("Registration"),
         )
 }
 
+/// How long a registration request can be confirmed after the code has been
+/// sent.
+pub(crate) const CODE_LIFETIME_MINUTES: i64 = 10;
+/// How many times the code can be sent for a single registration request.
+const MAX_CODE_MAILS_PER_REQUEST: i32 = 5;
+/// How long the client must wait before asking for the code to be sent again.
+const CODE_RESEND_INTERVAL_SECONDS: i64 = 60;
+/// How many confirmation code mails can be requested from a single address
+/// within an hour.
+pub(crate) const CODE_MAILS_PER_ADDRESS: usize = 10;
+/// How many incorrect codes are accepted before the request is cancelled.
+const MAX_FAILED_ATTEMPTS: i32 = 5;
+
+/// Checks if the client at the address is still allowed to cause a
+/// confirmation code to be mailed.
+pub(crate) fn limit_code_mails(
+    state: &AppState,
+    client_addr: std::net::IpAddr,
+) -> Result<(), ApiError> {
+    state.code_mail_limiter.hit(client_addr).map_err(|wait| {
+        TooManyRequestsError::default()
+            .with_msg("too many confirmation codes have been requested, try again later")
+            .with_retry_after(wait)
+            .into()
+    })
+}
+
 async fn get_request(
     State(state): State<AppState>,
     Path(email): Path<models::types::Email>,
@@ -43,6 +75,94 @@ async fn get_request(
     ]))
 }
 
+async fn resend_code(
+    State(state): State<AppState>,
+    crate::client_addr::ClientAddr(client_addr): crate::client_addr::ClientAddr,
+    Path(email): Path<models::types::Email>,
+) -> Result<ModifiedResource<RegistrationRequest>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    // Per-address limits
+    limit_code_mails(&state, client_addr)?;
+
+    let code = models::ConfirmationCode::new();
+    #[cfg(debug_assertions)]
+    println!("registration code {code} has been generated");
+
+    let now = chrono::Utc::now();
+    let valid_until = now
+        .checked_add_signed(chrono::Duration::minutes(CODE_LIFETIME_MINUTES))
+        .unwrap();
+    let stored_code: String = code.clone().into();
+    // Per-email limits, checked by the update itself so that concurrent
+    // requests cannot send more codes than allowed
+    let updated: Option<models::db::RegistrationRequest> =
+        diesel::update(db_schema::registration_requests::table.find(&email))
+            .filter(db_schema::registration_requests::sent_count.lt(MAX_CODE_MAILS_PER_REQUEST))
+            .filter(
+                db_schema::registration_requests::last_sent_at
+                    .le(now - chrono::Duration::seconds(CODE_RESEND_INTERVAL_SECONDS)),
+            )
+            .set((
+                db_schema::registration_requests::confirmation_code.eq(stored_code),
+                db_schema::registration_requests::valid_until.eq(valid_until),
+                db_schema::registration_requests::last_sent_at.eq(now),
+                db_schema::registration_requests::sent_count
+                    .eq(db_schema::registration_requests::sent_count + 1),
+            ))
+            .get_result(&mut db_conn)
+            .await
+            .optional()?;
+    let Some(req) = updated else {
+        // find out which of the limits has been hit
+        let req: Option<models::db::RegistrationRequest> = db_schema::registration_requests::table
+            .find(&email)
+            .get_result(&mut db_conn)
+            .await
+            .optional()?;
+        return Err(match req {
+            None => NotFoundError::default()
+                .with_msg("no registration has been started for the email address")
+                .with_links([("register", "/api/users".to_owned())])
+                .into(),
+            Some(req) if req.sent_count >= MAX_CODE_MAILS_PER_REQUEST => {
+                let wait = (req.valid_until - now).to_std().unwrap_or_default();
+                TooManyRequestsError::default()
+                    .with_msg("the code cannot be sent again, start the registration again once the request expires")
+                    .with_retry_after(wait)
+                    .with_links([("register", "/api/users".to_owned())])
+                    .into()
+            }
+            Some(req) => {
+                let next_allowed_at = req
+                    .last_sent_at
+                    .checked_add_signed(chrono::Duration::seconds(CODE_RESEND_INTERVAL_SECONDS))
+                    .unwrap();
+                TooManyRequestsError::default()
+                    .with_msg("the code has been sent recently, wait before asking for a new one")
+                    .with_retry_after((next_allowed_at - now).to_std().unwrap_or_default())
+                    .into()
+            }
+        });
+    };
+
+    state
+        .mailer
+        .send(crate::mail::templates::REGISTRATION_CODE.render(&email, &[("code", &code)]))
+        .await?;
+
+    Ok(ModifiedResource {
+        location: None,
+        resource: Resource::new(req.into()).with_links([
+            ("self", format!("/api/registration-request/{}", &email)),
+            (
+                "confirm",
+                format!("/api/registration-request/{}/code", &email),
+            ),
+        ]),
+    })
+}
+
 async fn confirm_request(
     State(state): State<AppState>,
     Path(email): Path<models::types::Email>,
@@ -51,122 +171,95 @@ async fn confirm_request(
     let mut db_conn = state.db.get().await?;
     let code = json.data;
 
-    // The api error to be returned will be saved outside the transaction.
-    // This trick is performed to avoid unnecessary and difficult to understand later trait
-    // impl shenanigans.
-    //
-    // ... still, this is a among first candidates for a refactor
-    let mut caught_api_err = None;
-    let api_err = &mut caught_api_err;
+    let req: models::db::RegistrationRequest = db_schema::registration_requests::table
+        .find(&email)
+        .get_result(&mut db_conn)
+        .await?;
+
+    // Request no longer valid
+    if req.valid_until < chrono::Utc::now() {
+        diesel::delete(db_schema::registration_requests::table.find(&email))
+            .execute(&mut db_conn)
+            .await?;
+        return Err(GoneError::default()
+            .with_docs()
+            .with_msg("the request is no longer valid")
+            .with_links([("register", "/api/users".to_owned())])
+            .into());
+    };
+
+    // Not the code we expected
+    if code != req.confirmation_code {
+        // The counter is incremented in the database so that concurrent
+        // guesses cannot slip past the limit
+        let failed_attempts: i32 =
+            diesel::update(db_schema::registration_requests::table.find(&email))
+                .set(
+                    db_schema::registration_requests::failed_attempts
+                        .eq(db_schema::registration_requests::failed_attempts + 1),
+                )
+                .returning(db_schema::registration_requests::failed_attempts)
+                .get_result(&mut db_conn)
+                .await?;
+        if failed_attempts >= MAX_FAILED_ATTEMPTS {
+            diesel::delete(db_schema::registration_requests::table.find(&email))
+                .execute(&mut db_conn)
+                .await?;
+            return Err(GoneError::default()
+                .with_docs()
+                .with_msg("too many incorrect codes, the request has been cancelled")
+                .with_links([("register", "/api/users".to_owned())])
+                .into());
+        }
+        return Err(BadRequestError::default()
+            .with_docs()
+            .with_msg("incorrect code")
+            .into());
+    };
+
+    let mut updated_req: models::RegistrationRequest = req.clone().into();
+    updated_req.confirmed_with_code = true;
 
-    let transaction_res = db_conn
-        .transaction(|tx| {
+    let new_user_id = models::types::Uuid::new();
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
             async move {
-                use db_schema::registration_requests::dsl::registration_requests;
-                use db_schema::trackers::dsl::trackers;
-                use db_schema::users::dsl::users;
-
-                let req: models::db::RegistrationRequest =
-                    match registration_requests.find(&email).get_result(tx).await {
-                        Ok(req) => req,
-                        Err(err) => {
-                            return Err(err);
-                        }
-                    };
-
-                // Request no longer valid
-                if req.valid_until < chrono::Utc::now() {
-                    // save the api err
-                    *api_err = Some(
-                        GoneError::default()
-                            .with_docs()
-                            .with_msg("the request is no longer valid")
-                            .into(),
-                    );
-                    // remove invalid request
-                    let clean_up_res = diesel::delete(registration_requests)
-                        .filter(db_schema::registration_requests::columns::email.eq(&req.email))
-                        .execute(tx)
-                        .await;
-                    // check and finish early if transaction successful
-                    match clean_up_res {
-                        Ok(_) => return Ok(None),
-                        Err(_) => {
-                            return Err(diesel::result::Error::RollbackTransaction);
-                        }
-                    }
-                };
-
-                // Not the code we expected
-                if code != req.confirmation_code {
-                    // save the api err
-                    *api_err = Some(
-                        BadRequestError::default()
-                            .with_docs()
-                            .with_msg("incorrect code")
-                            .into(),
-                    );
-                    return Err(diesel::result::Error::RollbackTransaction);
-                };
-
-                let mut updated_req: models::RegistrationRequest = req.clone().into();
-                updated_req.confirmed_with_code = true;
-
-                let new_user_id = models::types::Uuid::new();
-
-                match diesel::insert_into(users)
+                diesel::insert_into(db_schema::users::table)
                     .values(models::db::User {
                         user_id: new_user_id.clone(),
-                        email: req.email,
+                        email: req.email.clone(),
                         password: req.password,
                     })
                     .execute(tx)
-                    .await
-                {
-                    Err(err) => {
-                        return Err(err);
-                    }
-                    _ => {}
-                };
-
-                match diesel::insert_into(trackers)
+                    .await?;
+                diesel::insert_into(db_schema::trackers::table)
                     .values(models::core::Tracker {
                         user_id: new_user_id,
                         ..Default::default()
                     })
                     .execute(tx)
-                    .await
-                {
-                    Err(err) => return Err(err),
-                    _ => {}
-                };
-
-                match diesel::delete(registration_requests)
-                    .filter(db_schema::registration_requests::columns::email.eq(&email))
+                    .await?;
+                diesel::delete(db_schema::registration_requests::table.find(&req.email))
                     .execute(tx)
-                    .await
-                {
-                    Err(err) => Err(err),
-                    _ => Ok(Some(updated_req)),
-                }
+                    .await?;
+                Ok(())
             }
             .scope_boxed()
         })
-        .await;
-
-    // Return early with API error if we had any
-    if let Some(caught_api_err) = caught_api_err {
-        return Err(caught_api_err);
-    };
-
-    // Extract the registration request or handle the transaction errors
-    let registration_res = match transaction_res {
-        Ok(reg) => reg.unwrap(),
-        Err(transaction_err) => Err(transaction_err)?,
-    };
+        .await?;
 
     Ok(ModifiedResource {
         location: None,
-        resource: Resource::new(registration_res),
+        resource: Resource::new(updated_req),
     })
 }
+
+/// Removes the registration requests which have not been confirmed in time.
+pub(crate) async fn purge_expired_requests(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::registration_requests::table)
+        .filter(db_schema::registration_requests::valid_until.lt(chrono::Utc::now()))
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use crate::{auth::scope::ScopeVariable, prelude::*};
+use diesel::OptionalExtension;
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::{auth::refresh_token::RefreshToken, prelude::*};
 
 pub fn router() -> ApiRouter<AppState> {
     ApiRouter::new()
@@ -13,18 +16,82 @@ pub fn router() -> ApiRouter<AppState> {
             routing::post_with(authenticate, |op| op.summary("Start new session")),
             |op| op.tag("Log-in"),
         )
+        .api_route_with(
+            "/session/revoke",
+            routing::post_with(revoke_token, |op| {
+                op.summary("Revoke a token")
+                    .description("Works as described in RFC 7009. Revoking either of the tokens of a session ends the session. The response is the same whether the token was valid or not.")
+            }),
+            |op| op.tag("Log-in"),
+        )
+        .api_route_with(
+            "/user/:email/sessions",
+            routing::get_with(get_user_sessions, |op| {
+                op.summary("Fetch the active sessions of a user")
+            })
+            .delete_with(end_other_sessions, |op| {
+                op.summary("End all the sessions but the current one")
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::ACCOUNT_ADMIN,
+                    crate::auth::scope::permission::ACCOUNT_ADMIN,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Account Management"),
+        )
+        .api_route_with(
+            "/user/:email/sessions/:session_id",
+            routing::delete_with(end_user_session, |op| {
+                op.summary("End a session")
+                    .description("The tokens of the session are revoked immediately.")
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::ACCOUNT_ADMIN,
+                    crate::auth::scope::permission::ACCOUNT_ADMIN,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Account Management"),
+        )
+        .api_route_with(
+            "/session/introspect",
+            routing::post_with(introspect_token, |op| {
+                op.summary("Check if a token is active")
+                    .description("Works as described in RFC 7662. The request must be authorized with a valid access token and only the tokens issued to the same user are reported as active.")
+            })
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Log-in"),
+        )
 }
 
-/// OAuth2 authentication request. The server supports only resource owner flow
-/// at the moment and serves the role of both authorization server and its
-/// client.
+/// OAuth2 authentication request. The server serves the role of both the
+/// authorization server and its client for the resource owner flow. The
+/// third-party applications can use the authorization code flow instead.
 #[derive(Deserialize, JsonSchema)]
 #[serde(tag = "grant_type")]
 #[serde(rename_all = "snake_case")]
 pub enum AuthReq {
     Password(PasswordGrant),
+    /// Also accepted as `refresh_token`, the name given by RFC 6749
+    #[serde(alias = "refresh_token")]
     Refresh(RefreshRequest),
     ClientCredentials(ClientCredentialsGrant),
+    AuthorizationCode(AuthorizationCodeGrant),
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
+    DeviceCode(DeviceCodeGrant),
+    MfaOtp(MfaOtpGrant),
+    MagicLink(MagicLinkGrant),
 }
 
 /// OAuth2 authentication request for a resource owner authentication flow.
@@ -49,6 +116,53 @@ pub struct ClientCredentialsGrant {
     /// ID assigned to client by the tracke.rs server
     pub client_id: models::types::ClientSecretStr,
     pub client_secret: models::types::ClientSecretStr,
+    /// Space separated list of the requested scopes, all the scopes the
+    /// client can be given if not specified
+    pub scope: Option<String>,
+}
+
+/// OAuth2 authorization code exchange request. The code is obtained by
+/// redirecting the user to `/oauth/authorize` and can be used once.
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename = "authorization_code")]
+pub struct AuthorizationCodeGrant {
+    pub code: String,
+    /// Must be the same as the one used to obtain the code
+    pub redirect_uri: String,
+    pub client_id: models::types::ClientSecretStr,
+    /// The secret of the client, as for the `client_credentials` grant
+    pub client_secret: models::types::ClientSecretStr,
+    /// PKCE code verifier from which the `code_challenge` has been derived
+    pub code_verifier: String,
+}
+
+/// OAuth2 device access token request as described in RFC 8628. The device
+/// code is obtained from `/api/session/device`.
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
+pub struct DeviceCodeGrant {
+    pub device_code: String,
+    /// Required if it was given when the device authorization was started
+    pub client_id: Option<models::types::ClientSecretStr>,
+}
+
+/// Second step of the password grant for the accounts with two-factor
+/// authentication turned on.
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename = "mfa_otp")]
+pub struct MfaOtpGrant {
+    /// Returned with the `mfa_required` challenge
+    pub mfa_token: String,
+    /// A code from the authenticator app or one of the recovery codes
+    pub otp: String,
+}
+
+/// Passwordless login with the code sent by mail after a request to
+/// `/api/session/magic-link`.
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename = "magic_link")]
+pub struct MagicLinkGrant {
+    pub code: String,
 }
 
 /// OAuth2 access token which also works as session ID.
@@ -60,26 +174,257 @@ pub struct AccessToken {
     pub expires_in: u16,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub refresh_token: Option<String>,
+    /// Space separated list of the scopes granted to the token
+    pub scope: String,
+    /// OpenID Connect ID token, issued when the `openid` scope is granted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+/// Either the tokens or, for the accounts with two-factor authentication
+/// turned on, a challenge which has to be answered with the `mfa_otp` grant.
+/// The challenge is sent with the 403 status code.
+#[derive(Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum TokenResponse {
+    Token(AccessToken),
+    MfaRequired(services::mfa::MfaRequired),
+}
+
+impl IntoResponse for TokenResponse {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            TokenResponse::Token(token) => (StatusCode::OK, Json(token)).into_response(),
+            TokenResponse::MfaRequired(challenge) => {
+                (StatusCode::FORBIDDEN, Json(challenge)).into_response()
+            }
+        }
+    }
+}
+
+impl AideOperationOutput for TokenResponse {
+    type Inner = Self;
+
+    fn inferred_responses(
+        ctx: &mut aide::gen::GenContext,
+        _operation: &mut openapi::Operation,
+    ) -> Vec<(Option<u16>, openapi::Response)> {
+        let response = |description: &str, json_schema| openapi::Response {
+            description: description.to_owned(),
+            content: indexmap::indexmap! {
+                "application/json".to_owned() => openapi::MediaType {
+                    schema: Some(openapi::SchemaObject {
+                        json_schema,
+                        external_docs: None,
+                        example: None,
+                    }),
+                    ..openapi::MediaType::default()
+                }
+            },
+            ..openapi::Response::default()
+        };
+        vec![
+            (
+                Some(200),
+                response(
+                    "The session has been started.",
+                    ctx.schema.subschema_for::<AccessToken>(),
+                ),
+            ),
+            (
+                Some(403),
+                response(
+                    "A code from the authenticator app is required to finish logging in.",
+                    ctx.schema.subschema_for::<services::mfa::MfaRequired>(),
+                ),
+            ),
+        ]
+    }
+}
+
+/// Token revocation request as described in RFC 7009.
+#[derive(Deserialize, JsonSchema)]
+pub struct TokenRevocationRequest {
+    pub token: String,
+    /// Either `access_token` or `refresh_token`. The server finds out the type
+    /// of the token on its own so the hint is ignored.
+    pub token_type_hint: Option<String>,
+}
+
+/// Token introspection request as described in RFC 7662.
+#[derive(Deserialize, JsonSchema)]
+pub struct TokenIntrospectionRequest {
+    pub token: String,
+    /// Either `access_token` or `refresh_token`. The server finds out the type
+    /// of the token on its own so the hint is ignored.
+    pub token_type_hint: Option<String>,
+}
+
+/// Information about a token as described in RFC 7662. Only the `active`
+/// field is present for the tokens which are not active.
+#[derive(Serialize, JsonSchema, Default)]
+pub struct TokenIntrospection {
+    pub active: bool,
+    /// Space separated list of scopes held by the token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<&'static str>,
+    /// Expiration time in seconds since the Unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    /// ID of the user the token has been issued for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+}
+
+/// How long an access token can be used.
+const ACCESS_TOKEN_LIFETIME_SECONDS: u64 = 30 * 60;
+
+/// The refresh token lifetime counted from now, cut short at the end of the
+/// maximum lifetime of the session started at `started_at`.
+fn refresh_token_valid_until(
+    started_at: chrono::DateTime<chrono::Utc>,
+) -> chrono::DateTime<chrono::Utc> {
+    let valid_until = chrono::Utc::now()
+        .checked_add_signed(chrono::Duration::days(
+            crate::auth::refresh_token::REFRESH_TOKEN_LIFETIME_DAYS,
+        ))
+        .unwrap();
+    let session_end = started_at
+        .checked_add_signed(chrono::Duration::days(
+            crate::auth::refresh_token::SESSION_MAX_LIFETIME_DAYS,
+        ))
+        .unwrap();
+    valid_until.min(session_end)
+}
+
+/// Starts a session which can be continued with refresh tokens. The `scope` is
+/// the space separated list of the scopes granted to the session.
+async fn start_refreshable_session(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    actor: &services::audit::Actor,
+    user_id: models::types::Uuid,
+    scope: String,
+    client_name: Option<String>,
+    user_agent: Option<String>,
+) -> Result<Json<AccessToken>, ApiError> {
+    let access_token = crate::auth::layer::new_token_with_exp_and_scopes(
+        ACCESS_TOKEN_LIFETIME_SECONDS,
+        &user_id,
+        crate::auth::scope::granted(&scope, &user_id),
+        client_name.as_deref(),
+    );
+    let refresh_token = RefreshToken::new();
+    let event = services::audit::Event::new(&user_id, models::types::AuditAction::TokenIssued)
+        .details(format!("scope: {scope}"));
+    let actor = actor.with_client(client_name.clone());
+    let started_at = chrono::Utc::now();
+    let session = models::db::Session {
+        user_id,
+        access_token: access_token.clone().take(),
+        started_at,
+        valid_until: refresh_token_valid_until(started_at),
+        session_id: models::types::Uuid::new(),
+        client_name,
+        user_agent,
+        refresh_token_hash: Some(refresh_token.hash()),
+        scope: scope.clone(),
+    };
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                diesel::insert_into(db_schema::sessions::table)
+                    .values(session)
+                    .execute(tx)
+                    .await?;
+                event.record(tx, &actor).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    Ok(Json(AccessToken {
+        token_type: "bearer",
+        access_token: access_token.take(),
+        refresh_token: Some(refresh_token.take()),
+        expires_in: ACCESS_TOKEN_LIFETIME_SECONDS as u16,
+        scope,
+        id_token: None,
+    }))
+}
+
+/// Adds the ID token to the tokens of a session which has been granted the
+/// `openid` scope. The `client_id` of the client which started the session is
+/// the audience of the ID token.
+async fn with_id_token(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    Json(mut token): Json<AccessToken>,
+    user_id: &models::types::Uuid,
+    client_id: Option<&str>,
+    nonce: Option<&str>,
+) -> Result<Json<AccessToken>, ApiError> {
+    let openid = token
+        .scope
+        .split_whitespace()
+        .any(|name| name == crate::auth::scope::OPENID);
+    if openid {
+        let email: EmailAddress = db_schema::users::table
+            .find(user_id)
+            .select(db_schema::users::email)
+            .first(db_conn)
+            .await?;
+        token.id_token =
+            Some(crate::auth::id_token::issue(user_id, &email, client_id, nonce).take());
+    }
+    Ok(Json(token))
 }
 
 pub async fn authenticate(
     State(state): State<AppState>,
+    crate::client_addr::ClientAddr(client_addr): crate::client_addr::ClientAddr,
+    actor: services::audit::Actor,
+    user_agent: Option<axum::TypedHeader<axum::headers::UserAgent>>,
     axum::extract::Form(form): axum::extract::Form<AuthReq>,
-) -> Result<Json<AccessToken>, ApiError> {
+) -> Result<TokenResponse, ApiError> {
+    let user_agent = user_agent.map(|axum::TypedHeader(user_agent)| user_agent.to_string());
     let mut db_conn = state.db.get().await?;
     use db_schema::sessions::dsl::sessions;
     use db_schema::users::dsl::users;
 
-    match form {
+    let tokens = match form {
         AuthReq::Password(form) => {
+            services::login_protection::check_allowed(&state, &form.username, client_addr)?;
             let user_search_res: Result<models::db::User, _> = users
-                .filter(db_schema::users::email.eq(form.username))
+                .filter(db_schema::users::email.eq(&form.username))
                 .first(&mut db_conn)
                 .await;
             let user: models::db::User = match user_search_res {
                 Ok(user) => user,
                 Err(err) => match err {
                     diesel::result::Error::NotFound => {
+                        services::login_protection::record_failure(
+                            &state,
+                            &mut db_conn,
+                            &form.username,
+                            None,
+                            client_addr,
+                            user_agent.as_deref(),
+                        )
+                        .await?;
                         Err(BadRequestError::default().with_msg("email or password not correct"))?
                     }
                     _ => Err(err)?,
@@ -87,131 +432,797 @@ pub async fn authenticate(
             };
             // check password:
             if !form.password.match_with(user.password) {
+                services::login_protection::record_failure(
+                    &state,
+                    &mut db_conn,
+                    &form.username,
+                    Some(user.user_id.clone()),
+                    client_addr,
+                    user_agent.as_deref(),
+                )
+                .await?;
                 Err(BadRequestError::default().with_msg("email or password not correct"))?;
             }
+            // the failures are forgotten only once the second factor is given
+            let challenge = services::mfa::start_challenge(&mut db_conn, &user.user_id).await;
+            if challenge.as_ref().map_or(true, Option::is_some) {
+                services::login_protection::release_attempt(
+                    &state,
+                    &form.username,
+                    client_addr,
+                );
+            }
+            if let Some(challenge) = challenge? {
+                return Ok(TokenResponse::MfaRequired(challenge));
+            }
+            services::login_protection::record_success(&state, &form.username, client_addr);
+            services::audit::Event::new(&user.user_id, models::types::AuditAction::LoginSucceeded)
+                .details("with the password")
+                .record(&mut db_conn, &actor)
+                .await?;
+            let scope = crate::auth::scope::FULL_ACCESS.to_owned();
+            start_refreshable_session(&mut db_conn, &actor, user.user_id, scope, None, user_agent)
+                .await
+        }
+        AuthReq::Refresh(refresh) => {
+            let presented_hash = crate::auth::refresh_token::hash(&refresh.refresh_token);
+            let session_search_res: Result<models::db::Session, _> = sessions
+                .filter(db_schema::sessions::refresh_token_hash.eq(&presented_hash))
+                .first(&mut db_conn)
+                .await;
+            let session = match session_search_res {
+                Ok(session) => session,
+                Err(diesel::result::Error::NotFound) => {
+                    // A token which has already been used is held by both the
+                    // client and someone else and there is no telling which
+                    // one is presenting it. The whole session has to end.
+                    let reused_in: Result<models::db::Session, _> = sessions
+                        .filter(
+                            db_schema::sessions::session_id.eq_any(
+                                db_schema::used_refresh_tokens::table
+                                    .filter(
+                                        db_schema::used_refresh_tokens::token_hash
+                                            .eq(&presented_hash),
+                                    )
+                                    .select(db_schema::used_refresh_tokens::session_id),
+                            ),
+                        )
+                        .first(&mut db_conn)
+                        .await;
+                    if let Ok(session) = reused_in {
+                        end_reused_session(&state, &mut db_conn, &actor, session).await?;
+                    }
+                    return Err(BadRequestError::default()
+                        .with_msg("the refresh token is not valid")
+                        .with_links([("new session", "/api/session/token".to_owned())])
+                        .into());
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            if session.valid_until <= chrono::Utc::now() {
+                // cannot refresh anymore
+                return Err(BadRequestError::default()
+                    .with_msg("the session can no longer be refreshed")
+                    .with_links([("new session", "/api/session/token".to_owned())])
+                    .into());
+            }
+
             // generate tokens:
             let access_token = crate::auth::layer::new_token_with_exp_and_scopes(
-                30 * 60,
-                aliri_oauth2::Scope::default().and(
-                    aliri_oauth2::oauth2::ScopeToken::from_string(format!(
-                        "{}:{}",
-                        crate::auth::scope::UserIdScope::scope_name(),
-                        crate::auth::scope::UserIdScope(user.user_id.clone())
-                    ))
-                    .unwrap(),
-                ),
+                ACCESS_TOKEN_LIFETIME_SECONDS,
+                &session.user_id,
+                crate::auth::scope::granted(&session.scope, &session.user_id),
+                session.client_name.as_deref(),
             );
-            let refresh_token = crate::auth::layer::new_token_with_exp_and_scopes(
-                30 * 60,
-                aliri_oauth2::Scope::empty(),
-            );
-            diesel::insert_into(sessions)
-                .values(models::core::Session {
-                    user_id: user.user_id,
-                    access_token: access_token.clone().take(),
-                    refresh_token: Some(refresh_token.clone().take()),
-                    started_at: chrono::Utc::now(),
-                    valid_until: chrono::Utc::now()
-                        .checked_add_signed(chrono::Duration::seconds(30 * 60))
-                        .unwrap(),
+            let refresh_token = RefreshToken::new();
+
+            let new_access_token = access_token.clone().take();
+            let new_refresh_token_hash = refresh_token.hash();
+            let event = services::audit::Event::new(
+                &session.user_id,
+                models::types::AuditAction::TokenIssued,
+            )
+            .details(format!("scope: {}, refreshed", session.scope));
+            let session_actor = actor.with_client(session.client_name.clone());
+            let session_id = session.session_id.clone();
+            let rotated = db_conn
+                .transaction::<_, ApiError, _>(|tx| {
+                    async move {
+                        // Two concurrent uses of the same token collide here,
+                        // the one which comes second is a reuse
+                        let inserted = diesel::insert_into(db_schema::used_refresh_tokens::table)
+                            .values((
+                                db_schema::used_refresh_tokens::token_hash.eq(&presented_hash),
+                                db_schema::used_refresh_tokens::session_id
+                                    .eq(&session.session_id),
+                            ))
+                            .on_conflict_do_nothing()
+                            .execute(tx)
+                            .await?;
+                        if inserted == 0 {
+                            return Ok(None);
+                        }
+                        diesel::update(db_schema::sessions::table)
+                            .filter(db_schema::sessions::session_id.eq(&session.session_id))
+                            .set((
+                                db_schema::sessions::access_token.eq(new_access_token),
+                                db_schema::sessions::refresh_token_hash
+                                    .eq(new_refresh_token_hash),
+                                db_schema::sessions::valid_until
+                                    .eq(refresh_token_valid_until(session.started_at)),
+                            ))
+                            .execute(tx)
+                            .await?;
+                        // The access token issued along with the used refresh
+                        // token is replaced as well
+                        let revoked = match crate::auth::layer::verify_token(&session.access_token)
+                        {
+                            Some(claims) => Some(
+                                crate::auth::revocation::RevokedTokens::revoke(
+                                    tx,
+                                    claims.jti(),
+                                    claims.expires_at(),
+                                )
+                                .await?,
+                            ),
+                            None => None,
+                        };
+                        event.record(tx, &session_actor).await?;
+                        Ok(Some(revoked))
+                    }
+                    .scope_boxed()
                 })
-                .execute(&mut db_conn)
                 .await?;
+            let Some(revoked) = rotated else {
+                // The session has been refreshed in the meantime, it is
+                // ended as it is now
+                let current: Option<models::db::Session> = sessions
+                    .filter(db_schema::sessions::session_id.eq(&session_id))
+                    .first(&mut db_conn)
+                    .await
+                    .optional()?;
+                if let Some(current) = current {
+                    end_reused_session(&state, &mut db_conn, &actor, current).await?;
+                }
+                return Err(BadRequestError::default()
+                    .with_msg("the refresh token is not valid")
+                    .with_links([("new session", "/api/session/token".to_owned())])
+                    .into());
+            };
+            state.revoked_tokens.remember(revoked);
+
             Ok(Json(AccessToken {
                 token_type: "bearer",
                 access_token: access_token.take(),
                 refresh_token: Some(refresh_token.take()),
-                expires_in: 30 * 60,
+                expires_in: ACCESS_TOKEN_LIFETIME_SECONDS as u16,
+                scope: session.scope,
+                id_token: None,
             }))
         }
-        AuthReq::Refresh(refresh) => {
-            let session: models::core::Session = sessions
-                .filter(db_schema::sessions::refresh_token.eq(&refresh.refresh_token))
-                .first(&mut db_conn)
-                .await?;
-
-            if session.valid_until.gt(&chrono::Utc::now()) {
-                // can still refresh
-
-                // generate tokens:
-                let access_token = crate::auth::layer::new_token_with_exp_and_scopes(
-                    30 * 60,
-                    aliri_oauth2::Scope::empty(),
-                );
-                let refresh_token = crate::auth::layer::new_token_with_exp_and_scopes(
-                    30 * 60,
-                    aliri_oauth2::Scope::empty(),
-                );
-
-                diesel::update(sessions)
-                    .filter(db_schema::sessions::refresh_token.eq(&refresh.refresh_token))
-                    .set((
-                        db_schema::sessions::refresh_token.eq(refresh_token.clone().take()),
-                        db_schema::sessions::access_token.eq(access_token.clone().take()),
-                    ))
-                    .execute(&mut db_conn)
-                    .await?;
-
-                Ok(Json(AccessToken {
-                    token_type: "bearer",
-                    access_token: access_token.take(),
-                    refresh_token: Some(refresh_token.take()),
-                    expires_in: 30 * 60,
-                }))
-            } else {
-                // cannot refresh anymore
-                Err(BadRequestError::default()
-                    .with_msg("the session can no longer be refreshed")
-                    .into())
-            }
-        }
         AuthReq::ClientCredentials(credentials) => {
-            let Ok(client): Result<models::core::AuthorisedClientFull,_> = db_schema::authorised_clients::table
-                .filter(
-                    db_schema::authorised_clients::client_id
-                        .eq(credentials.client_id)
-                        .and(
-                            db_schema::authorised_clients::client_secret
-                                .eq(credentials.client_secret),
-                        ),
-                )
+            let client: Option<models::db::AuthorisedClient> = db_schema::authorised_clients::table
+                .filter(db_schema::authorised_clients::client_id.eq(credentials.client_id))
                 .first(&mut db_conn)
-                .await else {
-                    return Err(BadRequestError::default().with_docs().with_msg("client_id or client_secret is invalid").into());
-                };
+                .await
+                .optional()?;
+            let Some(client) =
+                client.filter(|client| client.secret_matches(&credentials.client_secret))
+            else {
+                return Err(BadRequestError::default()
+                    .with_docs()
+                    .with_msg("client_id or client_secret is invalid")
+                    .into());
+            };
+
+            // the client may ask for less than it can be given
+            let scope = match crate::auth::scope::parse_requested(
+                credentials.scope.as_deref().or(Some(client.scope.as_str())),
+            ) {
+                Some(scope)
+                    if scope
+                        .iter()
+                        .all(|name| crate::auth::scope::is_covered(name, &client.scope)) =>
+                {
+                    scope.join(" ")
+                }
+                _ => {
+                    return Err(BadRequestError::default()
+                        .with_docs()
+                        .with_msg("the requested scope exceeds the scope of the client")
+                        .into())
+                }
+            };
 
             // generate tokens:
             let access_token = crate::auth::layer::new_token_with_exp_and_scopes(
-                30 * 60,
-                aliri_oauth2::Scope::default().and(
-                    aliri_oauth2::oauth2::ScopeToken::from_string(format!(
-                        "{}:{}",
-                        crate::auth::scope::UserIdScope::scope_name(),
-                        crate::auth::scope::UserIdScope(client.user_id.clone())
+                ACCESS_TOKEN_LIFETIME_SECONDS,
+                &client.user_id,
+                crate::auth::scope::granted(&scope, &client.user_id),
+                Some(&client.name),
+            );
+            let event = services::audit::Event::new(
+                &client.user_id,
+                models::types::AuditAction::TokenIssued,
+            )
+            .details(format!("scope: {scope}"));
+            let actor = actor.with_client(Some(client.name.clone()));
+            let session = models::db::Session {
+                user_id: client.user_id,
+                access_token: access_token.clone().take(),
+                started_at: chrono::Utc::now(),
+                valid_until: chrono::Utc::now()
+                    .checked_add_signed(chrono::Duration::seconds(
+                        ACCESS_TOKEN_LIFETIME_SECONDS as i64,
                     ))
                     .unwrap(),
-                ),
-            );
-
-            diesel::insert_into(sessions)
-                .values(models::core::Session {
-                    user_id: client.user_id,
-                    access_token: access_token.clone().take(),
-                    refresh_token: None,
-                    started_at: chrono::Utc::now(),
-                    valid_until: chrono::Utc::now()
-                        .checked_add_signed(chrono::Duration::seconds(30 * 60))
-                        .unwrap(),
+                session_id: models::types::Uuid::new(),
+                client_name: Some(client.name),
+                user_agent,
+                refresh_token_hash: None,
+                scope: scope.clone(),
+            };
+            db_conn
+                .transaction::<_, ApiError, _>(|tx| {
+                    async move {
+                        diesel::insert_into(sessions)
+                            .values(session)
+                            .execute(tx)
+                            .await?;
+                        event.record(tx, &actor).await
+                    }
+                    .scope_boxed()
                 })
-                .execute(&mut db_conn)
                 .await?;
 
             Ok(Json(AccessToken {
                 token_type: "bearer",
                 access_token: access_token.take(),
                 refresh_token: None,
-                expires_in: 30 * 60,
+                expires_in: ACCESS_TOKEN_LIFETIME_SECONDS as u16,
+                scope,
+                id_token: None,
             }))
         }
+        AuthReq::AuthorizationCode(grant) => {
+            let invalid_grant = || -> ApiError {
+                BadRequestError::default()
+                    .with_msg("the authorization code is not valid")
+                    .with_docs()
+                    .into()
+            };
+
+            // The code can be used only once, even if the exchange fails
+            let code_search_res: Result<models::db::AuthorizationCode, _> =
+                diesel::delete(db_schema::authorization_codes::table)
+                    .filter(
+                        db_schema::authorization_codes::code_hash
+                            .eq(crate::auth::opaque::hash(&grant.code)),
+                    )
+                    .get_result(&mut db_conn)
+                    .await;
+            let code = match code_search_res {
+                Ok(code) => code,
+                Err(diesel::result::Error::NotFound) => return Err(invalid_grant()),
+                Err(err) => return Err(err.into()),
+            };
+
+            if code.expires_at <= chrono::Utc::now()
+                || code.client_id.to_string() != grant.client_id.to_string()
+                || code.redirect_uri != grant.redirect_uri
+                || !services::oauth::verify_code_challenge(
+                    &grant.code_verifier,
+                    &code.code_challenge,
+                )
+            {
+                return Err(invalid_grant());
+            }
+
+            // the code alone is not enough, the client has to authenticate
+            let client: Option<models::db::AuthorisedClient> = db_schema::authorised_clients::table
+                .filter(db_schema::authorised_clients::client_id.eq(&code.client_id))
+                .first(&mut db_conn)
+                .await
+                .optional()?;
+            let Some(client) = client.filter(|client| client.secret_matches(&grant.client_secret))
+            else {
+                return Err(BadRequestError::default()
+                    .with_docs()
+                    .with_msg("client_id or client_secret is invalid")
+                    .into());
+            };
+
+            let tokens = start_refreshable_session(
+                &mut db_conn,
+                &actor,
+                code.user_id.clone(),
+                code.scope,
+                Some(client.name),
+                user_agent,
+            )
+            .await?;
+            with_id_token(
+                &mut db_conn,
+                tokens,
+                &code.user_id,
+                Some(&code.client_id.to_string()),
+                code.nonce.as_deref(),
+            )
+            .await
+        }
+        AuthReq::DeviceCode(grant) => {
+            let (user_id, authorization) =
+                services::device::poll(&mut db_conn, &grant.device_code, grant.client_id.as_ref())
+                    .await?;
+
+            let client_name: Option<String> = match &authorization.client_id {
+                Some(client_id) => db_schema::authorised_clients::table
+                    .filter(db_schema::authorised_clients::client_id.eq(client_id))
+                    .select(db_schema::authorised_clients::name)
+                    .first(&mut db_conn)
+                    .await
+                    .optional()?,
+                None => None,
+            };
+
+            let tokens = start_refreshable_session(
+                &mut db_conn,
+                &actor,
+                user_id.clone(),
+                authorization.scope,
+                client_name,
+                user_agent,
+            )
+            .await?;
+            let client_id = authorization
+                .client_id
+                .map(|client_id| client_id.to_string());
+            with_id_token(&mut db_conn, tokens, &user_id, client_id.as_deref(), None).await
+        }
+        AuthReq::MfaOtp(grant) => {
+            let user_id = services::mfa::challenged_user(&mut db_conn, &grant.mfa_token).await?;
+            let email: EmailAddress = users
+                .find(&user_id)
+                .select(db_schema::users::email)
+                .first(&mut db_conn)
+                .await?;
+            // the second factor is guarded against guessing like the password
+            services::login_protection::check_allowed(&state, &email, client_addr)?;
+            let answered =
+                services::mfa::answer_challenge(&mut db_conn, &grant.mfa_token, &grant.otp).await;
+            match answered {
+                Ok(true) => {
+                    services::login_protection::record_success(&state, &email, client_addr)
+                }
+                Ok(false) => {
+                    services::login_protection::record_failure(
+                        &state,
+                        &mut db_conn,
+                        &email,
+                        Some(user_id),
+                        client_addr,
+                        user_agent.as_deref(),
+                    )
+                    .await?;
+                    return Err(BadRequestError::default()
+                        .with_msg("the authentication code is not correct")
+                        .with_docs()
+                        .into());
+                }
+                Err(err) => {
+                    services::login_protection::release_attempt(&state, &email, client_addr);
+                    return Err(err);
+                }
+            }
+            services::audit::Event::new(&user_id, models::types::AuditAction::LoginSucceeded)
+                .details("with the password and the second factor")
+                .record(&mut db_conn, &actor)
+                .await?;
+            let scope = crate::auth::scope::FULL_ACCESS.to_owned();
+            start_refreshable_session(&mut db_conn, &actor, user_id, scope, None, user_agent).await
+        }
+        AuthReq::MagicLink(grant) => {
+            let Some(user_id) = services::magic_link::use_code(&mut db_conn, &grant.code).await?
+            else {
+                return Err(BadRequestError::default()
+                    .with_msg("the code is not valid or has expired")
+                    .with_links([("new code", "/api/session/magic-link".to_owned())])
+                    .into());
+            };
+            // the code replaces only the password, not the second factor
+            if let Some(challenge) = services::mfa::start_challenge(&mut db_conn, &user_id).await? {
+                return Ok(TokenResponse::MfaRequired(challenge));
+            }
+            services::audit::Event::new(&user_id, models::types::AuditAction::LoginSucceeded)
+                .details("with a code sent by mail")
+                .record(&mut db_conn, &actor)
+                .await?;
+            let scope = crate::auth::scope::FULL_ACCESS.to_owned();
+            start_refreshable_session(&mut db_conn, &actor, user_id, scope, None, user_agent).await
+        }
+    };
+
+    tokens.map(|Json(token)| TokenResponse::Token(token))
+}
+
+/// Removes the sessions which can no longer be used or refreshed.
+pub(crate) async fn purge_expired_sessions(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    diesel::delete(db_schema::sessions::table)
+        .filter(db_schema::sessions::valid_until.lt(chrono::Utc::now()))
+        .execute(&mut db_conn)
+        .await?;
+    Ok(())
+}
+
+pub async fn revoke_token(
+    State(state): State<AppState>,
+    actor: services::audit::Actor,
+    axum::extract::Form(form): axum::extract::Form<TokenRevocationRequest>,
+) -> Result<(), ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    if form
+        .token
+        .starts_with(services::personal_access_token::TOKEN_PREFIX)
+    {
+        return db_conn
+            .transaction::<_, ApiError, _>(|tx| {
+                async move {
+                    if let Some(personal_token) =
+                        services::personal_access_token::revoke(tx, &form.token).await?
+                    {
+                        services::audit::Event::new(
+                            &personal_token.user_id,
+                            models::types::AuditAction::TokenRevoked,
+                        )
+                        .details(format!("personal access token \"{}\"", personal_token.name))
+                        .record(tx, &actor)
+                        .await?;
+                    }
+                    Ok(())
+                }
+                .scope_boxed()
+            })
+            .await;
+    }
+
+    let revoked = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let mut revoked = Vec::new();
+                if let Some(claims) = crate::auth::layer::verify_token(&form.token) {
+                    revoked.push(
+                        crate::auth::revocation::RevokedTokens::revoke(
+                            tx,
+                            claims.jti(),
+                            claims.expires_at(),
+                        )
+                        .await?,
+                    );
+                }
+
+                let session_search_res: Result<models::db::Session, _> = db_schema::sessions::table
+                    .filter(
+                        db_schema::sessions::access_token
+                            .eq(&form.token)
+                            .or(db_schema::sessions::refresh_token_hash
+                                .eq(crate::auth::refresh_token::hash(&form.token))),
+                    )
+                    .for_update()
+                    .first(tx)
+                    .await;
+                let session = match session_search_res {
+                    Ok(session) => session,
+                    // Invalid tokens do not cause an error response
+                    Err(diesel::result::Error::NotFound) => return Ok(revoked),
+                    Err(err) => return Err(err.into()),
+                };
+
+                // The other token of the session must stop working too
+                revoked.extend(end_session(tx, &session).await?);
+                services::audit::Event::new(
+                    &session.user_id,
+                    models::types::AuditAction::TokenRevoked,
+                )
+                .details(format!("session {}", session.session_id))
+                .record(tx, &actor.with_client(session.client_name))
+                .await?;
+                Ok(revoked)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    state.revoked_tokens.remember(revoked);
+
+    Ok(())
+}
+
+/// Ends the session whose refresh token has been used again.
+async fn end_reused_session(
+    state: &AppState,
+    db_conn: &mut diesel_async::AsyncPgConnection,
+    actor: &services::audit::Actor,
+    session: models::db::Session,
+) -> Result<(), ApiError> {
+    let revoked = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let revoked = end_session(tx, &session).await?;
+                services::audit::Event::new(
+                    &session.user_id,
+                    models::types::AuditAction::SessionEnded,
+                )
+                .details("a refresh token has been used again")
+                .record(tx, &actor.with_client(session.client_name))
+                .await?;
+                Ok(revoked)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    state.revoked_tokens.remember(revoked);
+    Ok(())
+}
+
+/// Revokes the tokens of the session and removes it. Meant to be called
+/// within a transaction, the revoked token has to be remembered once it
+/// commits.
+pub(crate) async fn end_session(
+    db_conn: &mut diesel_async::AsyncPgConnection,
+    session: &models::db::Session,
+) -> Result<Option<crate::auth::revocation::Revoked>, ApiError> {
+    // The refresh token stops working once the session is removed
+    let revoked = match crate::auth::layer::verify_token(&session.access_token) {
+        Some(claims) => Some(
+            crate::auth::revocation::RevokedTokens::revoke(
+                db_conn,
+                claims.jti(),
+                claims.expires_at(),
+            )
+            .await?,
+        ),
+        None => None,
+    };
+    diesel::delete(db_schema::sessions::table)
+        .filter(db_schema::sessions::session_id.eq(&session.session_id))
+        .execute(db_conn)
+        .await?;
+    Ok(revoked)
+}
+
+/// Revokes the tokens of all the sessions of the user and removes them. Meant
+/// to be called within a transaction, the revoked tokens have to be
+/// remembered once it commits.
+pub(crate) async fn end_all_sessions(
+    db_conn: &mut diesel_async::AsyncPgConnection,
+    user_id: &models::types::Uuid,
+) -> Result<Vec<crate::auth::revocation::Revoked>, ApiError> {
+    let access_tokens: Vec<String> = db_schema::sessions::table
+        .filter(db_schema::sessions::user_id.eq(user_id))
+        .select(db_schema::sessions::access_token)
+        .for_update()
+        .load(db_conn)
+        .await?;
+    let mut revoked = Vec::with_capacity(access_tokens.len());
+    // The expired tokens cannot be verified but they do not work anyway
+    for claims in access_tokens
+        .iter()
+        .filter_map(|token| crate::auth::layer::verify_token(token))
+    {
+        revoked.push(
+            crate::auth::revocation::RevokedTokens::revoke(
+                db_conn,
+                claims.jti(),
+                claims.expires_at(),
+            )
+            .await?,
+        );
+    }
+    diesel::delete(db_schema::sessions::table)
+        .filter(db_schema::sessions::user_id.eq(user_id))
+        .execute(db_conn)
+        .await?;
+    Ok(revoked)
+}
+
+/// Reports only the tokens issued to the user the request is authorized for
+/// as active so that the tokens of others cannot be checked.
+pub async fn introspect_token(
+    State(state): State<AppState>,
+    caller: crate::auth::UserClaims,
+    axum::extract::Form(form): axum::extract::Form<TokenIntrospectionRequest>,
+) -> Result<Json<TokenIntrospection>, ApiError> {
+    let Some(caller_id) = aliri::jwt::CoreClaims::sub(&caller).map(|sub| sub.to_string()) else {
+        return Ok(Json(TokenIntrospection::default()));
+    };
+
+    if let Some(claims) = crate::auth::layer::verify_token(&form.token) {
+        let sub = aliri::jwt::CoreClaims::sub(&claims).map(|sub| sub.to_string());
+        if state.revoked_tokens.contains(&claims.jti()) || sub.as_ref() != Some(&caller_id) {
+            return Ok(Json(TokenIntrospection::default()));
+        }
+        return Ok(Json(TokenIntrospection {
+            active: true,
+            scope: Some(claims.scope_list()),
+            token_type: Some("bearer"),
+            exp: Some(claims.exp().0),
+            iss: Some(claims.iss().to_string()),
+            aud: Some(claims.aud().iter().map(|aud| aud.to_string()).collect()),
+            jti: Some(claims.jti().to_string()),
+            sub,
+        }));
+    }
+
+    // Not an access token, maybe a refresh token
+    let mut db_conn = state.db.get().await?;
+    let session_search_res: Result<models::db::Session, _> = db_schema::sessions::table
+        .filter(
+            db_schema::sessions::refresh_token_hash
+                .eq(crate::auth::refresh_token::hash(&form.token))
+                .and(db_schema::sessions::valid_until.gt(chrono::Utc::now())),
+        )
+        .first(&mut db_conn)
+        .await;
+    match session_search_res {
+        Ok(session) if session.user_id.to_string() == caller_id => Ok(Json(TokenIntrospection {
+            active: true,
+            token_type: Some("refresh_token"),
+            exp: Some(session.valid_until.timestamp() as u64),
+            ..TokenIntrospection::default()
+        })),
+        Ok(_) | Err(diesel::result::Error::NotFound) => Ok(Json(TokenIntrospection::default())),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Bearer token used to authorize the request, needed to tell which of the
+/// sessions is the current one.
+type CurrentToken = axum::TypedHeader<
+    axum::headers::Authorization<axum::headers::authorization::Bearer>,
+>;
+
+async fn get_user_sessions(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::TypedHeader(current_token): CurrentToken,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+) -> Result<Resource<Vec<models::Session>>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let user_sessions: Vec<models::db::Session> = db_schema::sessions::table
+        .inner_join(db_schema::users::table)
+        .select(db_schema::sessions::all_columns)
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email))
+                .and(db_schema::sessions::valid_until.gt(chrono::Utc::now())),
+        )
+        .order(db_schema::sessions::started_at.desc())
+        .get_results(&mut db_conn)
+        .await?;
+
+    if user_sessions.is_empty() {
+        Err(NotFoundError::default().with_msg("failed to find any accessible sessions"))?;
     }
+
+    Ok(Resource::new(
+        user_sessions
+            .into_iter()
+            .map(|session| models::Session::from_db(session, current_token.token()))
+            .collect(),
+    )
+    .with_links([("self", format!("/api/user/{email}/sessions"))]))
+}
+
+async fn end_other_sessions(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::TypedHeader(current_token): CurrentToken,
+    actor: services::audit::Actor,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+) -> Result<DeletedResource, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let other_sessions: Vec<models::db::Session> = db_schema::sessions::table
+        .inner_join(db_schema::users::table)
+        .select(db_schema::sessions::all_columns)
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email))
+                .and(db_schema::sessions::access_token.ne(current_token.token())),
+        )
+        .get_results(&mut db_conn)
+        .await?;
+
+    let sessions_url = format!("/api/user/{email}/sessions");
+    let revoked = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let mut revoked = Vec::with_capacity(other_sessions.len());
+                for session in &other_sessions {
+                    revoked.extend(end_session(tx, session).await?);
+                    services::audit::Event::new(
+                        &session.user_id,
+                        models::types::AuditAction::SessionEnded,
+                    )
+                    .resource(format!("{sessions_url}/{}", session.session_id))
+                    .record(tx, &actor)
+                    .await?;
+                }
+                Ok(revoked)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    state.revoked_tokens.remember(revoked);
+
+    Ok(DeletedResource {
+        links: std::collections::HashMap::from([(
+            "sessions",
+            format!("/api/user/{email}/sessions"),
+        )]),
+    })
+}
+
+async fn end_user_session(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    actor: services::audit::Actor,
+    axum::extract::Path((email, session_id)): axum::extract::Path<(
+        EmailAddress,
+        models::types::Uuid,
+    )>,
+) -> Result<DeletedResource, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let session: models::db::Session = db_schema::sessions::table
+        .inner_join(db_schema::users::table)
+        .select(db_schema::sessions::all_columns)
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email))
+                .and(db_schema::sessions::session_id.eq(&session_id)),
+        )
+        .first(&mut db_conn)
+        .await?;
+
+    let resource = format!("/api/user/{email}/sessions/{session_id}");
+    let revoked = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let revoked = end_session(tx, &session).await?;
+                services::audit::Event::new(
+                    &session.user_id,
+                    models::types::AuditAction::SessionEnded,
+                )
+                .resource(resource)
+                .record(tx, &actor)
+                .await?;
+                Ok(revoked)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    state.revoked_tokens.remember(revoked);
+
+    Ok(DeletedResource {
+        links: std::collections::HashMap::from([(
+            "sessions",
+            format!("/api/user/{email}/sessions"),
+        )]),
+    })
 }
@@ -0,0 +1,101 @@
+use diesel::OptionalExtension;
+
+use crate::prelude::*;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/user/:email/settings",
+            routing::get_with(get_settings, |op| {
+                op.summary("Fetch the settings of the user")
+            })
+            .put_with(replace_settings, |op| {
+                op.summary("Replace the settings of the user")
+                    .description("The settings missing from the body are reset to their defaults.")
+            }),
+            |op| op.tag("Account Management"),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            crate::auth::scope::RequiredScope::new(
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+                crate::auth::scope::permission::ACCOUNT_ADMIN,
+            ),
+            crate::auth::scope::require_scope,
+        ))
+        .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()))
+}
+
+async fn get_settings(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+) -> Result<Resource<models::UserSettings>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let owner: models::types::Uuid = db_schema::users::table
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email)),
+        )
+        .select(db_schema::users::user_id)
+        .first(&mut db_conn)
+        .await?;
+
+    // the users who have never changed their settings have no row
+    let settings: Option<models::db::UserSettings> = db_schema::user_settings::table
+        .find(&owner)
+        .get_result(&mut db_conn)
+        .await
+        .optional()?;
+
+    Ok(
+        Resource::new(settings.map(|settings| settings.into()).unwrap_or_default())
+            .with_links([("self", format!("/api/user/{email}/settings"))]),
+    )
+}
+
+async fn replace_settings(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    json: JsonExtract<models::UserSettings>,
+) -> Result<ModifiedResource<models::UserSettings>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let input = json.extract();
+
+    let owner: models::types::Uuid = db_schema::users::table
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email)),
+        )
+        .select(db_schema::users::user_id)
+        .first(&mut db_conn)
+        .await?;
+
+    let settings = models::db::UserSettings {
+        user_id: owner,
+        auto_archive_after_days: input
+            .auto_archive_after_days
+            .map(|days| i32::from(days.get())),
+    };
+    let saved: models::db::UserSettings = diesel::insert_into(db_schema::user_settings::table)
+        .values(&settings)
+        .on_conflict(db_schema::user_settings::user_id)
+        .do_update()
+        .set(&settings)
+        .get_result(&mut db_conn)
+        .await?;
+
+    Ok(ModifiedResource {
+        location: Some(format!("/api/user/{email}/settings")),
+        resource: Resource::new(saved.into()),
+    })
+}
@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+
 use crate::{prelude::*, response::ModifiedResource};
+use diesel_async::scoped_futures::ScopedFutureExt;
 use models::{
     db::{self},
     Task,
@@ -9,7 +12,11 @@ pub fn router() -> ApiRouter<AppState> {
         .api_route_with(
             "/task/:task_id",
             routing::get_with(get_one_task, |op| op.summary("Fetch the task"))
-                .patch_with(patch_task, |op| op.summary("Update the task")),
+                .patch_with(patch_task, |op| op.summary("Update the task"))
+                .delete_with(delete_task, |op| {
+                    op.summary("Move the task to the trash")
+                        .description("The task can be restored until it is purged from the trash.")
+                }),
             |op| op.tag("Task Management"),
         )
         .api_route_with(
@@ -19,14 +26,34 @@ pub fn router() -> ApiRouter<AppState> {
                 .delete_with(make_uncompleted, |op| op.summary("Unmark the task as done")),
             |op| op.tag("Task Management"),
         )
+        .api_route_with(
+            "/task/:task_id/move",
+            routing::post_with(move_task, |op| {
+                op.summary("Move the task to another tracker")
+                    .description("The destination tracker must belong to the owner of the task.")
+            }),
+            |op| op.tag("Task Management"),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            crate::auth::scope::RequiredScope::new(
+                crate::auth::scope::permission::TASKS_READ,
+                crate::auth::scope::permission::TASKS_WRITE,
+            ),
+            crate::auth::scope::require_scope,
+        ))
         .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()))
 }
 
 // Attach appropiate links to the task resource
-fn task_links(task: &models::db::Task) -> Vec<(&'static str, String)> {
+pub(crate) fn task_links(task: &models::db::Task) -> Vec<(&'static str, String)> {
     let mut links = Vec::new();
     if task.completed_at.is_some() {
         links.push(("unmark", format!("/api/task/{}/checkmark", task.task_id)));
+        if task.archived_at.is_some() {
+            links.push(("unarchive", format!("/api/task/{}/archive", task.task_id)));
+        } else {
+            links.push(("archive", format!("/api/task/{}/archive", task.task_id)));
+        }
     } else {
         links.push(("checkmark", format!("/api/task/{}/checkmark", task.task_id)))
     }
@@ -39,6 +66,7 @@ fn task_links(task: &models::db::Task) -> Vec<(&'static str, String)> {
         links.push(("attach list", format!("/api/task/{}/list", task.task_id)))
     }
     links.push(("tracker", format!("/api/tracker/{}", task.tracker_id)));
+    links.push(("history", format!("/api/task/{}/history", task.task_id)));
     links.push(("self", format!("/api/task/{}", task.task_id)));
 
     links
@@ -59,6 +87,8 @@ async fn get_one_task(
         .inner_join(db_schema::trackers::table)
         .filter(db_schema::tasks::task_id.eq(task_id.clone()))
         // .filter(db_schema::trackers::user_id.eq(user_id.0))
+        .filter(db_schema::tasks::deleted_at.is_null())
+        .filter(db_schema::trackers::deleted_at.is_null())
         .select((db_schema::tasks::all_columns, db_schema::trackers::user_id))
         .first::<(db::Task, models::types::Uuid)>(&mut db_conn)
         .await?;
@@ -80,6 +110,7 @@ async fn patch_task(
         crate::auth::UserClaims,
     >,
     axum::extract::Path(task_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
     json: JsonExtract<models::core::TaskPatch>,
 ) -> Result<ModifiedResource<Task>, ApiError> {
     let mut db_conn = state.db.get().await?;
@@ -95,8 +126,9 @@ async fn patch_task(
         let res = db_schema::trackers::table
             .filter(
                 db_schema::trackers::user_id
-                    .eq(user_id.0)
-                    .and(db_schema::trackers::tracker_id.eq(tracker_id)),
+                    .eq(&user_id.0)
+                    .and(db_schema::trackers::tracker_id.eq(tracker_id))
+                    .and(db_schema::trackers::deleted_at.is_null()),
             )
             .execute(&mut db_conn)
             .await?;
@@ -107,10 +139,23 @@ async fn patch_task(
         }
     }
 
-    let updated: models::db::Task = diesel::update(db_schema::tasks::table)
-        .filter(db_schema::tasks::task_id.eq(task_id))
-        .set(models::db::TaskPatch::from(dbg!(patch)))
-        .get_result(&mut db_conn)
+    let updated: models::db::Task = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let updated: models::db::Task = diesel::update(db_schema::tasks::table)
+                    .filter(db_schema::tasks::task_id.eq(task_id))
+                    .filter(db_schema::tasks::deleted_at.is_null())
+                    .set(models::db::TaskPatch::from(dbg!(patch)))
+                    .get_result(tx)
+                    .await?;
+                services::audit::Event::new(&user_id.0, models::types::AuditAction::TaskUpdated)
+                    .resource(format!("/api/task/{}", updated.task_id))
+                    .record(tx, &actor)
+                    .await?;
+                Ok(updated)
+            }
+            .scope_boxed()
+        })
         .await?;
 
     let links = task_links(&updated);
@@ -128,6 +173,7 @@ async fn make_completed(
         crate::auth::UserClaims,
     >,
     axum::extract::Path(task_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
 ) -> Result<ModifiedResource<Task>, ApiError> {
     let mut db_conn = state.db.get().await?;
     use db_schema::tasks::dsl::tasks;
@@ -136,17 +182,33 @@ async fn make_completed(
     let res = tasks
         .inner_join(db_schema::trackers::table)
         .filter(db_schema::tasks::task_id.eq(task_id.clone()))
-        .filter(db_schema::trackers::user_id.eq(user_id.0))
+        .filter(db_schema::trackers::user_id.eq(&user_id.0))
+        .filter(db_schema::tasks::deleted_at.is_null())
+        .filter(db_schema::trackers::deleted_at.is_null())
         .execute(&mut db_conn)
         .await?;
     if res < 1 {
         Err(ForbiddenError::default().with_msg("no access to selected task"))?;
     }
 
-    let updated_task: db::Task = diesel::update(tasks)
-        .filter(db_schema::tasks::columns::task_id.eq(task_id.clone()))
-        .set(db_schema::tasks::columns::completed_at.eq(chrono::Utc::now()))
-        .get_result(&mut db_conn)
+    let task_id = &task_id;
+    let updated_task: db::Task = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let updated_task: db::Task = diesel::update(tasks)
+                    .filter(db_schema::tasks::columns::task_id.eq(task_id))
+                    .set(db_schema::tasks::columns::completed_at.eq(chrono::Utc::now()))
+                    .get_result(tx)
+                    .await?;
+                services::audit::Event::new(&user_id.0, models::types::AuditAction::TaskUpdated)
+                    .resource(format!("/api/task/{task_id}"))
+                    .details("marked done")
+                    .record(tx, &actor)
+                    .await?;
+                Ok(updated_task)
+            }
+            .scope_boxed()
+        })
         .await?;
 
     let links = task_links(&updated_task);
@@ -164,6 +226,7 @@ async fn make_uncompleted(
         crate::auth::UserClaims,
     >,
     axum::extract::Path(task_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
 ) -> Result<ModifiedResource<Task>, ApiError> {
     let mut db_conn = state.db.get().await?;
     use db_schema::tasks::dsl::tasks;
@@ -172,17 +235,36 @@ async fn make_uncompleted(
     let res = tasks
         .inner_join(db_schema::trackers::table)
         .filter(db_schema::tasks::task_id.eq(task_id.clone()))
-        .filter(db_schema::trackers::user_id.eq(user_id.0))
+        .filter(db_schema::trackers::user_id.eq(&user_id.0))
+        .filter(db_schema::tasks::deleted_at.is_null())
+        .filter(db_schema::trackers::deleted_at.is_null())
         .execute(&mut db_conn)
         .await?;
     if res < 1 {
         Err(ForbiddenError::default().with_msg("no access to selected task"))?;
     }
 
-    let updated_task: db::Task = diesel::update(tasks)
-        .filter(db_schema::tasks::columns::task_id.eq(task_id.clone()))
-        .set(db_schema::tasks::columns::completed_at.eq(Option::<chrono::NaiveDateTime>::None))
-        .get_result(&mut db_conn)
+    let task_id = &task_id;
+    let updated_task: db::Task = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let updated_task: db::Task = diesel::update(tasks)
+                    .filter(db_schema::tasks::columns::task_id.eq(task_id))
+                    .set(
+                        db_schema::tasks::columns::completed_at
+                            .eq(Option::<chrono::NaiveDateTime>::None),
+                    )
+                    .get_result(tx)
+                    .await?;
+                services::audit::Event::new(&user_id.0, models::types::AuditAction::TaskUpdated)
+                    .resource(format!("/api/task/{task_id}"))
+                    .details("unmarked as done")
+                    .record(tx, &actor)
+                    .await?;
+                Ok(updated_task)
+            }
+            .scope_boxed()
+        })
         .await?;
 
     let links = task_links(&updated_task);
@@ -192,3 +274,144 @@ async fn make_uncompleted(
         resource: Resource::new(updated_task.into()).with_links(links),
     })
 }
+
+async fn delete_task(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(task_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
+) -> Result<DeletedResource, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let (user_id, task_id) = (&user_id.0, &task_id);
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let affected = diesel::update(db_schema::tasks::table)
+                    .filter(
+                        db_schema::tasks::task_id
+                            .eq(task_id)
+                            .and(db_schema::tasks::deleted_at.is_null())
+                            .and(
+                                db_schema::tasks::tracker_id.eq_any(
+                                    db_schema::trackers::table
+                                        .filter(
+                                            db_schema::trackers::user_id
+                                                .eq(user_id)
+                                                .and(db_schema::trackers::deleted_at.is_null()),
+                                        )
+                                        .select(db_schema::trackers::tracker_id),
+                                ),
+                            ),
+                    )
+                    .set(db_schema::tasks::deleted_at.eq(chrono::Utc::now()))
+                    .execute(tx)
+                    .await?;
+                if affected < 1 {
+                    Err(NotFoundError::default().with_msg("no such task"))?;
+                }
+                services::audit::Event::new(user_id, models::types::AuditAction::TaskDeleted)
+                    .resource(format!("/api/task/{task_id}"))
+                    .details("moved to the trash")
+                    .record(tx, &actor)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    let user_email: String = db_schema::users::table
+        .find(user_id)
+        .select(db_schema::users::email)
+        .get_result(&mut db_conn)
+        .await?;
+
+    Ok(DeletedResource {
+        links: HashMap::from([
+            ("trash", format!("/api/user/{user_email}/trash")),
+            ("restore", format!("/api/task/{task_id}/restore")),
+        ]),
+    })
+}
+
+async fn move_task(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(task_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
+    json: JsonExtract<models::TaskMove>,
+) -> Result<ModifiedResource<Task>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let destination = json.extract().tracker_id;
+
+    let task_id = &task_id;
+    let moved: db::Task = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                // the task and its tracker are locked until the task is moved
+                let (source, owned_by): (models::types::Uuid, models::types::Uuid) =
+                    db_schema::tasks::table
+                        .inner_join(db_schema::trackers::table)
+                        .filter(db_schema::tasks::task_id.eq(task_id))
+                        .filter(db_schema::tasks::deleted_at.is_null())
+                        .filter(db_schema::trackers::deleted_at.is_null())
+                        .select((db_schema::tasks::tracker_id, db_schema::trackers::user_id))
+                        .for_update()
+                        .first(tx)
+                        .await?;
+                if owned_by != user_id.0 {
+                    Err(ForbiddenError::default().with_msg("no access to the selected task"))?;
+                }
+
+                // kept from being deleted until the task is moved
+                let destination_owned = db_schema::trackers::table
+                    .filter(
+                        db_schema::trackers::tracker_id
+                            .eq(&destination)
+                            .and(db_schema::trackers::user_id.eq(&user_id.0))
+                            .and(db_schema::trackers::deleted_at.is_null()),
+                    )
+                    .for_share()
+                    .execute(tx)
+                    .await?;
+                if destination_owned < 1 {
+                    Err(ForbiddenError::default()
+                        .with_msg("no access to the destination tracker")
+                        .with_docs())?;
+                }
+
+                let moved: db::Task = diesel::update(db_schema::tasks::table)
+                    .filter(db_schema::tasks::task_id.eq(task_id))
+                    .set(db_schema::tasks::tracker_id.eq(&destination))
+                    .get_result(tx)
+                    .await?;
+                if source != destination {
+                    services::audit::Event::new(
+                        &user_id.0,
+                        models::types::AuditAction::TaskUpdated,
+                    )
+                    .resource(format!("/api/task/{task_id}"))
+                    .details(format!("moved from the tracker {source} to {destination}"))
+                    .record(tx, &actor)
+                    .await?;
+                }
+                Ok(moved)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    let links = task_links(&moved);
+
+    Ok(ModifiedResource {
+        location: Some(format!("/api/task/{task_id}")),
+        resource: Resource::new(moved.into()).with_links(links),
+    })
+}
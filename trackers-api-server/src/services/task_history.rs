@@ -0,0 +1,172 @@
+//! Revision history of the tasks. The revisions are recorded by the database
+//! on every change of a task so that nothing overwritten is lost for good.
+
+use diesel::OptionalExtension;
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::prelude::*;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/task/:task_id/history",
+            routing::get_with(get_task_history, |op| {
+                op.summary("Fetch the change history of the task")
+                    .description("Lists the revisions of the task, the most recent first, each with the fields changed since the previous revision. The changes of the list are shown for each item separately.")
+            }),
+            |op| op.tag("Task Management"),
+        )
+        .api_route_with(
+            "/task/:task_id/history/:revision/restore",
+            routing::post_with(restore_revision, |op| {
+                op.summary("Restore the task as it was in a past revision")
+                    .description("The restored state is saved as a new revision so the restoration can be undone as well. The task stays in its current tracker if the tracker of the revision no longer exists.")
+            }),
+            |op| op.tag("Task Management"),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            crate::auth::scope::RequiredScope::new(
+                crate::auth::scope::permission::TASKS_READ,
+                crate::auth::scope::permission::TASKS_WRITE,
+            ),
+            crate::auth::scope::require_scope,
+        ))
+        .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()))
+}
+
+/// Finds the tracker of the task making sure that the task belongs to the user.
+async fn find_owned_task_tracker(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    task_id: &Base62Uuid,
+    user_id: &models::types::Uuid,
+) -> Result<models::types::Uuid, ApiError> {
+    let (tracker_id, owned_by): (models::types::Uuid, models::types::Uuid) =
+        db_schema::tasks::table
+            .inner_join(db_schema::trackers::table)
+            .filter(db_schema::tasks::task_id.eq(task_id))
+            .filter(db_schema::tasks::deleted_at.is_null())
+            .filter(db_schema::trackers::deleted_at.is_null())
+            .select((db_schema::tasks::tracker_id, db_schema::trackers::user_id))
+            .first(db_conn)
+            .await?;
+    if owned_by != *user_id {
+        Err(ForbiddenError::default().with_msg("no access to the selected task"))?;
+    }
+    Ok(tracker_id)
+}
+
+async fn get_task_history(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(task_id): axum::extract::Path<Base62Uuid>,
+) -> Result<Resource<Vec<models::TaskRevision>>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    find_owned_task_tracker(&mut db_conn, &task_id, &user_id.0).await?;
+
+    let revisions: Vec<models::db::TaskRevision> = db_schema::task_revisions::table
+        .filter(db_schema::task_revisions::task_id.eq(&task_id))
+        .order(db_schema::task_revisions::revision.asc())
+        .load(&mut db_conn)
+        .await?;
+
+    let mut history: Vec<models::TaskRevision> = revisions
+        .iter()
+        .enumerate()
+        .map(|(idx, revision)| {
+            let previous = idx.checked_sub(1).map(|prev_idx| &revisions[prev_idx]);
+            models::TaskRevision::from_db(revision, previous)
+        })
+        .collect();
+    history.reverse();
+
+    Ok(Resource::new(history).with_links([
+        ("task", format!("/api/task/{task_id}")),
+        ("self", format!("/api/task/{task_id}/history")),
+    ]))
+}
+
+async fn restore_revision(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path((task_id, revision)): axum::extract::Path<(Base62Uuid, i32)>,
+    actor: services::audit::Actor,
+) -> Result<ModifiedResource<models::Task>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let current_tracker_id = find_owned_task_tracker(&mut db_conn, &task_id, &user_id.0).await?;
+
+    let Some(past): Option<models::db::TaskRevision> = db_schema::task_revisions::table
+        .find((&task_id, revision))
+        .first(&mut db_conn)
+        .await
+        .optional()?
+    else {
+        return Err(NotFoundError::default()
+            .with_msg("no such revision of the task")
+            .with_links([("history", format!("/api/task/{task_id}/history"))])
+            .into());
+    };
+
+    let tracker_still_owned = db_schema::trackers::table
+        .filter(
+            db_schema::trackers::tracker_id
+                .eq(&past.tracker_id)
+                .and(db_schema::trackers::user_id.eq(&user_id.0))
+                .and(db_schema::trackers::deleted_at.is_null()),
+        )
+        .execute(&mut db_conn)
+        .await?;
+    let tracker_id = if tracker_still_owned > 0 {
+        past.tracker_id
+    } else {
+        current_tracker_id
+    };
+
+    let task_id = &task_id;
+    let restored: models::db::Task = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let restored: models::db::Task = diesel::update(db_schema::tasks::table)
+                    .filter(db_schema::tasks::task_id.eq(task_id))
+                    .set((
+                        db_schema::tasks::tracker_id.eq(tracker_id),
+                        db_schema::tasks::completed_at.eq(past.completed_at),
+                        db_schema::tasks::title.eq(past.title),
+                        db_schema::tasks::description.eq(past.description),
+                        db_schema::tasks::time_estimate.eq(past.time_estimate),
+                        db_schema::tasks::soft_deadline.eq(past.soft_deadline),
+                        db_schema::tasks::hard_deadline.eq(past.hard_deadline),
+                        db_schema::tasks::tags.eq(past.tags),
+                        db_schema::tasks::list.eq(past.list),
+                    ))
+                    .get_result(tx)
+                    .await?;
+                services::audit::Event::new(&user_id.0, models::types::AuditAction::TaskUpdated)
+                    .resource(format!("/api/task/{task_id}"))
+                    .details(format!("restored revision {revision}"))
+                    .record(tx, &actor)
+                    .await?;
+                Ok(restored)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    let links = services::task::task_links(&restored);
+
+    Ok(ModifiedResource {
+        location: Some(format!("/api/task/{task_id}")),
+        resource: Resource::new(restored.into()).with_links(links),
+    })
+}
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use diesel_async::scoped_futures::ScopedFutureExt;
+
 use crate::prelude::*;
 use models::{db, Task, TaskInput, Tracker, TrackerPatch, TrackerReplace};
 
@@ -12,9 +14,20 @@ pub fn router() -> ApiRouter<AppState> {
                     op.summary("Replace the tracker")
                 })
                 .delete_with(delete_tracker, |op| {
-                    op.summary("Deleta a tracker with all tasks stored within").description("The deletion will be unsuccessful if the tracker is marked as default tracker. Instead of deleting the default tracker it is recommended to rename it or replace its data completely.")
+                    op.summary("Move a tracker with all tasks stored within to the trash").description("The tracker and its tasks can be restored until they are purged from the trash. The deletion will be unsuccessful if the tracker is marked as default tracker. Instead of deleting the default tracker it is recommended to rename it or replace its data completely.")
                 })
-                .patch_with(patch_tracker, |op| op.summary("Partially update a tracker")),
+                .patch_with(patch_tracker, |op| op.summary("Partially update a tracker"))
+                .layer(axum::middleware::from_fn_with_state(
+                    crate::auth::scope::RequiredScope::new(
+                        crate::auth::scope::permission::TASKS_READ,
+                        crate::auth::scope::permission::TRACKERS_ADMIN,
+                    )
+                    .or_tracker_bound(crate::auth::scope::TrackerAccess::Read),
+                    crate::auth::scope::require_scope,
+                ))
+                .layer(
+                    crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+                ),
             |op| op.tag("Task Management"),
         )
         .api_route_with(
@@ -24,10 +37,38 @@ pub fn router() -> ApiRouter<AppState> {
             })
             .post_with(post_to_tracker_a_task, |op| {
                 op.summary("Create a task and add it to the specified tracker")
-            }),
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::TASKS_READ,
+                    crate::auth::scope::permission::TASKS_WRITE,
+                )
+                .or_tracker_bound(crate::auth::scope::TrackerAccess::Write),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Task Management"),
+        )
+        .api_route_with(
+            "/tracker/:tracker_id/merge-into/:other",
+            routing::post_with(merge_tracker, |op| {
+                op.summary("Merge the tracker into another one")
+                    .description("All the tasks of the tracker are moved to the other tracker, the views showing the tracker show the other one instead and the emptied tracker is moved to the trash. The default tracker cannot be merged into another one.")
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::TASKS_READ,
+                    crate::auth::scope::permission::TRACKERS_ADMIN,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
             |op| op.tag("Task Management"),
         )
-        .layer(crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()))
 }
 
 async fn get_one_tracker(
@@ -43,6 +84,7 @@ async fn get_one_tracker(
 
     let the_tracker: Tracker = trackers
         // .filter(db_schema::trackers::user_id.eq(user_id.0))
+        .filter(db_schema::trackers::deleted_at.is_null())
         .find(tracker_id.clone())
         .get_result(&mut db_conn)
         .await?;
@@ -64,6 +106,7 @@ async fn patch_tracker(
         crate::auth::UserClaims,
     >,
     axum::extract::Path(tracker_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
     json: JsonExtract<TrackerPatch>,
 ) -> Result<ModifiedResource<Tracker>, ApiError> {
     let mut db_conn = state.db.get().await?;
@@ -80,6 +123,7 @@ async fn patch_tracker(
     // Check if tracker actually existed before update
     let res = db_schema::trackers::table
         .filter(db_schema::trackers::user_id.eq(&user_id.0))
+        .filter(db_schema::trackers::deleted_at.is_null())
         .find(&tracker_id)
         .execute(&mut db_conn)
         .await?;
@@ -96,10 +140,23 @@ async fn patch_tracker(
             .with_links([("new tracker", format!("/api/user/{user_email}/trackers"))]))?;
     }
 
-    let tracker: Tracker = diesel::update(db_schema::trackers::table)
-        .filter(db_schema::trackers::tracker_id.eq(&tracker_id))
-        .set(input)
-        .get_result::<Tracker>(&mut db_conn)
+    let tracker_id = &tracker_id;
+    let tracker: Tracker = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let tracker: Tracker = diesel::update(db_schema::trackers::table)
+                    .filter(db_schema::trackers::tracker_id.eq(tracker_id))
+                    .set(input)
+                    .get_result::<Tracker>(tx)
+                    .await?;
+                services::audit::Event::new(&user_id.0, models::types::AuditAction::TrackerUpdated)
+                    .resource(format!("/api/tracker/{tracker_id}"))
+                    .record(tx, &actor)
+                    .await?;
+                Ok(tracker)
+            }
+            .scope_boxed()
+        })
         .await?;
 
     Ok(ModifiedResource {
@@ -118,6 +175,7 @@ async fn replace_tracker(
         crate::auth::UserClaims,
     >,
     axum::extract::Path(tracker_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
     json: JsonExtract<Tracker>,
 ) -> Result<ModifiedResource<Tracker>, ApiError> {
     let mut db_conn = state.db.get().await?;
@@ -132,6 +190,7 @@ async fn replace_tracker(
     // Check if tracker actually existed before update
     let res = db_schema::trackers::table
         .filter(db_schema::trackers::user_id.eq(&user_id.0))
+        .filter(db_schema::trackers::deleted_at.is_null())
         .find(&tracker_id)
         .execute(&mut db_conn)
         .await?;
@@ -148,10 +207,24 @@ async fn replace_tracker(
             .with_links([("new tracker", format!("/api/user/{user_email}/trackers"))]))?;
     }
 
-    let tracker: Tracker = diesel::update(db_schema::trackers::table)
-        .filter(db_schema::trackers::tracker_id.eq(&tracker_id))
-        .set(TrackerReplace::from(input))
-        .get_result::<Tracker>(&mut db_conn)
+    let tracker_id = &tracker_id;
+    let tracker: Tracker = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let tracker: Tracker = diesel::update(db_schema::trackers::table)
+                    .filter(db_schema::trackers::tracker_id.eq(tracker_id))
+                    .set(TrackerReplace::from(input))
+                    .get_result::<Tracker>(tx)
+                    .await?;
+                services::audit::Event::new(&user_id.0, models::types::AuditAction::TrackerUpdated)
+                    .resource(format!("/api/tracker/{tracker_id}"))
+                    .details("replaced")
+                    .record(tx, &actor)
+                    .await?;
+                Ok(tracker)
+            }
+            .scope_boxed()
+        })
         .await?;
 
     Ok(ModifiedResource {
@@ -170,12 +243,11 @@ async fn delete_tracker(
         crate::auth::UserClaims,
     >,
     axum::extract::Path(tracker_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
 ) -> Result<DeletedResource, ApiError> {
     let mut db_conn = state.db.get().await?;
     use db_schema::trackers::{columns, dsl::trackers};
 
-    // TODO: use a transaction here
-
     let to_delete_search_res: diesel::result::QueryResult<(Tracker, models::types::Email)> =
         trackers
             .inner_join(db_schema::users::table)
@@ -183,7 +255,8 @@ async fn delete_tracker(
                 columns::tracker_id
                     .eq(&tracker_id)
                     .and(columns::user_id.eq(&user_id.0))
-                    .and(columns::is_default.is_null()),
+                    .and(columns::is_default.is_null())
+                    .and(columns::deleted_at.is_null()),
             )
             .select((db_schema::trackers::all_columns, db_schema::users::email))
             .first(&mut db_conn)
@@ -211,23 +284,36 @@ async fn delete_tracker(
         },
     };
 
-    // delete all the tasks that belonged to that tracker
-    diesel::delete(db_schema::tasks::table)
-        .filter(db_schema::tasks::tracker_id.eq(&to_delete.tracker_id))
-        .execute(&mut db_conn)
-        .await?;
+    // the tasks are left as they are, they are hidden along with the tracker
+    // and are purged together with it
+    let tracker_id = &tracker_id;
+    db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let affected = diesel::update(trackers)
+                    .filter(columns::tracker_id.eq(&to_delete.tracker_id))
+                    .set(columns::deleted_at.eq(chrono::Utc::now()))
+                    .execute(tx)
+                    .await?;
 
-    let affected = diesel::delete(trackers)
-        .filter(columns::tracker_id.eq(&to_delete.tracker_id))
-        .execute(&mut db_conn)
+                if affected < 1 {
+                    Err(ServerError::from(anyhow::anyhow!(
+                        "failed to delete anything despite successfull query execution"
+                    )))?;
+                }
+                services::audit::Event::new(&user_id.0, models::types::AuditAction::TrackerDeleted)
+                    .resource(format!("/api/tracker/{tracker_id}"))
+                    .details(format!(
+                        "\"{}\" with all its tasks, moved to the trash",
+                        to_delete.name
+                    ))
+                    .record(tx, &actor)
+                    .await
+            }
+            .scope_boxed()
+        })
         .await?;
 
-    if affected < 1 {
-        Err(ServerError::from(anyhow::anyhow!(
-            "failed to delete anything despite successfull query execution"
-        )))?;
-    }
-
     Ok(DeletedResource {
         links: HashMap::from([
             (
@@ -235,6 +321,8 @@ async fn delete_tracker(
                 format!("/api/user/{user_email}/trackers"),
             ),
             ("user trackers", format!("/api/user/{user_email}/trackers")),
+            ("trash", format!("/api/user/{user_email}/trash")),
+            ("restore", format!("/api/tracker/{tracker_id}/restore")),
         ]),
     })
 }
@@ -254,17 +342,15 @@ async fn get_trackers_tasks(
         .filter(
             db_schema::trackers::columns::tracker_id
                 .eq(the_tracker_id)
-                .and(db_schema::trackers::columns::user_id.eq(user_id.0)),
+                .and(db_schema::trackers::columns::user_id.eq(user_id.0))
+                .and(db_schema::trackers::columns::deleted_at.is_null()),
         )
         .inner_join(db_schema::tasks::table)
         .select(db_schema::tasks::all_columns)
         .into_boxed();
 
-    if let Some(query) = query {
-        if !query.is_empty() {
-            tasks_query = tasks_query.filter(query.into_join_filters());
-        }
-    }
+    let query = query.map(|QsQuery(query)| query).unwrap_or_default();
+    tasks_query = tasks_query.filter(query.into_join_filters());
 
     let trackers_tasks: Vec<db::Task> = tasks_query.load(&mut db_conn).await?;
 
@@ -280,6 +366,7 @@ async fn post_to_tracker_a_task(
         crate::auth::UserClaims,
     >,
     axum::extract::Path(the_tracker_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
     json: JsonExtract<TaskInput>,
 ) -> Result<CreatedResource<Task>, ApiError> {
     let mut db_conn = state.db.get().await?;
@@ -301,7 +388,8 @@ async fn post_to_tracker_a_task(
         .filter(
             db_schema::trackers::tracker_id
                 .eq(&the_tracker_id)
-                .and(db_schema::trackers::user_id.eq(user_id.0)),
+                .and(db_schema::trackers::user_id.eq(&user_id.0))
+                .and(db_schema::trackers::deleted_at.is_null()),
         )
         .execute(&mut db_conn)
         .await?;
@@ -309,30 +397,44 @@ async fn post_to_tracker_a_task(
         Err(ForbiddenError::default().with_msg("no access to the selected tracker"))?;
     }
 
-    diesel::insert_into(tasks)
-        .values(db::Task {
-            task_id: new_task_id.clone(),
-            tracker_id: the_tracker_id,
-            title: input.title,
-            description: input.description,
-            completed_at: {
-                if input.checkmarked {
-                    Some(chrono::Utc::now())
-                } else {
-                    None
-                }
-            },
-            time_estimate: input.time_estimate,
-            soft_deadline: input.soft_deadline,
-            hard_deadline: input.hard_deadline,
-            tags: input.tags,
-            list: input.list,
+    let new_task_id = &new_task_id;
+    let inserted: db::Task = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let inserted: db::Task = diesel::insert_into(tasks)
+                    .values(db::Task {
+                        task_id: new_task_id.clone(),
+                        tracker_id: the_tracker_id,
+                        title: input.title,
+                        description: input.description,
+                        completed_at: {
+                            if input.checkmarked {
+                                Some(chrono::Utc::now())
+                            } else {
+                                None
+                            }
+                        },
+                        time_estimate: input.time_estimate,
+                        soft_deadline: input.soft_deadline,
+                        hard_deadline: input.hard_deadline,
+                        tags: input.tags,
+                        list: input.list,
+                        deleted_at: None,
+                        archived_at: None,
+                    })
+                    .get_result(tx)
+                    .await?;
+                services::audit::Event::new(&user_id.0, models::types::AuditAction::TaskCreated)
+                    .resource(format!("/api/task/{new_task_id}"))
+                    .details(format!("in the tracker {}", inserted.tracker_id))
+                    .record(tx, &actor)
+                    .await?;
+                Ok(inserted)
+            }
+            .scope_boxed()
         })
-        .execute(&mut db_conn)
         .await?;
 
-    let inserted: db::Task = tasks.find(new_task_id.clone()).first(&mut db_conn).await?;
-
     Ok(CreatedResource {
         location: format!("/api/task/{new_task_id}"),
         resource: Resource::new(inserted.into()).with_links([
@@ -341,3 +443,110 @@ async fn post_to_tracker_a_task(
         ]),
     })
 }
+
+async fn merge_tracker(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path((tracker_id, other)): axum::extract::Path<(Base62Uuid, Base62Uuid)>,
+    actor: services::audit::Actor,
+) -> Result<ModifiedResource<Tracker>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    if tracker_id == other {
+        Err(ConflictError::default()
+            .with_msg("a tracker cannot be merged into itself")
+            .with_docs())?;
+    }
+
+    let resource = format!("/api/tracker/{tracker_id}");
+    let (tracker_id, other) = (&tracker_id, &other);
+    let target: Tracker = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                // Both of the trackers are locked, in the same order by every
+                // merge, so that neither of them can be changed or deleted
+                // until the tasks are moved
+                let mut locked: Vec<Tracker> = db_schema::trackers::table
+                    .filter(db_schema::trackers::tracker_id.eq_any([tracker_id, other]))
+                    .filter(db_schema::trackers::deleted_at.is_null())
+                    .order(db_schema::trackers::tracker_id)
+                    .for_update()
+                    .load(tx)
+                    .await?;
+                let Some(source) = locked
+                    .iter()
+                    .position(|tracker| tracker.tracker_id == *tracker_id)
+                    .map(|index| locked.remove(index))
+                else {
+                    return Err(NotFoundError::default()
+                        .with_msg("no such task tracker")
+                        .into());
+                };
+                let Some(target) = locked.pop() else {
+                    return Err(NotFoundError::default()
+                        .with_msg("no such task tracker to merge into")
+                        .into());
+                };
+                if source.user_id != user_id.0 {
+                    Err(ForbiddenError::default().with_msg("no access to the selected task tracker"))?;
+                }
+                if source.is_default.into() {
+                    return Err(ForbiddenError::default().with_docs().with_links([("update tracker", format!("/api/tracker/{tracker_id}"))]).with_msg("the specified task tracker is considered the default task tracker for the user and as such it cannot be removed").into());
+                }
+                if target.user_id != user_id.0 {
+                    Err(ForbiddenError::default().with_msg("no access to the tracker to merge into"))?;
+                }
+
+                let moved_tasks = diesel::update(db_schema::tasks::table)
+                    .filter(db_schema::tasks::tracker_id.eq(tracker_id))
+                    .set(db_schema::tasks::tracker_id.eq(other))
+                    .execute(tx)
+                    .await?;
+                // the views showing both of the trackers already show the target
+                let target_views: Vec<models::types::Uuid> = db_schema::tracker_views::table
+                    .filter(db_schema::tracker_views::tracker_id.eq(other))
+                    .select(db_schema::tracker_views::view_id)
+                    .load(tx)
+                    .await?;
+                diesel::delete(db_schema::tracker_views::table)
+                    .filter(db_schema::tracker_views::tracker_id.eq(tracker_id))
+                    .filter(db_schema::tracker_views::view_id.eq_any(&target_views))
+                    .execute(tx)
+                    .await?;
+                diesel::update(db_schema::tracker_views::table)
+                    .filter(db_schema::tracker_views::tracker_id.eq(tracker_id))
+                    .set(db_schema::tracker_views::tracker_id.eq(other))
+                    .execute(tx)
+                    .await?;
+                // the emptied tracker goes to the trash like a deleted one, so
+                // that a merge into the wrong tracker can be undone
+                diesel::update(db_schema::trackers::table)
+                    .filter(db_schema::trackers::tracker_id.eq(tracker_id))
+                    .set(db_schema::trackers::deleted_at.eq(chrono::Utc::now()))
+                    .execute(tx)
+                    .await?;
+                services::audit::Event::new(&user_id.0, models::types::AuditAction::TrackerDeleted)
+                    .resource(resource)
+                    .details(format!(
+                        "\"{}\" merged into the tracker {other} along with {moved_tasks} tasks, moved to the trash",
+                        source.name
+                    ))
+                    .record(tx, &actor)
+                    .await?;
+                Ok(target)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(ModifiedResource {
+        location: Some(format!("/api/tracker/{other}")),
+        resource: Resource::new(target).with_links([
+            ("tasks", format!("/api/tracker/{other}/tasks")),
+            ("self", format!("/api/tracker/{other}")),
+        ]),
+    })
+}
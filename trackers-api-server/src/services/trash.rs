@@ -0,0 +1,315 @@
+//! Trash bin for the deleted trackers and tasks.
+//!
+//! Deleting a tracker or a task only marks it with the time of the deletion.
+//! The marked rows are left out by all the other endpoints and can be
+//! restored until they are purged after the retention period, set in days
+//! with `TRASH_RETENTION_DAYS`.
+
+use diesel::OptionalExtension;
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::prelude::*;
+
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+pub fn router() -> ApiRouter<AppState> {
+    ApiRouter::new()
+        .api_route_with(
+            "/user/:email/trash",
+            routing::get_with(get_trash, |op| {
+                op.summary("Fetch the deleted trackers and tasks")
+                    .description("The trackers and tasks stay in the trash for 30 days unless configured otherwise. Until then they can be restored.")
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::TASKS_READ,
+                    crate::auth::scope::permission::TASKS_WRITE,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Task Management"),
+        )
+        .api_route_with(
+            "/task/:task_id/restore",
+            routing::post_with(restore_task, |op| {
+                op.summary("Restore a deleted task")
+                    .description("The task can be restored only if its tracker is not in the trash.")
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::TASKS_READ,
+                    crate::auth::scope::permission::TASKS_WRITE,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Task Management"),
+        )
+        .api_route_with(
+            "/tracker/:tracker_id/restore",
+            routing::post_with(restore_tracker, |op| {
+                op.summary("Restore a deleted tracker")
+                    .description("The tasks of the tracker are restored along with it, except for the ones which have been deleted on their own.")
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::TASKS_READ,
+                    crate::auth::scope::permission::TRACKERS_ADMIN,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Task Management"),
+        )
+}
+
+/// How long the deleted trackers and tasks are kept in the trash. Anything
+/// but a positive number of days falls back to the default, as the purge
+/// would otherwise empty the trash right away.
+pub(crate) fn retention() -> chrono::Duration {
+    let days = crate::env_var("TRASH_RETENTION_DAYS")
+        .and_then(|days| days.parse().ok())
+        .filter(|days: &i64| *days > 0)
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+    chrono::Duration::days(days)
+}
+
+async fn get_trash(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+) -> Result<Resource<models::Trash>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let owner: models::types::Uuid = db_schema::users::table
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email)),
+        )
+        .select(db_schema::users::user_id)
+        .first(&mut db_conn)
+        .await?;
+
+    let trashed_trackers: Vec<models::Tracker> = db_schema::trackers::table
+        .filter(db_schema::trackers::user_id.eq(&owner))
+        .filter(db_schema::trackers::deleted_at.is_not_null())
+        .order(db_schema::trackers::deleted_at.desc())
+        .load(&mut db_conn)
+        .await?;
+
+    let trashed_tasks: Vec<models::db::Task> = db_schema::tasks::table
+        .inner_join(db_schema::trackers::table)
+        .filter(db_schema::trackers::user_id.eq(&owner))
+        .filter(db_schema::tasks::deleted_at.is_not_null())
+        .select(db_schema::tasks::all_columns)
+        .order(db_schema::tasks::deleted_at.desc())
+        .load(&mut db_conn)
+        .await?;
+
+    let retention = retention();
+    let trash = models::Trash {
+        trackers: trashed_trackers
+            .into_iter()
+            .filter_map(|tracker| {
+                let deleted_at = tracker.deleted_at?;
+                Some(models::Trashed {
+                    item: tracker,
+                    deleted_at,
+                    purged_at: deleted_at + retention,
+                })
+            })
+            .collect(),
+        tasks: trashed_tasks
+            .into_iter()
+            .filter_map(|task| {
+                let deleted_at = task.deleted_at?;
+                Some(models::Trashed {
+                    item: task.into(),
+                    deleted_at,
+                    purged_at: deleted_at + retention,
+                })
+            })
+            .collect(),
+    };
+
+    Ok(Resource::new(trash).with_links([("self", format!("/api/user/{email}/trash"))]))
+}
+
+async fn restore_task(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(task_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
+) -> Result<ModifiedResource<models::Task>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let (task_deleted_at, tracker_id, tracker_deleted_at, owned_by): (
+        Option<chrono::DateTime<chrono::Utc>>,
+        models::types::Uuid,
+        Option<chrono::DateTime<chrono::Utc>>,
+        models::types::Uuid,
+    ) = db_schema::tasks::table
+        .inner_join(db_schema::trackers::table)
+        .filter(db_schema::tasks::task_id.eq(&task_id))
+        .select((
+            db_schema::tasks::deleted_at,
+            db_schema::tasks::tracker_id,
+            db_schema::trackers::deleted_at,
+            db_schema::trackers::user_id,
+        ))
+        .first(&mut db_conn)
+        .await?;
+
+    if owned_by != user_id.0 {
+        Err(ForbiddenError::default().with_msg("no access to the selected task"))?;
+    }
+    if task_deleted_at.is_none() {
+        return Err(NotFoundError::default()
+            .with_msg("the task is not in the trash")
+            .with_links([("task", format!("/api/task/{task_id}"))])
+            .into());
+    }
+    if tracker_deleted_at.is_some() {
+        return Err(ConflictError::default()
+            .with_msg("the tracker of the task is in the trash, it has to be restored first")
+            .with_docs()
+            .with_links([(
+                "restore tracker",
+                format!("/api/tracker/{tracker_id}/restore"),
+            )])
+            .into());
+    }
+
+    let task_id = &task_id;
+    let restored: models::db::Task = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let restored: models::db::Task = diesel::update(db_schema::tasks::table)
+                    .filter(db_schema::tasks::task_id.eq(task_id))
+                    .set(
+                        db_schema::tasks::deleted_at
+                            .eq(Option::<chrono::DateTime<chrono::Utc>>::None),
+                    )
+                    .get_result(tx)
+                    .await?;
+                services::audit::Event::new(&user_id.0, models::types::AuditAction::TaskRestored)
+                    .resource(format!("/api/task/{task_id}"))
+                    .record(tx, &actor)
+                    .await?;
+                Ok(restored)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    let links = services::task::task_links(&restored);
+
+    Ok(ModifiedResource {
+        location: Some(format!("/api/task/{task_id}")),
+        resource: Resource::new(restored.into()).with_links(links),
+    })
+}
+
+async fn restore_tracker(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(tracker_id): axum::extract::Path<Base62Uuid>,
+    actor: services::audit::Actor,
+) -> Result<ModifiedResource<models::Tracker>, ApiError> {
+    let mut db_conn = state.db.get().await?;
+
+    let tracker_id = &tracker_id;
+    let restored: models::Tracker = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let restored: Option<models::Tracker> = diesel::update(db_schema::trackers::table)
+                    .filter(
+                        db_schema::trackers::tracker_id
+                            .eq(tracker_id)
+                            .and(db_schema::trackers::user_id.eq(&user_id.0))
+                            .and(db_schema::trackers::deleted_at.is_not_null()),
+                    )
+                    .set(
+                        db_schema::trackers::deleted_at
+                            .eq(Option::<chrono::DateTime<chrono::Utc>>::None),
+                    )
+                    .get_result(tx)
+                    .await
+                    .optional()?;
+                let Some(restored) = restored else {
+                    return Err(NotFoundError::default()
+                        .with_msg("no such tracker in the trash")
+                        .with_links([("tracker", format!("/api/tracker/{tracker_id}"))])
+                        .into());
+                };
+                services::audit::Event::new(
+                    &user_id.0,
+                    models::types::AuditAction::TrackerRestored,
+                )
+                .resource(format!("/api/tracker/{tracker_id}"))
+                .record(tx, &actor)
+                .await?;
+                Ok(restored)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(ModifiedResource {
+        location: Some(format!("/api/tracker/{tracker_id}")),
+        resource: Resource::new(restored).with_links([
+            ("tasks", format!("/api/tracker/{tracker_id}/tasks")),
+            ("self", format!("/api/tracker/{tracker_id}")),
+        ]),
+    })
+}
+
+/// Deletes for good the trackers and tasks which have been in the trash for
+/// longer than the retention period.
+pub(crate) async fn purge_expired(state: AppState) -> Result<(), anyhow::Error> {
+    let mut db_conn = state.db.get().await?;
+    let cutoff = chrono::Utc::now() - retention();
+
+    db_conn
+        .transaction::<_, diesel::result::Error, _>(|tx| {
+            async move {
+                diesel::delete(db_schema::tasks::table)
+                    .filter(
+                        db_schema::tasks::deleted_at
+                            .lt(cutoff)
+                            .or(db_schema::tasks::tracker_id.eq_any(
+                                db_schema::trackers::table
+                                    .filter(db_schema::trackers::deleted_at.lt(cutoff))
+                                    .select(db_schema::trackers::tracker_id),
+                            )),
+                    )
+                    .execute(tx)
+                    .await?;
+                diesel::delete(db_schema::trackers::table)
+                    .filter(db_schema::trackers::deleted_at.lt(cutoff))
+                    .execute(tx)
+                    .await?;
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await?;
+    Ok(())
+}
@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+
+use diesel_async::scoped_futures::ScopedFutureExt;
+
 use crate::prelude::*;
 use models::{RegistrationRequest, TaskInput, Tracker, TrackerInput, UserCreation};
 
@@ -11,6 +15,13 @@ pub fn router() -> ApiRouter<AppState> {
             .post_with(post_to_users_trackers, |op| {
                 op.summary("Create a new tracker")
             })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::TASKS_READ,
+                    crate::auth::scope::permission::TRACKERS_ADMIN,
+                ),
+                crate::auth::scope::require_scope,
+            ))
             .layer(
                 crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
             ),
@@ -24,11 +35,36 @@ pub fn router() -> ApiRouter<AppState> {
             .post_with(add_to_the_default_or_selected_tracker, |op| {
                 op.summary("Create a task in user's default tracker")
             })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::TASKS_READ,
+                    crate::auth::scope::permission::TASKS_WRITE,
+                ),
+                crate::auth::scope::require_scope,
+            ))
             .layer(
                 crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
             ),
             |op| op.tag("Task Management"),
         )
+        .api_route_with(
+            "/user/:email",
+            routing::delete_with(delete_user_account, |op| {
+                op.summary("Delete the user account")
+                    .description("The account is removed along with all the trackers, tasks, views, sessions and authorised clients owned by the user. The current password must be provided in the body of the request to confirm the deletion.")
+            })
+            .layer(axum::middleware::from_fn_with_state(
+                crate::auth::scope::RequiredScope::new(
+                    crate::auth::scope::permission::ACCOUNT_ADMIN,
+                    crate::auth::scope::permission::ACCOUNT_ADMIN,
+                ),
+                crate::auth::scope::require_scope,
+            ))
+            .layer(
+                crate::auth::layer::authorizer().jwt_layer(crate::auth::layer::authority().clone()),
+            ),
+            |op| op.tag("Account Management"),
+        )
         .api_route_with(
             "/users",
             routing::post_with(start_user_registaration, |op| {
@@ -38,51 +74,100 @@ pub fn router() -> ApiRouter<AppState> {
         )
 }
 
-async fn send_registration_code_mail(
-    generated_code: &models::core::ConfirmationCode,
-    receiver: &models::types::Email,
-) -> Result<reqwest::Response, reqwest::Error> {
-    let sendgrid_api_key = {
-        #[cfg(feature = "local-dev")]
-        let key = dotenvy::var("SENDGRID_API_KEY")
-            .expect("SENDGRID_API_KEY environment variable must be set");
-        #[cfg(not(feature = "local-dev"))]
-        let key = std::env::var("SENDGRID_API_KEY")
-            .expect("SENDGRID_API_KEY environment variable must be set");
-        key
+/// Checks whether the email address can be assigned to an account. An address
+/// is unavailable when it is used by an account, when it has been used by an
+/// account that changed its address within the redirection grace period or
+/// when it is awaiting confirmation by someone else.
+pub(crate) async fn is_email_available(
+    db_conn: &mut deadpool::managed::Object<
+        diesel_async::pooled_connection::AsyncDieselConnectionManager<
+            diesel_async::AsyncPgConnection,
+        >,
+    >,
+    email: &models::types::Email,
+) -> Result<bool, ApiError> {
+    let now = chrono::Utc::now();
+
+    let users_with_email = db_schema::users::table
+        .filter(db_schema::users::email.eq(email))
+        .execute(db_conn)
+        .await?;
+    let redirects_from_email = db_schema::email_redirects::table
+        .filter(
+            db_schema::email_redirects::old_email
+                .eq(email)
+                .and(db_schema::email_redirects::valid_until.gt(now)),
+        )
+        .execute(db_conn)
+        .await?;
+    let pending_changes_to_email = db_schema::email_change_requests::table
+        .filter(
+            db_schema::email_change_requests::new_email
+                .eq(email)
+                .and(db_schema::email_change_requests::valid_until.gt(now)),
+        )
+        .execute(db_conn)
+        .await?;
+
+    Ok(users_with_email + redirects_from_email + pending_changes_to_email == 0)
+}
+
+/// Redirects requests which use the old email address of an account in the
+/// `/api/user/:email` paths to the paths with the current address. The
+/// redirection is available only for a grace period after the address change.
+pub async fn redirect_changed_email<B: Send>(
+    State(state): State<AppState>,
+    request: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> axum::response::Response {
+    let path = request.uri().path().to_owned();
+    let Some(rest) = path.strip_prefix("/api/user/") else {
+        return next.run(request).await;
+    };
+    let (email_segment, remainder) = match rest.split_once('/') {
+        Some((email, remainder)) => (email, format!("/{remainder}")),
+        None => (rest, String::new()),
+    };
+    let Ok(email_segment) = percent_encoding::percent_decode_str(email_segment).decode_utf8()
+    else {
+        return next.run(request).await;
     };
-    let sendgrid_template_id = {
-        #[cfg(feature = "local-dev")]
-        let id = dotenvy::var("SENDGRID_REG_CODE_TEMPLATE_ID")
-            .expect("SENDGRID_REG_CODE_TEMPLATE_ID environment variable must be set");
-        #[cfg(not(feature = "local-dev"))]
-        let id = std::env::var("SENDGRID_REG_CODE_TEMPLATE_ID")
-            .expect("SENDGRID_REG_CODE_TEMPLATE_ID environment variable must be set");
-        id
+    let Ok(old_email) = models::types::Email::try_from(email_segment.into_owned()) else {
+        return next.run(request).await;
     };
-    let sendgrid_sender = {
-        #[cfg(feature = "local-dev")]
-        let address = dotenvy::var("SENDGRID_MAIL_SENDER")
-            .expect("SENDGRID_MAIL_SENDER environment variable must be set");
-        #[cfg(not(feature = "local-dev"))]
-        let address = std::env::var("SENDGRID_MAIL_SENDER")
-            .expect("SENDGRID_MAIL_SENDER environment variable must be set");
-        address
+    // only the addresses known to be redirected reach the database
+    let Some(user_id) = state.email_redirects.user_of(&old_email) else {
+        return next.run(request).await;
+    };
+    let Ok(mut db_conn) = state.db.get().await else {
+        return next.run(request).await;
     };
 
-    let body = format!(
-        r#"{{"from":{{"email":"{sendgrid_sender}"}},"personalizations":[{{"to":[{{"email":"{receiver}"}}],"dynamic_template_data":{{"code":"{generated_code}"}}}}],"template_id":"{sendgrid_template_id}"}}"#
-    );
-
-    let client = reqwest::Client::new();
-    let res = client
-        .post("https://api.sendgrid.com/v3/mail/send")
-        .bearer_auth(sendgrid_api_key)
-        .header("Content-Type", "application/json")
-        .body(body)
-        .send()
+    let current_email: Result<models::types::Email, _> = db_schema::users::table
+        .filter(db_schema::users::user_id.eq(&user_id))
+        .select(db_schema::users::email)
+        .first(&mut db_conn)
         .await;
-    res
+    let Ok(current_email) = current_email else {
+        return next.run(request).await;
+    };
+
+    let location = match request.uri().query().map(ToOwned::to_owned) {
+        Some(query) => format!("/api/user/{current_email}{remainder}?{query}"),
+        None => format!("/api/user/{current_email}{remainder}"),
+    };
+
+    (
+        [(axum::http::header::LOCATION, location.clone())],
+        ApiError {
+            status: 307,
+            msg: "the email address of the account has changed".to_owned(),
+            links: Some(HashMap::from([("moved to", location)])),
+            retry_after: None,
+            error: None,
+        },
+    )
+        .into_response()
 }
 
 /// Modifies the default json rejection to be a bit more user friendly
@@ -99,7 +184,10 @@ impl crate::json::FromJsonRejection for UserCreationRejection {
                     .strip_prefix(&(err.to_string() + ": "))
                     .unwrap()
                     .to_owned();
-                if let Some(passwd_msg) = msg.strip_prefix("password: ") {
+                if let Some(passwd_msg) = msg
+                    .strip_prefix("password: ")
+                    .or_else(|| msg.strip_prefix("new_password: "))
+                {
                     msg = String::new() + "password too weak; " + passwd_msg;
                     msg = msg.split_at(msg.find(" at line").unwrap()).0.to_owned();
                 };
@@ -123,6 +211,7 @@ impl aide::OperationOutput for UserCreationRejection {
 
 async fn start_user_registaration(
     State(state): State<AppState>,
+    crate::client_addr::ClientAddr(client_addr): crate::client_addr::ClientAddr,
     json: JsonExtract<UserCreation, UserCreationRejection>,
 ) -> Result<CreatedResource<RegistrationRequest>, ApiError> {
     let mut db_conn = state.db.get().await?;
@@ -135,36 +224,66 @@ async fn start_user_registaration(
     }
 
     // Check if email is free and can be used to create a new account
-    let existing_users_with_email = db_schema::users::table
-        .filter(db_schema::users::email.eq(&new_user.email))
+    if !is_email_available(&mut db_conn, &new_user.email).await? {
+        Err(ConflictError::default().with_msg("email already taken by another account"))?;
+    }
+
+    // A request that has not been confirmed in time can be replaced, a pending
+    // one should rather have its code sent again
+    diesel::delete(db_schema::registration_requests::table)
+        .filter(
+            db_schema::registration_requests::email
+                .eq(&new_user.email)
+                .and(db_schema::registration_requests::valid_until.le(chrono::Utc::now())),
+        )
         .execute(&mut db_conn)
         .await?;
-    if existing_users_with_email > 0 {
-        Err(ConflictError::default().with_msg("email already taken by another account"))?;
+    let pending_requests = db_schema::registration_requests::table
+        .find(&new_user.email)
+        .execute(&mut db_conn)
+        .await?;
+    if pending_requests != 0 {
+        Err(ConflictError::default()
+            .with_msg("the registration has already been started for this email")
+            .with_links([(
+                "resend code",
+                format!("/api/registration-request/{}/code", &new_user.email),
+            )]))?;
     }
 
+    crate::services::registration::limit_code_mails(&state, client_addr)?;
+
     // Generate confirmation code
     let code = models::ConfirmationCode::new();
     #[cfg(debug_assertions)]
     println!("registration code {code} has been generated");
 
     // Create registration request
+    let now = chrono::offset::Utc::now();
     let req: models::db::RegistrationRequest =
         diesel::insert_into(db_schema::registration_requests::table)
             .values(models::db::RegistrationRequest {
-                issued_at: chrono::offset::Utc::now(),
-                valid_until: chrono::offset::Utc::now()
-                    .checked_add_signed(chrono::Duration::minutes(10))
+                issued_at: now,
+                valid_until: now
+                    .checked_add_signed(chrono::Duration::minutes(
+                        crate::services::registration::CODE_LIFETIME_MINUTES,
+                    ))
                     .unwrap(),
                 email: new_user.email.clone(),
                 password: new_user.password.into_storeable(),
                 confirmation_code: code.clone().into(),
+                last_sent_at: now,
+                sent_count: 1,
+                failed_attempts: 0,
             })
             .get_result(&mut db_conn)
             .await?;
 
-    // Send the code through an email with SendGrid
-    send_registration_code_mail(&code, &new_user.email).await?;
+    // Send the code through an email
+    state
+        .mailer
+        .send(crate::mail::templates::REGISTRATION_CODE.render(&new_user.email, &[("code", &code)]))
+        .await?;
 
     Ok(CreatedResource {
         location: format!("/api/registration-request/{}", &new_user.email),
@@ -181,6 +300,72 @@ async fn start_user_registaration(
     })
 }
 
+async fn delete_user_account(
+    State(state): State<AppState>,
+    crate::auth::VariableScope(user_id): crate::auth::VariableScope<
+        crate::auth::scope::UserIdScope,
+        crate::auth::UserClaims,
+    >,
+    axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    json: JsonExtract<models::AccountDeletion>,
+) -> Result<DeletedResource, ApiError> {
+    let mut db_conn = state.db.get().await?;
+    let confirmation = json.extract();
+
+    let user_search_res: Result<models::db::User, _> = db_schema::users::table
+        .filter(
+            db_schema::users::user_id
+                .eq(&user_id.0)
+                .and(db_schema::users::email.eq(&email)),
+        )
+        .first(&mut db_conn)
+        .await;
+    let user = match user_search_res {
+        Ok(user) => user,
+        Err(diesel::result::Error::NotFound) => {
+            return Err(ForbiddenError::default()
+                .with_msg("cannot delete such user from current session")
+                .into())
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    if !confirmation.password.match_with(user.password) {
+        Err(BadRequestError::default()
+            .with_msg("password not correct")
+            .with_docs())?;
+    }
+
+    // The trackers, tasks and views are removed by the database through the
+    // cascading foreign keys. Sessions and client credentials are removed
+    // explicitly to make the revocation obvious to the reader.
+    let deleted_user_id = user.user_id;
+    let revoked = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                // The access tokens issued so far would keep working until
+                // they expire
+                let revoked = services::session::end_all_sessions(tx, &deleted_user_id).await?;
+                diesel::delete(db_schema::authorised_clients::table)
+                    .filter(db_schema::authorised_clients::user_id.eq(&deleted_user_id))
+                    .execute(tx)
+                    .await?;
+                diesel::delete(db_schema::users::table)
+                    .filter(db_schema::users::user_id.eq(&deleted_user_id))
+                    .execute(tx)
+                    .await?;
+                Ok(revoked)
+            }
+            .scope_boxed()
+        })
+        .await?;
+    state.revoked_tokens.remember(revoked);
+
+    Ok(DeletedResource {
+        links: HashMap::from([("register", "/api/users".to_owned())]),
+    })
+}
+
 async fn get_users_trackers(
     State(state): State<AppState>,
     crate::auth::VariableScope(user_id): crate::auth::VariableScope<
@@ -188,11 +373,13 @@ async fn get_users_trackers(
         crate::auth::UserClaims,
     >,
     axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    query: Option<QsQuery<crate::query_param::TrackersQuery>>,
 ) -> Result<Resource<Vec<Tracker>>, ApiError> {
     let mut db_conn = state.db.get().await?;
     use db_schema::trackers::dsl::trackers;
+    let query = query.map(|QsQuery(query)| query).unwrap_or_default();
 
-    let user_trackers: Vec<Tracker> = trackers
+    let mut trackers_query = trackers
         .inner_join(db_schema::users::table)
         .select(db_schema::trackers::all_columns)
         .filter(
@@ -200,8 +387,13 @@ async fn get_users_trackers(
                 .eq(&user_id.0)
                 .and(db_schema::users::email.eq(&email)),
         )
-        .get_results(&mut db_conn)
-        .await?;
+        .filter(db_schema::trackers::deleted_at.is_null())
+        .into_boxed();
+    if !query.archived.unwrap_or(false) {
+        trackers_query = trackers_query.filter(db_schema::trackers::archived_at.is_null());
+    }
+
+    let user_trackers: Vec<Tracker> = trackers_query.get_results(&mut db_conn).await?;
 
     if user_trackers.is_empty() {
         Err(NotFoundError::default().with_msg("failed to find any accessible trackers"))?;
@@ -227,6 +419,7 @@ async fn post_to_users_trackers(
         crate::auth::UserClaims,
     >,
     axum::extract::Path(email): axum::extract::Path<EmailAddress>,
+    actor: services::audit::Actor,
     json: JsonExtract<TrackerInput>,
 ) -> Result<CreatedResource<Tracker>, ApiError> {
     let mut db_conn = state.db.get().await?;
@@ -254,14 +447,32 @@ async fn post_to_users_trackers(
             .with_msg("cannot add trackers for such user from current session"))?;
     }
 
-    let inserted: Tracker = diesel::insert_into(db_schema::trackers::dsl::trackers)
-        .values(Tracker {
-            tracker_id: new_tracker_id,
-            user_id: user_id.0,
-            name: input.name,
-            is_default: false.into(),
+    let inserted: Tracker = db_conn
+        .transaction::<_, ApiError, _>(|tx| {
+            async move {
+                let inserted: Tracker = diesel::insert_into(db_schema::trackers::dsl::trackers)
+                    .values(Tracker {
+                        tracker_id: new_tracker_id,
+                        user_id: user_id.0,
+                        name: input.name,
+                        is_default: false.into(),
+                        deleted_at: None,
+                        archived_at: None,
+                    })
+                    .get_result(tx)
+                    .await?;
+                services::audit::Event::new(
+                    &inserted.user_id,
+                    models::types::AuditAction::TrackerCreated,
+                )
+                .resource(format!("/api/tracker/{}", inserted.tracker_id))
+                .details(format!("\"{}\"", inserted.name))
+                .record(tx, &actor)
+                .await?;
+                Ok(inserted)
+            }
+            .scope_boxed()
         })
-        .get_result(&mut db_conn)
         .await?;
 
     let links = [("self", format!("/api/tracker/{}", &inserted.tracker_id))];
@@ -283,17 +494,19 @@ async fn get_all_user_tasks(
 ) -> Result<Resource<Vec<models::core::task::Task>>, ApiError> {
     let mut db_conn = state.db.get().await?;
 
+    let query = query.map(|QsQuery(query)| query).unwrap_or_default();
+
     let mut tasks_query = db_schema::trackers::table
         .filter(db_schema::trackers::columns::user_id.eq(user_id.0))
+        .filter(db_schema::trackers::columns::deleted_at.is_null())
         .inner_join(db_schema::tasks::table)
         .select(db_schema::tasks::all_columns)
         .into_boxed();
 
-    if let Some(query) = query {
-        if !query.is_empty() {
-            tasks_query = tasks_query.filter(query.into_join_filters());
-        }
+    if !query.archived.unwrap_or(false) {
+        tasks_query = tasks_query.filter(db_schema::trackers::columns::archived_at.is_null());
     }
+    tasks_query = tasks_query.filter(query.into_join_filters());
 
     let trackers_tasks: Vec<models::db::Task> = tasks_query.load(&mut db_conn).await?;
 
@@ -309,6 +522,7 @@ async fn add_to_the_default_or_selected_tracker(
         crate::auth::UserClaims,
     >,
     axum::extract::Path(_email): axum::extract::Path<EmailAddress>,
+    actor: services::audit::Actor,
     json: JsonExtract<TaskInput>,
 ) -> Result<CreatedResource<models::core::task::Task>, ApiError> {
     let mut db_conn = state.db.get().await?;
@@ -319,7 +533,8 @@ async fn add_to_the_default_or_selected_tracker(
             .filter(
                 db_schema::trackers::user_id
                     .eq(&user_id.0)
-                    .and(db_schema::trackers::tracker_id.eq(selected_tracker_id)),
+                    .and(db_schema::trackers::tracker_id.eq(selected_tracker_id))
+                    .and(db_schema::trackers::deleted_at.is_null()),
             )
             .execute(&mut db_conn)
             .await?;
@@ -329,28 +544,47 @@ async fn add_to_the_default_or_selected_tracker(
 
         let new_task_id = input.task_id.unwrap_or(models::types::Uuid::new());
 
-        let inserted: models::db::Task = diesel::insert_into(db_schema::tasks::table)
-            .values(models::db::Task {
-                task_id: new_task_id.clone(),
-                tracker_id: selected_tracker_id.clone(),
-                completed_at: {
-                    match (input.checkmarked, input.checkmarked_at) {
-                        (false, None) => None,
-                        (true, Some(c_at)) => Some(c_at),
-                        (true, None) => Some(chrono::Utc::now()),
-                        (false, Some(_)) => None,
-                    }
-                },
-                title: input.title,
-                description: input.description,
-                time_estimate: input.time_estimate,
-                soft_deadline: input.soft_deadline,
-                hard_deadline: input.hard_deadline,
-                tags: input.tags,
-                list: input.list,
+        let new_task_id = &new_task_id;
+        let inserted: models::db::Task = db_conn
+            .transaction::<_, ApiError, _>(|tx| {
+                async move {
+                    let inserted: models::db::Task = diesel::insert_into(db_schema::tasks::table)
+                        .values(models::db::Task {
+                            task_id: new_task_id.clone(),
+                            tracker_id: selected_tracker_id.clone(),
+                            completed_at: {
+                                match (input.checkmarked, input.checkmarked_at) {
+                                    (false, None) => None,
+                                    (true, Some(c_at)) => Some(c_at),
+                                    (true, None) => Some(chrono::Utc::now()),
+                                    (false, Some(_)) => None,
+                                }
+                            },
+                            title: input.title,
+                            description: input.description,
+                            time_estimate: input.time_estimate,
+                            soft_deadline: input.soft_deadline,
+                            hard_deadline: input.hard_deadline,
+                            tags: input.tags,
+                            list: input.list,
+                            deleted_at: None,
+                            archived_at: None,
+                        })
+                        .returning(db_schema::tasks::all_columns)
+                        .get_result(tx)
+                        .await?;
+                    services::audit::Event::new(
+                        &user_id.0,
+                        models::types::AuditAction::TaskCreated,
+                    )
+                    .resource(format!("/api/task/{new_task_id}"))
+                    .details(format!("in the tracker {}", inserted.tracker_id))
+                    .record(tx, &actor)
+                    .await?;
+                    Ok(inserted)
+                }
+                .scope_boxed()
             })
-            .returning(db_schema::tasks::all_columns)
-            .get_result(&mut db_conn)
             .await?;
 
         Ok(CreatedResource {
@@ -361,7 +595,7 @@ async fn add_to_the_default_or_selected_tracker(
         let default_tracker_id: models::types::Uuid = db_schema::trackers::table
             .filter(
                 db_schema::trackers::user_id
-                    .eq(user_id.0)
+                    .eq(&user_id.0)
                     .and(db_schema::trackers::is_default.eq(true)),
             )
             .select(db_schema::trackers::tracker_id)
@@ -370,28 +604,47 @@ async fn add_to_the_default_or_selected_tracker(
 
         let new_task_id = input.task_id.unwrap_or(models::types::Uuid::new());
 
-        let inserted: models::db::Task = diesel::insert_into(db_schema::tasks::table)
-            .values(models::db::Task {
-                task_id: new_task_id.clone(),
-                tracker_id: default_tracker_id,
-                completed_at: {
-                    match (input.checkmarked, input.checkmarked_at) {
-                        (false, None) => None,
-                        (true, Some(c_at)) => Some(c_at),
-                        (true, None) => Some(chrono::Utc::now()),
-                        (false, Some(_)) => None,
-                    }
-                },
-                title: input.title,
-                description: input.description,
-                time_estimate: input.time_estimate,
-                soft_deadline: input.soft_deadline,
-                hard_deadline: input.hard_deadline,
-                tags: input.tags,
-                list: input.list,
+        let new_task_id = &new_task_id;
+        let inserted: models::db::Task = db_conn
+            .transaction::<_, ApiError, _>(|tx| {
+                async move {
+                    let inserted: models::db::Task = diesel::insert_into(db_schema::tasks::table)
+                        .values(models::db::Task {
+                            task_id: new_task_id.clone(),
+                            tracker_id: default_tracker_id,
+                            completed_at: {
+                                match (input.checkmarked, input.checkmarked_at) {
+                                    (false, None) => None,
+                                    (true, Some(c_at)) => Some(c_at),
+                                    (true, None) => Some(chrono::Utc::now()),
+                                    (false, Some(_)) => None,
+                                }
+                            },
+                            title: input.title,
+                            description: input.description,
+                            time_estimate: input.time_estimate,
+                            soft_deadline: input.soft_deadline,
+                            hard_deadline: input.hard_deadline,
+                            tags: input.tags,
+                            list: input.list,
+                            deleted_at: None,
+                            archived_at: None,
+                        })
+                        .returning(db_schema::tasks::all_columns)
+                        .get_result(tx)
+                        .await?;
+                    services::audit::Event::new(
+                        &user_id.0,
+                        models::types::AuditAction::TaskCreated,
+                    )
+                    .resource(format!("/api/task/{new_task_id}"))
+                    .details(format!("in the tracker {}", inserted.tracker_id))
+                    .record(tx, &actor)
+                    .await?;
+                    Ok(inserted)
+                }
+                .scope_boxed()
             })
-            .returning(db_schema::tasks::all_columns)
-            .get_result(&mut db_conn)
             .await?;
 
         Ok(CreatedResource {
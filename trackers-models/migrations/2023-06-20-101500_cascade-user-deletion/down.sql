@@ -0,0 +1,21 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tracker_views DROP CONSTRAINT tracker_views_tracker_id_fkey,
+  ADD CONSTRAINT tracker_views_tracker_id_fkey FOREIGN KEY (tracker_id) REFERENCES trackers (tracker_id);
+
+ALTER TABLE tracker_views DROP CONSTRAINT tracker_views_view_id_fkey,
+  ADD CONSTRAINT tracker_views_view_id_fkey FOREIGN KEY (view_id) REFERENCES views (view_id);
+
+ALTER TABLE views DROP CONSTRAINT views_user_id_fkey,
+  ADD CONSTRAINT views_user_id_fkey FOREIGN KEY (user_id) REFERENCES users;
+
+ALTER TABLE authorised_clients DROP CONSTRAINT authorised_clients_user_id_fkey,
+  ADD CONSTRAINT authorised_clients_user_id_fkey FOREIGN KEY (user_id) REFERENCES users;
+
+ALTER TABLE tasks DROP CONSTRAINT tasks_tracker_id_fkey,
+  ADD CONSTRAINT tasks_tracker_id_fkey FOREIGN KEY (tracker_id) REFERENCES trackers;
+
+ALTER TABLE trackers DROP CONSTRAINT trackers_user_id_fkey,
+  ADD CONSTRAINT trackers_user_id_fkey FOREIGN KEY (user_id) REFERENCES users;
+
+ALTER TABLE sessions DROP CONSTRAINT sessions_user_id_fkey,
+  ADD CONSTRAINT sessions_user_id_fkey FOREIGN KEY (user_id) REFERENCES users;
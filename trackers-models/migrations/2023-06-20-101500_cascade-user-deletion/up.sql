@@ -0,0 +1,25 @@
+-- Your SQL goes here
+
+-- Deleting a user must remove everything that belongs to them. The foreign
+-- keys are recreated with cascades so that a single DELETE on the users table
+-- is enough to clean up all the owned resources.
+ALTER TABLE sessions DROP CONSTRAINT sessions_user_id_fkey,
+  ADD CONSTRAINT sessions_user_id_fkey FOREIGN KEY (user_id) REFERENCES users ON DELETE CASCADE;
+
+ALTER TABLE trackers DROP CONSTRAINT trackers_user_id_fkey,
+  ADD CONSTRAINT trackers_user_id_fkey FOREIGN KEY (user_id) REFERENCES users ON DELETE CASCADE;
+
+ALTER TABLE tasks DROP CONSTRAINT tasks_tracker_id_fkey,
+  ADD CONSTRAINT tasks_tracker_id_fkey FOREIGN KEY (tracker_id) REFERENCES trackers ON DELETE CASCADE;
+
+ALTER TABLE authorised_clients DROP CONSTRAINT authorised_clients_user_id_fkey,
+  ADD CONSTRAINT authorised_clients_user_id_fkey FOREIGN KEY (user_id) REFERENCES users ON DELETE CASCADE;
+
+ALTER TABLE views DROP CONSTRAINT views_user_id_fkey,
+  ADD CONSTRAINT views_user_id_fkey FOREIGN KEY (user_id) REFERENCES users ON DELETE CASCADE;
+
+ALTER TABLE tracker_views DROP CONSTRAINT tracker_views_view_id_fkey,
+  ADD CONSTRAINT tracker_views_view_id_fkey FOREIGN KEY (view_id) REFERENCES views (view_id) ON DELETE CASCADE;
+
+ALTER TABLE tracker_views DROP CONSTRAINT tracker_views_tracker_id_fkey,
+  ADD CONSTRAINT tracker_views_tracker_id_fkey FOREIGN KEY (tracker_id) REFERENCES trackers (tracker_id) ON DELETE CASCADE;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE password_reset_requests(
+  user_id uuid not null primary key references users on delete cascade,
+  issued_at timestamp with time zone not null default now(),
+  valid_until timestamp with time zone not null default now() + interval '15 minutes',
+  code_hash bytea not null -- unlike registration codes the reset codes are stored hashed
+);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX email_redirects_user_id_idx;
+
+DROP TABLE email_redirects;
+DROP TABLE email_change_requests;
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE email_change_requests(
+  user_id uuid not null primary key references users on delete cascade,
+  new_email varchar(320) not null unique,
+  issued_at timestamp with time zone not null default now(),
+  valid_until timestamp with time zone not null default now() + interval '15 minutes',
+  code_hash bytea not null
+);
+
+-- Old addresses are kept for a grace period during which the paths using them
+-- are redirected and the addresses cannot be taken by another account.
+CREATE TABLE email_redirects(
+  old_email varchar(320) not null primary key,
+  user_id uuid not null references users on delete cascade,
+  valid_until timestamp with time zone not null default now() + interval '30 days'
+);
+CREATE INDEX email_redirects_user_id_idx ON email_redirects USING HASH (user_id);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+alter table registration_requests
+  drop column last_sent_at,
+  drop column sent_count,
+  drop column failed_attempts;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+alter table registration_requests
+  add column last_sent_at timestamp with time zone not null default now(),
+  add column sent_count integer not null default 1,
+  add column failed_attempts integer not null default 0;
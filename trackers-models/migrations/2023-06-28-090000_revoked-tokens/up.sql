@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE revoked_tokens(
+  jti uuid not null primary key,
+  expires_at timestamp with time zone not null
+);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+alter table sessions
+  drop column session_id,
+  drop column client_name,
+  drop column user_agent;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+alter table sessions
+  add column session_id uuid not null unique default gen_random_uuid(),
+  add column client_name varchar null,
+  add column user_agent varchar null;
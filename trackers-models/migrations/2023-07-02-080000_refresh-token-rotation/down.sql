@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE used_refresh_tokens;
+
+alter table sessions
+  drop column refresh_token_hash,
+  add column refresh_token varchar null;
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+alter table sessions
+  drop column refresh_token,
+  add column refresh_token_hash bytea null unique;
+
+CREATE TABLE used_refresh_tokens(
+  token_hash bytea not null primary key,
+  session_id uuid not null references sessions(session_id) on delete cascade,
+  used_at timestamp with time zone not null default now()
+);
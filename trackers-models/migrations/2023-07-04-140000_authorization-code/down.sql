@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE authorization_codes;
+
+alter table authorised_clients
+  drop column redirect_uris;
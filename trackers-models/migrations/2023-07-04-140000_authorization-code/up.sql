@@ -0,0 +1,13 @@
+-- Your SQL goes here
+alter table authorised_clients
+  add column redirect_uris text[] not null default '{}';
+
+CREATE TABLE authorization_codes(
+  code_hash bytea not null primary key,
+  client_id varchar not null references authorised_clients(client_id) on delete cascade,
+  user_id uuid not null references users on delete cascade,
+  redirect_uri text not null,
+  scope text not null,
+  code_challenge varchar not null,
+  expires_at timestamp with time zone not null
+);
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE device_authorizations(
+  device_code_hash bytea not null primary key,
+  user_code varchar not null unique,
+  client_id varchar references authorised_clients(client_id) on delete cascade,
+  scope text not null,
+  user_id uuid references users on delete cascade,
+  denied boolean not null default false,
+  poll_interval integer not null,
+  last_polled_at timestamp with time zone,
+  expires_at timestamp with time zone not null
+);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+alter table sessions
+  drop column scope;
+
+alter table authorised_clients
+  drop column scope;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+alter table authorised_clients
+  add column scope text not null default 'user_resources';
+
+alter table sessions
+  add column scope text not null default 'user_resources';
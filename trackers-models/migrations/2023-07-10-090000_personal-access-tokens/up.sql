@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE personal_access_tokens(
+  token_id uuid not null primary key,
+  user_id uuid not null references users on delete cascade,
+  name varchar not null,
+  token_hash bytea not null unique,
+  scope text not null,
+  created_at timestamp with time zone not null,
+  expires_at timestamp with time zone not null,
+  last_used_at timestamp with time zone
+);
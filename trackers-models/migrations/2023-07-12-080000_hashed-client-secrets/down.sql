@@ -0,0 +1,17 @@
+-- This file should undo anything in `up.sql`
+-- The secrets cannot be recovered from the hashes. The existing clients are
+-- given random secrets which nobody knows, so their secrets have to be rotated.
+ALTER TABLE authorised_clients
+  ADD COLUMN client_secret varchar;
+
+UPDATE authorised_clients
+  SET client_secret = encode(gen_random_bytes(32), 'hex');
+
+ALTER TABLE authorised_clients
+  ALTER COLUMN client_secret SET NOT NULL,
+  ADD UNIQUE (client_secret),
+  DROP COLUMN client_secret_hash,
+  DROP COLUMN previous_secret_hash,
+  DROP COLUMN previous_secret_valid_until;
+
+CREATE INDEX authorised_clients_credentials_idx ON authorised_clients (client_id, client_secret);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+
+ALTER TABLE authorised_clients
+  ADD COLUMN client_secret_hash bytea,
+  ADD COLUMN previous_secret_hash bytea,
+  ADD COLUMN previous_secret_valid_until timestamp with time zone;
+
+-- Hash the secrets the same way the server does, with bcrypt at cost 12
+UPDATE authorised_clients
+  SET client_secret_hash = convert_to(crypt(client_secret, gen_salt('bf', 12)), 'UTF8');
+
+ALTER TABLE authorised_clients
+  ALTER COLUMN client_secret_hash SET NOT NULL,
+  DROP COLUMN client_secret;
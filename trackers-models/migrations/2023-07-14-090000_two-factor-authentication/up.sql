@@ -0,0 +1,19 @@
+-- Your SQL goes here
+CREATE TABLE totp_credentials(
+  user_id uuid not null primary key references users on delete cascade,
+  secret bytea not null,
+  confirmed_at timestamp with time zone,
+  last_used_step bigint
+);
+
+CREATE TABLE recovery_codes(
+  code_hash bytea not null primary key,
+  user_id uuid not null references users on delete cascade
+);
+
+CREATE TABLE mfa_challenges(
+  challenge_hash bytea not null primary key,
+  user_id uuid not null references users on delete cascade,
+  attempts integer not null default 0,
+  expires_at timestamp with time zone not null
+);
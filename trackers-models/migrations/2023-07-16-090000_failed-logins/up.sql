@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE failed_logins(
+  attempt_id uuid not null primary key,
+  email varchar not null,
+  user_id uuid references users on delete cascade,
+  ip_address varchar not null,
+  user_agent varchar,
+  attempted_at timestamp with time zone not null
+);
+
+CREATE INDEX failed_logins_email_idx ON failed_logins(email, attempted_at);
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE magic_links(
+  user_id uuid not null primary key references users on delete cascade,
+  code_hash bytea not null unique,
+  issued_at timestamp with time zone not null,
+  valid_until timestamp with time zone not null
+);
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE authorization_codes ADD COLUMN nonce varchar;
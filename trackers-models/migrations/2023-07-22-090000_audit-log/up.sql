@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE audit_events(
+  event_id uuid not null primary key,
+  user_id uuid not null references users on delete cascade,
+  action varchar not null,
+  resource varchar,
+  client varchar,
+  ip_address varchar,
+  details text,
+  occurred_at timestamp with time zone not null
+);
+
+CREATE INDEX audit_events_user_idx ON audit_events(user_id, occurred_at);
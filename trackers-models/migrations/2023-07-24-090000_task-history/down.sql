@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER tasks_record_update ON tasks;
+DROP TRIGGER tasks_record_insert ON tasks;
+DROP FUNCTION record_task_revision();
+DROP TABLE task_revisions;
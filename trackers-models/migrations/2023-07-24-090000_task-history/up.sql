@@ -0,0 +1,48 @@
+-- Your SQL goes here
+CREATE TABLE task_revisions(
+  task_id uuid not null references tasks on delete cascade,
+  revision integer not null,
+  tracker_id uuid not null,
+  completed_at timestamp with time zone null,
+  title varchar(256) not null,
+  description text null,
+  time_estimate bigint null,
+  soft_deadline timestamp with time zone null,
+  hard_deadline timestamp with time zone null,
+  tags text[] null,
+  list list_item_t[] null,
+  revised_at timestamp with time zone not null default now(),
+  primary key (task_id, revision)
+);
+
+-- Every write to a task is kept, whichever endpoint it comes from
+CREATE FUNCTION record_task_revision() RETURNS trigger AS $$
+BEGIN
+  INSERT INTO task_revisions (task_id, revision, tracker_id, completed_at, title, description,
+                              time_estimate, soft_deadline, hard_deadline, tags, list)
+  SELECT NEW.task_id, coalesce(max(revision), 0) + 1, NEW.tracker_id, NEW.completed_at,
+         NEW.title, NEW.description, NEW.time_estimate, NEW.soft_deadline, NEW.hard_deadline,
+         NEW.tags, NEW.list
+  FROM task_revisions WHERE task_id = NEW.task_id;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tasks_record_insert AFTER INSERT ON tasks
+  FOR EACH ROW EXECUTE FUNCTION record_task_revision();
+
+CREATE TRIGGER tasks_record_update AFTER UPDATE ON tasks
+  FOR EACH ROW
+  WHEN ((OLD.tracker_id, OLD.completed_at, OLD.title, OLD.description, OLD.time_estimate,
+         OLD.soft_deadline, OLD.hard_deadline, OLD.tags, OLD.list)
+        IS DISTINCT FROM
+        (NEW.tracker_id, NEW.completed_at, NEW.title, NEW.description, NEW.time_estimate,
+         NEW.soft_deadline, NEW.hard_deadline, NEW.tags, NEW.list))
+  EXECUTE FUNCTION record_task_revision();
+
+-- The tasks which existed before start their history as they are now
+INSERT INTO task_revisions (task_id, revision, tracker_id, completed_at, title, description,
+                            time_estimate, soft_deadline, hard_deadline, tags, list)
+SELECT task_id, 1, tracker_id, completed_at, title, description, time_estimate, soft_deadline,
+       hard_deadline, tags, list
+FROM tasks;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX tasks_deleted_at_idx;
+DROP INDEX trackers_deleted_at_idx;
+ALTER TABLE tasks DROP COLUMN deleted_at;
+ALTER TABLE trackers DROP COLUMN deleted_at;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE trackers ADD COLUMN deleted_at timestamp with time zone null default null;
+ALTER TABLE tasks ADD COLUMN deleted_at timestamp with time zone null default null;
+
+CREATE INDEX trackers_deleted_at_idx ON trackers (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX tasks_deleted_at_idx ON tasks (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE user_settings;
+DROP TRIGGER tasks_unarchive_unchecked ON tasks;
+DROP FUNCTION unarchive_unchecked_task();
+DROP INDEX tasks_active_idx;
+ALTER TABLE tasks DROP COLUMN archived_at;
+ALTER TABLE trackers DROP COLUMN archived_at;
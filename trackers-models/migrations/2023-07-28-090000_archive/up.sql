@@ -0,0 +1,25 @@
+-- Your SQL goes here
+ALTER TABLE trackers ADD COLUMN archived_at timestamp with time zone null default null;
+ALTER TABLE tasks ADD COLUMN archived_at timestamp with time zone null default null;
+
+-- The listings skip the archived tasks unless asked otherwise
+CREATE INDEX tasks_active_idx ON tasks (tracker_id) WHERE archived_at IS NULL AND deleted_at IS NULL;
+
+-- Only the checkmarked tasks stay in the archive, whichever way they get
+-- uncheckmarked
+CREATE FUNCTION unarchive_unchecked_task() RETURNS trigger AS $$
+BEGIN
+  IF NEW.completed_at IS NULL THEN
+    NEW.archived_at := NULL;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tasks_unarchive_unchecked BEFORE INSERT OR UPDATE ON tasks
+  FOR EACH ROW EXECUTE FUNCTION unarchive_unchecked_task();
+
+CREATE TABLE user_settings (
+  user_id uuid primary key references users(user_id) on delete cascade,
+  auto_archive_after_days integer null default null check (auto_archive_after_days > 0)
+);
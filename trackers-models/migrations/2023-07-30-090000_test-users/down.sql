@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM email_redirects WHERE old_email = 'moved@tracke.rs';
+DELETE FROM users WHERE user_id IN (
+  '00000000-0000-0000-0000-000000000026',
+  '00000000-0000-0000-0000-000000000028',
+  '00000000-0000-0000-0000-000000000033',
+  '00000000-0000-0000-0000-000000000041'
+);
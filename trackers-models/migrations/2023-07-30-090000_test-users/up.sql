@@ -0,0 +1,35 @@
+-- Your SQL goes here
+
+-- Accounts used up by the tests, the password is the same as the one of the
+-- main test user: password$123
+INSERT INTO users (user_id, email, password) VALUES
+('00000000-0000-0000-0000-000000000026', 'deleted@tracke.rs', '$2a$12$EZbyhEM/VbrWhzmOs9UAteRiJWYygryQC0cSZhDq5aKHIE56WmZby'::bytea);
+
+INSERT INTO trackers (tracker_id, user_id, name, is_default) VALUES
+('00000000-0000-0000-0000-000000000026', '00000000-0000-0000-0000-000000000026', 'Backlog', true);
+
+INSERT INTO users (user_id, email, password) VALUES
+('00000000-0000-0000-0000-000000000028', 'expired@tracke.rs', '$2a$12$EZbyhEM/VbrWhzmOs9UAteRiJWYygryQC0cSZhDq5aKHIE56WmZby'::bytea);
+
+INSERT INTO users (user_id, email, password) VALUES
+('00000000-0000-0000-0000-000000000033', 'other@tracke.rs', '$2a$12$EZbyhEM/VbrWhzmOs9UAteRiJWYygryQC0cSZhDq5aKHIE56WmZby'::bytea);
+
+-- Two-factor authentication is on, the authenticator app uses the secret of
+-- the test vectors of RFC 6238 (GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ in base32)
+-- and the only recovery code is test-recovery-code
+INSERT INTO users (user_id, email, password) VALUES
+('00000000-0000-0000-0000-000000000041', 'mfa@tracke.rs', '$2a$12$EZbyhEM/VbrWhzmOs9UAteRiJWYygryQC0cSZhDq5aKHIE56WmZby'::bytea);
+
+INSERT INTO totp_credentials (user_id, secret, confirmed_at) VALUES
+('00000000-0000-0000-0000-000000000041', '12345678901234567890'::bytea, now());
+
+INSERT INTO recovery_codes (code_hash, user_id) VALUES
+(sha256('test-recovery-code'::bytea), '00000000-0000-0000-0000-000000000041');
+
+-- An email change which has not been confirmed in time and not purged yet
+INSERT INTO email_change_requests (user_id, new_email, issued_at, valid_until, code_hash) VALUES
+('00000000-0000-0000-0000-000000000028', 'wanted@tracke.rs', now() - interval '1 hour', now() - interval '45 minutes', ''::bytea);
+
+-- The main test user used to have another address
+INSERT INTO email_redirects (old_email, user_id) VALUES
+('moved@tracke.rs', '00000000-0000-0000-0000-000000000000');
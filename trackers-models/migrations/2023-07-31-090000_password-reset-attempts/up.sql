@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE password_reset_requests
+  ADD COLUMN failed_attempts integer not null default 0;
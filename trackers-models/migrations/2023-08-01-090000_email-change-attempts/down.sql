@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE email_change_requests DROP COLUMN failed_attempts;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM email_redirects WHERE user_id = '00000000-0000-0000-0000-000000000128';
+DELETE FROM users WHERE user_id = '00000000-0000-0000-0000-000000000128';
+
+INSERT INTO email_redirects (old_email, user_id) VALUES
+('moved@tracke.rs', '00000000-0000-0000-0000-000000000000');
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+-- The redirect is created by the tests instead, a seeded one would expire
+-- with the grace period counted from the time of the migration
+DELETE FROM email_redirects WHERE old_email = 'moved@tracke.rs';
+
+-- An account in the middle of an email change, the password is the same as
+-- the one of the main test user: password$123 and the code is MOVED1234.
+-- The request is valid long enough that the tests can confirm it at any time.
+INSERT INTO users (user_id, email, password) VALUES
+('00000000-0000-0000-0000-000000000128', 'moving@tracke.rs', '$2a$12$EZbyhEM/VbrWhzmOs9UAteRiJWYygryQC0cSZhDq5aKHIE56WmZby'::bytea);
+
+INSERT INTO trackers (tracker_id, user_id, name, is_default) VALUES
+('00000000-0000-0000-0000-000000000128', '00000000-0000-0000-0000-000000000128', 'Backlog', true);
+
+INSERT INTO email_change_requests (user_id, new_email, issued_at, valid_until, code_hash) VALUES
+('00000000-0000-0000-0000-000000000128', 'moved@tracke.rs', now(), now() + interval '100 years', convert_to(crypt('MOVED1234', gen_salt('bf', 12)), 'UTF8'));
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM users WHERE user_id = '00000000-0000-0000-0000-000000000043';
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+
+-- An account which has been sent a login code, the code is
+-- test-magic-link-code and the password is the same as the one of the main
+-- test user: password$123
+INSERT INTO users (user_id, email, password) VALUES
+('00000000-0000-0000-0000-000000000043', 'magic@tracke.rs', '$2a$12$EZbyhEM/VbrWhzmOs9UAteRiJWYygryQC0cSZhDq5aKHIE56WmZby'::bytea);
+
+INSERT INTO trackers (tracker_id, user_id, name, is_default) VALUES
+('00000000-0000-0000-0000-000000000043', '00000000-0000-0000-0000-000000000043', 'Backlog', true);
+
+INSERT INTO magic_links (user_id, code_hash, issued_at, valid_until) VALUES
+('00000000-0000-0000-0000-000000000043', sha256('test-magic-link-code'::bytea), now(), now() + interval '100 years');
@@ -0,0 +1,34 @@
+/// A security-relevant or data-changing event recorded in the audit log of
+/// an account.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct AuditEvent {
+    pub event_id: crate::types::Uuid,
+    pub action: crate::types::AuditAction,
+    /// Path of the affected resource, if there is one
+    pub resource: Option<String>,
+    /// Name of the authorised client or of the personal access token used to
+    /// make the request. Empty for the requests made within the sessions
+    /// started by the user directly.
+    pub client: Option<String>,
+    /// Address of the client which made the request
+    pub ip_address: Option<String>,
+    /// Additional information depending on the action, e.g. the granted
+    /// scopes or the changed fields
+    pub details: Option<String>,
+    pub occurred_at: chrono::DateTime<chrono::offset::Utc>,
+}
+
+#[cfg(feature = "diesel")]
+impl From<crate::db::AuditEvent> for AuditEvent {
+    fn from(value: crate::db::AuditEvent) -> Self {
+        Self {
+            event_id: value.event_id,
+            action: value.action,
+            resource: value.resource,
+            client: value.client,
+            ip_address: value.ip_address,
+            details: value.details,
+            occurred_at: value.occurred_at,
+        }
+    }
+}
@@ -0,0 +1,59 @@
+/// A change applied to each of the tasks selected for a bulk operation.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    Checkmark,
+    Uncheckmark,
+    /// Adds the tags the tasks do not have yet
+    AddTags(crate::types::Tags),
+    RemoveTags(crate::types::Tags),
+    /// Moves the tasks to another tracker of the same user
+    Move {
+        tracker_id: crate::types::Uuid,
+    },
+    /// Sets the selected deadline of the tasks, an empty deadline clears it
+    SetDeadline {
+        kind: DeadlineKind,
+        #[serde(default)]
+        deadline: Option<chrono::DateTime<chrono::offset::Utc>>,
+    },
+    /// Moves the tasks to the trash
+    Delete,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadlineKind {
+    Soft,
+    Hard,
+}
+
+/// The outcome of a bulk operation for each of the selected tasks.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct BulkReport {
+    /// Number of the tasks which have been changed
+    pub updated: usize,
+    /// Number of the tasks which have been left as they were because there
+    /// was nothing to change
+    pub skipped: usize,
+    /// Number of the requested tasks which could not be found
+    pub failed: usize,
+    pub results: Vec<BulkItemResult>,
+}
+
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct BulkItemResult {
+    pub task_id: crate::types::Uuid,
+    pub outcome: BulkOutcome,
+    /// Why the task has been skipped or could not be changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, Clone, Copy, PartialEq, Eq, schemars::JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOutcome {
+    Updated,
+    Skipped,
+    Failed,
+}
@@ -7,8 +7,18 @@ pub struct ClientCredentialsRequest {
     /// Name of the client assigned by the user
     pub name: String,
     /// An URL at which more information can be found about the client
-    /// appliaction
+    /// appliaction, must be an http or https URL
     pub website: String,
+    /// The URIs to which the users can be redirected after they agree to let
+    /// the client access their account with the authorization code flow. Only
+    /// https URIs are accepted, or http URIs of the loopback interface for the
+    /// native applications.
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    /// Space separated list of the scopes the client can be given, full
+    /// access to the resources of the user if not specified
+    #[serde(default)]
+    pub scope: Option<String>,
 }
 
 impl ClientCredentialsRequest {
@@ -19,6 +29,11 @@ impl ClientCredentialsRequest {
             website: self.website,
             client_id: crate::types::ClientSecretStr::new(),
             client_secret: crate::types::ClientSecretStr::new(),
+            redirect_uris: self.redirect_uris,
+            scope: self
+                .scope
+                .unwrap_or_else(|| String::from("user_resources")),
+            previous_secret_valid_until: None,
         }
     }
 }
@@ -51,24 +66,19 @@ pub struct AuthorisedClient {
     /// The id given to the client application that can be used for OAuth2
     /// Client Credentials flow
     pub client_id: crate::types::ClientSecretStr,
+    /// The URIs registered for the authorization code flow
+    pub redirect_uris: Vec<String>,
+    /// Space separated list of the scopes the client can be given
+    pub scope: String,
+    /// Until when the secret replaced during the last rotation is still
+    /// accepted
+    pub previous_secret_valid_until: Option<chrono::DateTime<chrono::offset::Utc>>,
 }
 
-/// Just like [`AuthorisedClient`] but with the secret included
+/// Just like [`AuthorisedClient`] but with the secret included. Only the hash
+/// of the secret is stored so the secret is available only in the responses
+/// to the requests which generate it.
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, schemars::JsonSchema)]
-#[cfg_attr(
-    feature = "diesel",
-    derive(
-        diesel::Queryable,
-        diesel::Insertable,
-        diesel::Associations,
-        diesel::AsChangeset
-    )
-)]
-#[cfg_attr(feature="diesel", diesel(table_name = crate::db::schema::authorised_clients))]
-#[cfg_attr(
-    feature = "diesel",
-    diesel(belongs_to(crate::db::user::User, foreign_key = user_id))
-)]
 pub struct AuthorisedClientFull {
     /// ID of requesting user    
     pub user_id: crate::types::Uuid,
@@ -82,6 +92,13 @@ pub struct AuthorisedClientFull {
     pub client_id: crate::types::ClientSecretStr,
     /// The secret assigned to the client application neccessary
     pub client_secret: crate::types::ClientSecretStr,
+    /// The URIs registered for the authorization code flow
+    pub redirect_uris: Vec<String>,
+    /// Space separated list of the scopes the client can be given
+    pub scope: String,
+    /// Until when the secret replaced during the last rotation is still
+    /// accepted
+    pub previous_secret_valid_until: Option<chrono::DateTime<chrono::offset::Utc>>,
 }
 
 impl From<AuthorisedClientFull> for AuthorisedClient {
@@ -91,6 +108,24 @@ impl From<AuthorisedClientFull> for AuthorisedClient {
             name: value.name,
             website: value.website,
             client_id: value.client_id,
+            redirect_uris: value.redirect_uris,
+            scope: value.scope,
+            previous_secret_valid_until: value.previous_secret_valid_until,
+        }
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl From<crate::db::AuthorisedClient> for AuthorisedClient {
+    fn from(value: crate::db::AuthorisedClient) -> Self {
+        Self {
+            user_id: value.user_id,
+            name: value.name,
+            website: value.website,
+            client_id: value.client_id,
+            redirect_uris: value.redirect_uris,
+            scope: value.scope,
+            previous_secret_valid_until: value.previous_secret_valid_until,
         }
     }
 }
@@ -16,6 +16,16 @@ impl ConfirmationCode {
             "1234567890ABCDEFGHIJKLMNOPRSTUWXYZ@#$%&",
         ))
     }
+    /// Hashes the code so that it can be stored without revealing it to anyone
+    /// with access to the database.
+    pub fn to_storeable(&self) -> Vec<u8> {
+        crate::types::bcrypt_hash::hash(&self.0)
+    }
+    /// Checks the code against a hash produced with
+    /// [`to_storeable`](Self::to_storeable). Malformed hashes never match.
+    pub fn match_with(&self, stored: Vec<u8>) -> bool {
+        crate::types::bcrypt_hash::verify(&self.0, stored)
+    }
 }
 
 impl Into<String> for ConfirmationCode {
@@ -49,7 +59,7 @@ impl schemars::JsonSchema for ConfirmationCode {
             metadata: Some(Box::new(schemars::schema::Metadata {
                 title: Some(std::string::String::from("registration confirmation code")),
                 description: Some(String::from(
-                    r#"A 9-character long code used to confirm registration request. Expected to be sent in a body of application/json type."#,
+                    r#"A 9-character long code used to confirm registration request or other actions which require access to the email address. Expected to be sent in a body of application/json type."#,
                 )),
                 default: None,
                 examples: vec!["1H7Z&O9PL".into()],
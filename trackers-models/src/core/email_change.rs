@@ -0,0 +1,30 @@
+/// Input starting the change of the email address used by an account. The
+/// current password must be confirmed before a confirmation code is sent to
+/// the new address.
+#[derive(Debug, serde::Deserialize, Clone, schemars::JsonSchema)]
+pub struct EmailChangeInit {
+    pub new_email: crate::types::Email,
+    /// The current password of the account
+    pub password: crate::types::PasswordInput,
+}
+
+/// The request is issued when a user wants to use a different email address
+/// for their account. The address is swapped once the request is confirmed
+/// with the code sent to the new address. For a grace period after the swap
+/// requests using the old address in their path are redirected to the new
+/// one.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct EmailChangeRequest {
+    /// The timestamp of the request.
+    pub issued_at: chrono::DateTime<chrono::offset::Utc>,
+    /// How long can the request be confirmed with the confirmation code until
+    /// it becomes invalid.
+    pub valid_until: chrono::DateTime<chrono::offset::Utc>,
+    /// The address used by the account before the change.
+    pub old_email: crate::types::Email,
+    /// The address that will be used by the account after the change.
+    pub new_email: crate::types::Email,
+    /// Has the request been confirmed with the confirmation code sent to the
+    /// new address?
+    pub confirmed_with_code: bool,
+}
@@ -0,0 +1,20 @@
+/// Input asking for a login code to be sent by mail. If an account with the
+/// given email exists the code will be sent to that address.
+#[derive(Debug, serde::Deserialize, Clone, schemars::JsonSchema)]
+pub struct MagicLinkInit {
+    pub email: crate::types::Email,
+}
+
+/// The request is issued when a user wants to log in without the password.
+/// The code sent to the email address of the account can be exchanged for the
+/// tokens once, using the `magic_link` grant. Asking for a new code makes the
+/// previous one stop working.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct MagicLinkRequest {
+    /// The timestamp of the request.
+    pub issued_at: chrono::DateTime<chrono::offset::Utc>,
+    /// Until when the code can be used.
+    pub valid_until: chrono::DateTime<chrono::offset::Utc>,
+    /// The email address the code has been sent to.
+    pub user_email: crate::types::Email,
+}
@@ -0,0 +1,42 @@
+/// The state of the two-factor authentication of an account.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct MfaStatus {
+    /// Are the codes from an authenticator app required to log in?
+    pub totp_enabled: bool,
+    /// How many of the recovery codes have not been used yet.
+    pub recovery_codes_left: i64,
+}
+
+/// Details needed to add the account to an authenticator app. The
+/// enrolment has to be confirmed with a code generated by the app before
+/// the codes are required to log in.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct TotpEnrolment {
+    /// The shared secret encoded with base32, for the apps which cannot scan
+    /// QR codes
+    pub secret: String,
+    /// The URI to be shown as a QR code
+    pub otpauth_uri: String,
+}
+
+/// A code generated by the authenticator app.
+#[derive(Debug, serde::Deserialize, Clone, schemars::JsonSchema)]
+pub struct TotpConfirmation {
+    pub code: String,
+}
+
+/// Codes which can be used once each in place of a code from the
+/// authenticator app, for example when the device with the app is lost. They
+/// are shown only once.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct RecoveryCodes {
+    pub codes: Vec<String>,
+}
+
+/// Input turning off the two-factor authentication. The current password
+/// must be confirmed.
+#[derive(Debug, serde::Deserialize, Clone, schemars::JsonSchema)]
+pub struct MfaDisable {
+    /// The current password of the account
+    pub password: crate::types::PasswordInput,
+}
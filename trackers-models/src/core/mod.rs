@@ -8,10 +8,18 @@ pub mod tracker;
 pub use tracker::*;
 pub mod task;
 pub use task::*;
+pub mod task_revision;
+pub use task_revision::*;
+pub mod bulk;
+pub use bulk::*;
+pub mod trash;
+pub use trash::*;
 pub mod registration_req;
 pub use registration_req::*;
 pub mod user;
 pub use user::*;
+pub mod user_settings;
+pub use user_settings::*;
 pub mod confirmation_code;
 pub use confirmation_code::*;
 pub mod session;
@@ -20,3 +28,15 @@ pub mod client_credentials;
 pub use client_credentials::*;
 pub mod view;
 pub use view::*;
+pub mod mfa;
+pub use mfa::*;
+pub mod magic_link;
+pub use magic_link::*;
+pub mod password_reset;
+pub use password_reset::*;
+pub mod personal_access_token;
+pub use personal_access_token::*;
+pub mod email_change;
+pub use email_change::*;
+pub mod audit_event;
+pub use audit_event::*;
@@ -0,0 +1,33 @@
+/// Input starting the password reset process. If an account with the given
+/// email exists a confirmation code will be sent to that address.
+#[derive(Debug, serde::Deserialize, Clone, schemars::JsonSchema)]
+pub struct PasswordResetInit {
+    pub email: crate::types::Email,
+}
+
+/// The request is issued when a user who forgot their password asks for a
+/// reset. The confirmation code sent to the email address of the account
+/// together with a new password completes the process. The code can be used
+/// only once and all the existing sessions of the user are ended after the
+/// password gets changed.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct PasswordResetRequest {
+    /// The timestamp of the request.
+    pub issued_at: chrono::DateTime<chrono::offset::Utc>,
+    /// How long can the request be confirmed with the confirmation code until
+    /// it becomes invalid.
+    pub valid_until: chrono::DateTime<chrono::offset::Utc>,
+    /// The email of the account which password is being reset.
+    pub user_email: crate::types::Email,
+    /// Has the request been confirmed with the confirmation code and the
+    /// password changed?
+    pub confirmed_with_code: bool,
+}
+
+/// The code sent to the email address along with the password that should
+/// replace the forgotten one.
+#[derive(serde::Deserialize, Clone, schemars::JsonSchema)]
+pub struct PasswordResetConfirmation {
+    pub code: crate::core::ConfirmationCode,
+    pub new_password: crate::types::PasswordInput,
+}
@@ -0,0 +1,51 @@
+/// Input creating a personal access token. The token can be used as a bearer
+/// token in place of the access tokens obtained by logging in.
+#[derive(Debug, serde::Deserialize, Clone, schemars::JsonSchema)]
+pub struct PersonalAccessTokenRequest {
+    /// Name helping to recognise the token later on
+    pub name: String,
+    /// Space separated list of the scopes given to the token, full access to
+    /// the resources of the user if not specified
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// When the token stops working
+    pub expires_at: chrono::DateTime<chrono::offset::Utc>,
+}
+
+/// A long-lived token created by the user for their own scripts and tools.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct PersonalAccessToken {
+    pub token_id: crate::types::Uuid,
+    pub name: String,
+    /// Space separated list of the scopes given to the token.
+    pub scope: String,
+    pub created_at: chrono::DateTime<chrono::offset::Utc>,
+    pub expires_at: chrono::DateTime<chrono::offset::Utc>,
+    /// When the token has been used for the last time, empty if it has never
+    /// been used.
+    pub last_used_at: Option<chrono::DateTime<chrono::offset::Utc>>,
+}
+
+/// Just like [`PersonalAccessToken`] but with the token included. It is shown
+/// only once, when the token is created.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct NewPersonalAccessToken {
+    /// The value to be put in the `Authorization` header as a bearer token
+    pub token: String,
+    #[serde(flatten)]
+    pub details: PersonalAccessToken,
+}
+
+#[cfg(feature = "diesel")]
+impl From<crate::db::PersonalAccessToken> for PersonalAccessToken {
+    fn from(value: crate::db::PersonalAccessToken) -> Self {
+        Self {
+            token_id: value.token_id,
+            name: value.name,
+            scope: value.scope,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            last_used_at: value.last_used_at,
+        }
+    }
+}
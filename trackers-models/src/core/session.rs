@@ -1,10 +1,33 @@
+/// A session started by logging in either with the password or with the
+/// credentials of an authorised client. Ending the session revokes its tokens.
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, schemars::JsonSchema)]
-#[cfg_attr(feature = "diesel", derive(diesel::Queryable, diesel::Insertable))]
-#[cfg_attr(feature="diesel", diesel(table_name = crate::db::schema::sessions))]
 pub struct Session {
-    pub user_id: crate::types::Uuid,
-    pub access_token: String,
-    pub refresh_token: Option<String>,
+    pub session_id: crate::types::Uuid,
     pub started_at: chrono::DateTime<chrono::offset::Utc>,
+    /// Until when the session can be used or refreshed.
     pub valid_until: chrono::DateTime<chrono::offset::Utc>,
+    /// Name of the authorised client which started the session. Empty for
+    /// the sessions started with the password.
+    pub client_name: Option<String>,
+    /// The `User-Agent` header sent when the session was started.
+    pub user_agent: Option<String>,
+    /// Space separated list of the scopes granted to the session.
+    pub scope: String,
+    /// Is it the session used to make the request?
+    pub current: bool,
+}
+
+#[cfg(feature = "diesel")]
+impl Session {
+    pub fn from_db(value: crate::db::Session, current_access_token: &str) -> Self {
+        Self {
+            current: value.access_token == current_access_token,
+            session_id: value.session_id,
+            started_at: value.started_at,
+            valid_until: value.valid_until,
+            client_name: value.client_name,
+            user_agent: value.user_agent,
+            scope: value.scope,
+        }
+    }
 }
@@ -32,6 +32,10 @@ pub struct Task {
     pub tags: Option<crate::types::Tags>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub list: Option<crate::types::ListItems>,
+    /// When the task has been archived. The archived tasks are left out of the
+    /// listings unless asked for.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub archived_at: Option<chrono::DateTime<chrono::offset::Utc>>,
 }
 
 #[cfg(feature = "diesel")]
@@ -49,6 +53,7 @@ impl From<crate::db::Task> for Task {
             hard_deadline: t.hard_deadline,
             tags: t.tags,
             list: t.list,
+            archived_at: t.archived_at,
         }
     }
 }
@@ -79,6 +84,13 @@ pub struct TaskInput {
     pub list: Option<crate::types::ListItems>,
 }
 
+/// The tracker to which a task is moved.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone)]
+pub struct TaskMove {
+    /// The ID of the destination tracker
+    pub tracker_id: crate::types::Uuid,
+}
+
 #[derive(serde::Deserialize, Debug, Clone)]
 pub struct TaskPatch {
     #[serde(default)]
@@ -0,0 +1,116 @@
+/// One of the revisions of a task together with what has changed in it since
+/// the previous revision.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct TaskRevision {
+    /// Number of the revision, the first one is the task as it was created
+    pub revision: i32,
+    pub revised_at: chrono::DateTime<chrono::offset::Utc>,
+    pub changes: Vec<FieldChange>,
+}
+
+/// A change of a single field of a task. The changes of the list are reported
+/// for each item separately with the fields named like `list[1]`, using the
+/// same numbering of the items as the list endpoints.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct FieldChange {
+    pub field: String,
+    /// The value before the change, missing if the field was empty
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<serde_json::Value>,
+    /// The value after the change, missing if the field has been cleared
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<serde_json::Value>,
+}
+
+#[cfg(feature = "diesel")]
+impl TaskRevision {
+    /// Compares the revision with the one preceding it. Every field which is
+    /// not empty counts as changed in the first revision.
+    pub fn from_db(
+        revision: &crate::db::TaskRevision,
+        previous: Option<&crate::db::TaskRevision>,
+    ) -> Self {
+        let mut changes = Vec::new();
+        let mut compare =
+            |field: &str, from: Option<serde_json::Value>, to: Option<serde_json::Value>| {
+                let from = from.filter(|value| !value.is_null());
+                let to = to.filter(|value| !value.is_null());
+                if from != to {
+                    changes.push(FieldChange {
+                        field: field.to_owned(),
+                        from,
+                        to,
+                    });
+                }
+            };
+        fn value_of<T: serde::Serialize>(value: &T) -> Option<serde_json::Value> {
+            serde_json::to_value(value).ok()
+        }
+
+        compare(
+            "tracker_id",
+            previous.and_then(|prev| value_of(&prev.tracker_id)),
+            value_of(&revision.tracker_id),
+        );
+        compare(
+            "checkmarked_at",
+            previous.and_then(|prev| value_of(&prev.completed_at)),
+            value_of(&revision.completed_at),
+        );
+        compare(
+            "title",
+            previous.and_then(|prev| value_of(&prev.title)),
+            value_of(&revision.title),
+        );
+        compare(
+            "description",
+            previous.and_then(|prev| value_of(&prev.description)),
+            value_of(&revision.description),
+        );
+        compare(
+            "time_estimate",
+            previous.and_then(|prev| value_of(&prev.time_estimate)),
+            value_of(&revision.time_estimate),
+        );
+        compare(
+            "soft_deadline",
+            previous.and_then(|prev| value_of(&prev.soft_deadline)),
+            value_of(&revision.soft_deadline),
+        );
+        compare(
+            "hard_deadline",
+            previous.and_then(|prev| value_of(&prev.hard_deadline)),
+            value_of(&revision.hard_deadline),
+        );
+        compare(
+            "tags",
+            previous.and_then(|prev| value_of(&prev.tags)),
+            value_of(&revision.tags),
+        );
+
+        // the items are compared one by one so that a single checkmark does
+        // not show up as a change of the whole list
+        let previous_items = previous
+            .and_then(|prev| prev.list.as_ref())
+            .map(|list| list.0.as_slice())
+            .unwrap_or_default();
+        let items = revision
+            .list
+            .as_ref()
+            .map(|list| list.0.as_slice())
+            .unwrap_or_default();
+        for idx in 0..previous_items.len().max(items.len()) {
+            compare(
+                &format!("list[{}]", idx + 1),
+                previous_items.get(idx).and_then(value_of),
+                items.get(idx).and_then(value_of),
+            );
+        }
+
+        Self {
+            revision: revision.revision,
+            revised_at: revision.revised_at,
+            changes,
+        }
+    }
+}
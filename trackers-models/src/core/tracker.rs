@@ -22,6 +22,13 @@ pub struct Tracker {
     /// assigned to that default tracker.
     #[serde(default, skip_deserializing)]
     pub is_default: crate::types::NullOrTrue,
+    /// When the tracker has been moved to the trash, empty if it has not
+    #[serde(skip)]
+    pub deleted_at: Option<chrono::DateTime<chrono::offset::Utc>>,
+    /// When the tracker has been archived. The archived trackers and their
+    /// tasks are left out of the listings unless asked for.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<chrono::DateTime<chrono::offset::Utc>>,
 }
 
 /// The default tracker that should be created as the first tracker for every new user.
@@ -32,6 +39,8 @@ impl Default for Tracker {
             user_id: crate::types::Uuid::new(),
             name: "Default Task Tracker".to_owned().try_into().unwrap(),
             is_default: true.into(),
+            deleted_at: None,
+            archived_at: None,
         }
     }
 }
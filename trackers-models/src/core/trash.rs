@@ -0,0 +1,19 @@
+/// The trackers and tasks which have been deleted but can still be restored.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct Trash {
+    /// The trackers in the trash. Their tasks are restored along with them.
+    pub trackers: Vec<Trashed<crate::core::Tracker>>,
+    /// The tasks deleted one by one
+    pub tasks: Vec<Trashed<crate::core::Task>>,
+}
+
+/// An item in the trash.
+#[derive(Debug, serde::Serialize, Clone, schemars::JsonSchema)]
+pub struct Trashed<T> {
+    #[serde(flatten)]
+    pub item: T,
+    /// When the item has been moved to the trash
+    pub deleted_at: chrono::DateTime<chrono::offset::Utc>,
+    /// When the item is going to be deleted for good
+    pub purged_at: chrono::DateTime<chrono::offset::Utc>,
+}
@@ -5,3 +5,12 @@ pub struct UserCreation {
     /// Explicit acceptance of the Terms of Service is required to create an account
     pub accepted_tos: bool,
 }
+
+/// Confirmation required to permanently delete an account. Along with the
+/// account all the trackers, tasks, views, sessions and authorised clients
+/// owned by the user are removed.
+#[derive(Debug, serde::Deserialize, Clone, schemars::JsonSchema)]
+pub struct AccountDeletion {
+    /// The current password of the account
+    pub password: crate::types::PasswordInput,
+}
@@ -0,0 +1,20 @@
+/// Preferences of a user changing how their tasks are treated.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, Clone, schemars::JsonSchema)]
+pub struct UserSettings {
+    /// Number of days after which the checkmarked tasks are archived
+    /// automatically. The tasks are never archived automatically if empty.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub auto_archive_after_days: Option<std::num::NonZeroU16>,
+}
+
+#[cfg(feature = "diesel")]
+impl From<crate::db::UserSettings> for UserSettings {
+    fn from(settings: crate::db::UserSettings) -> Self {
+        Self {
+            auto_archive_after_days: settings
+                .auto_archive_after_days
+                .and_then(|days| u16::try_from(days).ok())
+                .and_then(std::num::NonZeroU16::new),
+        }
+    }
+}
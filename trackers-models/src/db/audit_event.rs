@@ -0,0 +1,18 @@
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::audit_events)]
+pub struct AuditEvent {
+    pub event_id: crate::types::Uuid,
+    /// The owner of the account the event concerns
+    pub user_id: crate::types::Uuid,
+    pub action: crate::types::AuditAction,
+    /// Path of the affected resource
+    pub resource: Option<String>,
+    /// Name of the client application or of the personal access token which
+    /// made the request
+    pub client: Option<String>,
+    pub ip_address: Option<String>,
+    pub details: Option<String>,
+    pub occurred_at: chrono::DateTime<chrono::offset::Utc>,
+}
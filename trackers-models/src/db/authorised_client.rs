@@ -0,0 +1,57 @@
+#[derive(
+    Debug,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    diesel::Queryable,
+    diesel::Insertable,
+    diesel::AsChangeset,
+)]
+#[diesel(table_name = crate::db::schema::authorised_clients)]
+pub struct AuthorisedClient {
+    pub user_id: crate::types::Uuid,
+    pub name: String,
+    pub website: String,
+    pub client_id: crate::types::ClientSecretStr,
+    pub redirect_uris: Vec<String>,
+    /// Space separated names of the scopes the client can be given
+    pub scope: String,
+    /// The secret hashed with bcrypt, it is shown only once when it is
+    /// generated
+    pub client_secret_hash: Vec<u8>,
+    /// The hash of the secret replaced during the last rotation
+    pub previous_secret_hash: Option<Vec<u8>>,
+    /// Until when the previous secret is accepted along with the current one
+    pub previous_secret_valid_until: Option<chrono::DateTime<chrono::offset::Utc>>,
+}
+
+impl AuthorisedClient {
+    /// Prepares a newly authorised client to be stored, hashing its secret.
+    pub fn new(client: &crate::core::AuthorisedClientFull) -> Self {
+        Self {
+            user_id: client.user_id.clone(),
+            name: client.name.clone(),
+            website: client.website.clone(),
+            client_id: client.client_id.clone(),
+            redirect_uris: client.redirect_uris.clone(),
+            scope: client.scope.clone(),
+            client_secret_hash: client.client_secret.to_storeable(),
+            previous_secret_hash: None,
+            previous_secret_valid_until: None,
+        }
+    }
+
+    /// Checks the secret against the current one and, during the overlap
+    /// period after a rotation, against the previous one.
+    pub fn secret_matches(&self, secret: &crate::types::ClientSecretStr) -> bool {
+        if secret.match_with(self.client_secret_hash.clone()) {
+            return true;
+        }
+        match (&self.previous_secret_hash, self.previous_secret_valid_until) {
+            (Some(previous), Some(valid_until)) if valid_until > chrono::Utc::now() => {
+                secret.match_with(previous.clone())
+            }
+            _ => false,
+        }
+    }
+}
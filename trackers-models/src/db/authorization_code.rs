@@ -0,0 +1,21 @@
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::authorization_codes)]
+pub struct AuthorizationCode {
+    /// SHA-256 hash of the code
+    pub code_hash: Vec<u8>,
+    pub client_id: crate::types::ClientSecretStr,
+    /// The user who has given the consent
+    pub user_id: crate::types::Uuid,
+    /// Must be repeated when the code is exchanged for the tokens
+    pub redirect_uri: String,
+    /// Space separated names of the scopes the user has agreed to
+    pub scope: String,
+    /// PKCE challenge computed with the S256 method
+    pub code_challenge: String,
+    pub expires_at: chrono::DateTime<chrono::offset::Utc>,
+    /// Given by the client asking for the `openid` scope, put into the ID
+    /// token unchanged
+    pub nonce: Option<String>,
+}
@@ -0,0 +1,21 @@
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::device_authorizations)]
+pub struct DeviceAuthorization {
+    /// SHA-256 hash of the code polled by the device
+    pub device_code_hash: Vec<u8>,
+    /// The code entered by the user, stored without the separator
+    pub user_code: String,
+    /// The client which has started the authorization, if it is registered
+    pub client_id: Option<crate::types::ClientSecretStr>,
+    /// Space separated names of the requested scopes
+    pub scope: String,
+    /// The user who has approved the authorization
+    pub user_id: Option<crate::types::Uuid>,
+    pub denied: bool,
+    /// Minimal number of seconds between the polls of the device
+    pub poll_interval: i32,
+    pub last_polled_at: Option<chrono::DateTime<chrono::offset::Utc>>,
+    pub expires_at: chrono::DateTime<chrono::offset::Utc>,
+}
@@ -0,0 +1,26 @@
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::email_change_requests)]
+pub struct EmailChangeRequest {
+    pub user_id: crate::types::Uuid,
+    pub new_email: crate::types::Email,
+    pub issued_at: chrono::DateTime<chrono::offset::Utc>,
+    pub valid_until: chrono::DateTime<chrono::offset::Utc>,
+    /// The confirmation code hashed with bcrypt
+    pub code_hash: Vec<u8>,
+    /// Number of the incorrect codes given so far
+    pub failed_attempts: i32,
+}
+
+/// Remembers the previous email address of an account so that the paths
+/// using it can be redirected for some time after the address has changed.
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::email_redirects)]
+pub struct EmailRedirect {
+    pub old_email: crate::types::Email,
+    pub user_id: crate::types::Uuid,
+    pub valid_until: chrono::DateTime<chrono::offset::Utc>,
+}
@@ -0,0 +1,15 @@
+/// A record of an incorrect email and password pair given when logging in.
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::failed_logins)]
+pub struct FailedLogin {
+    pub attempt_id: crate::types::Uuid,
+    /// The email address given, whether an account uses it or not
+    pub email: crate::types::Email,
+    /// The account using the email address, if there is one
+    pub user_id: Option<crate::types::Uuid>,
+    pub ip_address: String,
+    pub user_agent: Option<String>,
+    pub attempted_at: chrono::DateTime<chrono::offset::Utc>,
+}
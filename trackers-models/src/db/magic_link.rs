@@ -0,0 +1,11 @@
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::magic_links)]
+pub struct MagicLink {
+    pub user_id: crate::types::Uuid,
+    /// SHA-256 hash of the code sent by mail, the code can be used once
+    pub code_hash: Vec<u8>,
+    pub issued_at: chrono::DateTime<chrono::offset::Utc>,
+    pub valid_until: chrono::DateTime<chrono::offset::Utc>,
+}
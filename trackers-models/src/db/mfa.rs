@@ -0,0 +1,38 @@
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::totp_credentials)]
+pub struct TotpCredential {
+    pub user_id: crate::types::Uuid,
+    /// The secret shared with the authenticator app of the user
+    pub secret: Vec<u8>,
+    /// Empty until the user proves their app generates the right codes, the
+    /// codes are not required to log in before that
+    pub confirmed_at: Option<chrono::DateTime<chrono::offset::Utc>>,
+    /// The time step of the last accepted code which cannot be used again
+    pub last_used_step: Option<i64>,
+}
+
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::recovery_codes)]
+pub struct RecoveryCode {
+    /// SHA-256 hash of the code, each code can be used once
+    pub code_hash: Vec<u8>,
+    pub user_id: crate::types::Uuid,
+}
+
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::mfa_challenges)]
+pub struct MfaChallenge {
+    /// SHA-256 hash of the `mfa_token` given to the client
+    pub challenge_hash: Vec<u8>,
+    /// The user who has given the correct password
+    pub user_id: crate::types::Uuid,
+    /// Number of the incorrect codes given so far
+    pub attempts: i32,
+    pub expires_at: chrono::DateTime<chrono::offset::Utc>,
+}
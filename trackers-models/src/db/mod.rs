@@ -7,11 +7,37 @@
 //! to serialize data in the PostgreSQL database.
 
 pub mod schema;
+pub mod audit_event;
+pub use audit_event::*;
+pub mod authorised_client;
+pub use authorised_client::*;
+pub mod authorization_code;
+pub use authorization_code::*;
+pub mod device_authorization;
+pub use device_authorization::*;
 pub mod task;
 pub use task::*;
+pub mod task_revision;
+pub use task_revision::*;
 pub mod user;
 pub use user::*;
+pub mod user_settings;
+pub use user_settings::*;
 pub mod registration_req;
 pub use registration_req::*;
+pub mod session;
+pub use session::*;
 pub mod view;
 pub use view::*;
+pub mod mfa;
+pub use mfa::*;
+pub mod password_reset;
+pub use password_reset::*;
+pub mod personal_access_token;
+pub use personal_access_token::*;
+pub mod email_change;
+pub use email_change::*;
+pub mod failed_login;
+pub use failed_login::*;
+pub mod magic_link;
+pub use magic_link::*;
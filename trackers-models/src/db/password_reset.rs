@@ -0,0 +1,13 @@
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::password_reset_requests)]
+pub struct PasswordResetRequest {
+    pub user_id: crate::types::Uuid,
+    pub issued_at: chrono::DateTime<chrono::offset::Utc>,
+    pub valid_until: chrono::DateTime<chrono::offset::Utc>,
+    /// The confirmation code hashed with bcrypt
+    pub code_hash: Vec<u8>,
+    /// Number of the incorrect codes given so far
+    pub failed_attempts: i32,
+}
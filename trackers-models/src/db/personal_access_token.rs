@@ -0,0 +1,16 @@
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::personal_access_tokens)]
+pub struct PersonalAccessToken {
+    pub token_id: crate::types::Uuid,
+    pub user_id: crate::types::Uuid,
+    pub name: String,
+    /// SHA-256 hash of the token, the token itself is shown only once
+    pub token_hash: Vec<u8>,
+    /// Space separated names of the scopes granted to the token
+    pub scope: String,
+    pub created_at: chrono::DateTime<chrono::offset::Utc>,
+    pub expires_at: chrono::DateTime<chrono::offset::Utc>,
+    pub last_used_at: Option<chrono::DateTime<chrono::offset::Utc>>,
+}
@@ -8,4 +8,8 @@ pub struct RegistrationRequest {
     pub valid_until: chrono::DateTime<chrono::offset::Utc>,
     pub confirmation_code: String,
     pub password: Vec<u8>,
+    pub last_sent_at: chrono::DateTime<chrono::offset::Utc>,
+    /// How many times the code has been sent, including the first mail
+    pub sent_count: i32,
+    pub failed_attempts: i32,
 }
@@ -10,13 +10,135 @@ pub mod sql_types {
     pub struct ViewKvT;
 }
 
+diesel::table! {
+    audit_events (event_id) {
+        event_id -> Uuid,
+        user_id -> Uuid,
+        action -> Varchar,
+        resource -> Nullable<Varchar>,
+        client -> Nullable<Varchar>,
+        ip_address -> Nullable<Varchar>,
+        details -> Nullable<Text>,
+        occurred_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     authorised_clients (client_id) {
         user_id -> Uuid,
         name -> Varchar,
         website -> Varchar,
         client_id -> Varchar,
-        client_secret -> Varchar,
+        redirect_uris -> Array<Text>,
+        scope -> Text,
+        client_secret_hash -> Bytea,
+        previous_secret_hash -> Nullable<Bytea>,
+        previous_secret_valid_until -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    authorization_codes (code_hash) {
+        code_hash -> Bytea,
+        client_id -> Varchar,
+        user_id -> Uuid,
+        redirect_uri -> Text,
+        scope -> Text,
+        code_challenge -> Varchar,
+        expires_at -> Timestamptz,
+        nonce -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    device_authorizations (device_code_hash) {
+        device_code_hash -> Bytea,
+        user_code -> Varchar,
+        client_id -> Nullable<Varchar>,
+        scope -> Text,
+        user_id -> Nullable<Uuid>,
+        denied -> Bool,
+        poll_interval -> Int4,
+        last_polled_at -> Nullable<Timestamptz>,
+        expires_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    email_change_requests (user_id) {
+        user_id -> Uuid,
+        new_email -> Varchar,
+        issued_at -> Timestamptz,
+        valid_until -> Timestamptz,
+        code_hash -> Bytea,
+        failed_attempts -> Int4,
+    }
+}
+
+diesel::table! {
+    email_redirects (old_email) {
+        old_email -> Varchar,
+        user_id -> Uuid,
+        valid_until -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    failed_logins (attempt_id) {
+        attempt_id -> Uuid,
+        email -> Varchar,
+        user_id -> Nullable<Uuid>,
+        ip_address -> Varchar,
+        user_agent -> Nullable<Varchar>,
+        attempted_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    magic_links (user_id) {
+        user_id -> Uuid,
+        code_hash -> Bytea,
+        issued_at -> Timestamptz,
+        valid_until -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    mfa_challenges (challenge_hash) {
+        challenge_hash -> Bytea,
+        user_id -> Uuid,
+        attempts -> Int4,
+        expires_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    password_reset_requests (user_id) {
+        user_id -> Uuid,
+        issued_at -> Timestamptz,
+        valid_until -> Timestamptz,
+        code_hash -> Bytea,
+        failed_attempts -> Int4,
+    }
+}
+
+diesel::table! {
+    personal_access_tokens (token_id) {
+        token_id -> Uuid,
+        user_id -> Uuid,
+        name -> Varchar,
+        token_hash -> Bytea,
+        scope -> Text,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        last_used_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    recovery_codes (code_hash) {
+        code_hash -> Bytea,
+        user_id -> Uuid,
     }
 }
 
@@ -27,6 +149,16 @@ diesel::table! {
         valid_until -> Timestamptz,
         confirmation_code -> Bpchar,
         password -> Bytea,
+        last_sent_at -> Timestamptz,
+        sent_count -> Int4,
+        failed_attempts -> Int4,
+    }
+}
+
+diesel::table! {
+    revoked_tokens (jti) {
+        jti -> Uuid,
+        expires_at -> Timestamptz,
     }
 }
 
@@ -34,9 +166,33 @@ diesel::table! {
     sessions (access_token) {
         user_id -> Uuid,
         access_token -> Varchar,
-        refresh_token -> Nullable<Varchar>,
         started_at -> Timestamptz,
         valid_until -> Timestamptz,
+        session_id -> Uuid,
+        client_name -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+        refresh_token_hash -> Nullable<Bytea>,
+        scope -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ListItemT;
+
+    task_revisions (task_id, revision) {
+        task_id -> Uuid,
+        revision -> Int4,
+        tracker_id -> Uuid,
+        completed_at -> Nullable<Timestamptz>,
+        title -> Varchar,
+        description -> Nullable<Text>,
+        time_estimate -> Nullable<Int8>,
+        soft_deadline -> Nullable<Timestamptz>,
+        hard_deadline -> Nullable<Timestamptz>,
+        tags -> Nullable<Array<Nullable<Text>>>,
+        list -> Nullable<Array<Nullable<ListItemT>>>,
+        revised_at -> Timestamptz,
     }
 }
 
@@ -55,6 +211,17 @@ diesel::table! {
         hard_deadline -> Nullable<Timestamptz>,
         tags -> Nullable<Array<Nullable<Text>>>,
         list -> Nullable<Array<Nullable<ListItemT>>>,
+        deleted_at -> Nullable<Timestamptz>,
+        archived_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    totp_credentials (user_id) {
+        user_id -> Uuid,
+        secret -> Bytea,
+        confirmed_at -> Nullable<Timestamptz>,
+        last_used_step -> Nullable<Int8>,
     }
 }
 
@@ -76,6 +243,23 @@ diesel::table! {
         user_id -> Uuid,
         name -> Varchar,
         is_default -> Nullable<Bool>,
+        deleted_at -> Nullable<Timestamptz>,
+        archived_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    used_refresh_tokens (token_hash) {
+        token_hash -> Bytea,
+        session_id -> Uuid,
+        used_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    user_settings (user_id) {
+        user_id -> Uuid,
+        auto_archive_after_days -> Nullable<Int4>,
     }
 }
 
@@ -95,20 +279,52 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(audit_events -> users (user_id));
 diesel::joinable!(authorised_clients -> users (user_id));
+diesel::joinable!(authorization_codes -> authorised_clients (client_id));
+diesel::joinable!(authorization_codes -> users (user_id));
+diesel::joinable!(device_authorizations -> authorised_clients (client_id));
+diesel::joinable!(device_authorizations -> users (user_id));
+diesel::joinable!(email_change_requests -> users (user_id));
+diesel::joinable!(email_redirects -> users (user_id));
+diesel::joinable!(failed_logins -> users (user_id));
+diesel::joinable!(magic_links -> users (user_id));
+diesel::joinable!(mfa_challenges -> users (user_id));
+diesel::joinable!(password_reset_requests -> users (user_id));
+diesel::joinable!(personal_access_tokens -> users (user_id));
+diesel::joinable!(recovery_codes -> users (user_id));
 diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(task_revisions -> tasks (task_id));
 diesel::joinable!(tasks -> trackers (tracker_id));
+diesel::joinable!(totp_credentials -> users (user_id));
 diesel::joinable!(tracker_views -> trackers (tracker_id));
 diesel::joinable!(trackers -> users (user_id));
+diesel::joinable!(user_settings -> users (user_id));
 diesel::joinable!(views -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    audit_events,
     authorised_clients,
+    authorization_codes,
+    device_authorizations,
+    email_change_requests,
+    email_redirects,
+    failed_logins,
+    magic_links,
+    mfa_challenges,
+    password_reset_requests,
+    personal_access_tokens,
+    recovery_codes,
     registration_requests,
+    revoked_tokens,
     sessions,
+    task_revisions,
     tasks,
+    totp_credentials,
     tracker_views,
     trackers,
+    used_refresh_tokens,
+    user_settings,
     users,
     views,
 );
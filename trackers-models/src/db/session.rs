@@ -0,0 +1,20 @@
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable, diesel::Insertable,
+)]
+#[diesel(table_name = crate::db::schema::sessions)]
+pub struct Session {
+    pub user_id: crate::types::Uuid,
+    pub access_token: String,
+    pub started_at: chrono::DateTime<chrono::offset::Utc>,
+    pub valid_until: chrono::DateTime<chrono::offset::Utc>,
+    pub session_id: crate::types::Uuid,
+    /// Name of the authorised client which started the session
+    pub client_name: Option<String>,
+    pub user_agent: Option<String>,
+    /// SHA-256 hash of the current refresh token, empty for the sessions
+    /// which cannot be refreshed
+    pub refresh_token_hash: Option<Vec<u8>>,
+    /// Space separated names of the scopes granted to the session, the
+    /// refreshed tokens are given the same scopes
+    pub scope: String,
+}
@@ -26,6 +26,10 @@ pub struct Task {
     pub hard_deadline: Option<chrono::DateTime<chrono::offset::Utc>>,
     pub tags: Option<crate::types::Tags>,
     pub list: Option<crate::types::ListItems>,
+    /// When the task has been moved to the trash, empty if it has not
+    pub deleted_at: Option<chrono::DateTime<chrono::offset::Utc>>,
+    /// When the task has been archived, empty if it has not
+    pub archived_at: Option<chrono::DateTime<chrono::offset::Utc>>,
 }
 
 #[derive(
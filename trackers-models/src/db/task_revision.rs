@@ -0,0 +1,19 @@
+/// The state of a task after one of the changes made to it. The revisions are
+/// recorded by the database itself on every insert and update of a task.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, diesel::Queryable)]
+#[diesel(table_name = crate::db::schema::task_revisions)]
+pub struct TaskRevision {
+    pub task_id: crate::types::Uuid,
+    /// Number of the revision, counted from 1 for each task
+    pub revision: i32,
+    pub tracker_id: crate::types::Uuid,
+    pub completed_at: Option<chrono::DateTime<chrono::offset::Utc>>,
+    pub title: crate::types::String<256>,
+    pub description: Option<crate::types::String<4096>>,
+    pub time_estimate: Option<crate::types::Duration>,
+    pub soft_deadline: Option<chrono::DateTime<chrono::offset::Utc>>,
+    pub hard_deadline: Option<chrono::DateTime<chrono::offset::Utc>>,
+    pub tags: Option<crate::types::Tags>,
+    pub list: Option<crate::types::ListItems>,
+    pub revised_at: chrono::DateTime<chrono::offset::Utc>,
+}
@@ -0,0 +1,6 @@
+#[derive(Debug, Clone, diesel::Queryable, diesel::Insertable, diesel::AsChangeset)]
+#[diesel(table_name = crate::db::schema::user_settings, treat_none_as_null = true)]
+pub struct UserSettings {
+    pub user_id: crate::types::Uuid,
+    pub auto_archive_after_days: Option<i32>,
+}
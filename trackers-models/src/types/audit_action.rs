@@ -0,0 +1,147 @@
+/// What has happened in an event recorded in the audit log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(
+    feature = "diesel",
+    derive(diesel::deserialize::FromSqlRow, diesel::expression::AsExpression)
+)]
+#[cfg_attr(feature="diesel", diesel(sql_type=diesel::sql_types::VarChar))]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    LoginSucceeded,
+    LoginFailed,
+    TokenIssued,
+    TokenRevoked,
+    SessionEnded,
+    ClientAuthorised,
+    ClientSecretRotated,
+    ClientUnauthorised,
+    TaskCreated,
+    TaskUpdated,
+    TaskDeleted,
+    TaskRestored,
+    TrackerCreated,
+    TrackerUpdated,
+    TrackerDeleted,
+    TrackerRestored,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 16] = [
+        AuditAction::LoginSucceeded,
+        AuditAction::LoginFailed,
+        AuditAction::TokenIssued,
+        AuditAction::TokenRevoked,
+        AuditAction::SessionEnded,
+        AuditAction::ClientAuthorised,
+        AuditAction::ClientSecretRotated,
+        AuditAction::ClientUnauthorised,
+        AuditAction::TaskCreated,
+        AuditAction::TaskUpdated,
+        AuditAction::TaskDeleted,
+        AuditAction::TaskRestored,
+        AuditAction::TrackerCreated,
+        AuditAction::TrackerUpdated,
+        AuditAction::TrackerDeleted,
+        AuditAction::TrackerRestored,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::LoginSucceeded => "login_succeeded",
+            AuditAction::LoginFailed => "login_failed",
+            AuditAction::TokenIssued => "token_issued",
+            AuditAction::TokenRevoked => "token_revoked",
+            AuditAction::SessionEnded => "session_ended",
+            AuditAction::ClientAuthorised => "client_authorised",
+            AuditAction::ClientSecretRotated => "client_secret_rotated",
+            AuditAction::ClientUnauthorised => "client_unauthorised",
+            AuditAction::TaskCreated => "task_created",
+            AuditAction::TaskUpdated => "task_updated",
+            AuditAction::TaskDeleted => "task_deleted",
+            AuditAction::TaskRestored => "task_restored",
+            AuditAction::TrackerCreated => "tracker_created",
+            AuditAction::TrackerUpdated => "tracker_updated",
+            AuditAction::TrackerDeleted => "tracker_deleted",
+            AuditAction::TrackerRestored => "tracker_restored",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownAuditAction;
+
+impl std::fmt::Display for UnknownAuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown audit action")
+    }
+}
+
+impl std::error::Error for UnknownAuditAction {}
+
+impl std::str::FromStr for AuditAction {
+    type Err = UnknownAuditAction;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AuditAction::ALL
+            .into_iter()
+            .find(|action| action.as_str() == s)
+            .ok_or(UnknownAuditAction)
+    }
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl schemars::JsonSchema for AuditAction {
+    fn schema_name() -> std::string::String {
+        "audit action".to_owned()
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        schemars::schema::Schema::Object(schemars::schema::SchemaObject {
+            metadata: Some(Box::new(schemars::schema::Metadata {
+                title: Some(std::string::String::from("audit action")),
+                description: Some(std::string::String::from(
+                    "What has happened in an event recorded in the audit log.",
+                )),
+                ..schemars::schema::Metadata::default()
+            })),
+            instance_type: Some(schemars::schema::SingleOrVec::Single(Box::new(
+                schemars::schema::InstanceType::String,
+            ))),
+            enum_values: Some(
+                AuditAction::ALL
+                    .iter()
+                    .map(|action| action.as_str().into())
+                    .collect(),
+            ),
+            ..schemars::schema::SchemaObject::default()
+        })
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl diesel::serialize::ToSql<diesel::sql_types::VarChar, diesel::pg::Pg> for AuditAction {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
+    ) -> diesel::serialize::Result {
+        <str as diesel::serialize::ToSql<diesel::sql_types::VarChar, _>>::to_sql(self.as_str(), out)
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl diesel::deserialize::FromSql<diesel::sql_types::VarChar, diesel::pg::Pg> for AuditAction {
+    fn from_sql(
+        bytes: diesel::backend::RawValue<'_, diesel::pg::Pg>,
+    ) -> diesel::deserialize::Result<Self> {
+        let v = <std::string::String as diesel::deserialize::FromSql<
+            diesel::sql_types::VarChar,
+            _,
+        >>::from_sql(bytes)?;
+        Ok(v.parse()?)
+    }
+}
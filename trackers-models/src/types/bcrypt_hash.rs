@@ -0,0 +1,22 @@
+//! Hashing of the generated secrets and codes which are stored only to be
+//! checked later, so that they are not revealed to anyone with access to the
+//! database.
+
+/// Hashes the secret with bcrypt.
+///
+/// # Panics
+/// Expects the `secret` to be hashable with bcrypt.
+pub fn hash(secret: &str) -> Vec<u8> {
+    let hashed: String =
+        bcrypt::hash(secret, bcrypt::DEFAULT_COST).expect("failed to hash the secret with bcrypt");
+    hashed.into_bytes()
+}
+
+/// Checks the secret against a hash produced with [`hash`]. Malformed hashes
+/// never match.
+pub fn verify(secret: &str, stored: Vec<u8>) -> bool {
+    let Ok(stored) = String::from_utf8(stored) else {
+        return false;
+    };
+    bcrypt::verify(secret, &stored).unwrap_or(false)
+}
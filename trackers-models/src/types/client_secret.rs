@@ -26,6 +26,16 @@ impl ClientSecretStr {
 
         Self(str_buf)
     }
+    /// Hashes the secret so that it can be stored without revealing it to
+    /// anyone with access to the database.
+    pub fn to_storeable(&self) -> Vec<u8> {
+        super::bcrypt_hash::hash(&self.0)
+    }
+    /// Checks the secret against a hash produced with
+    /// [`to_storeable`](Self::to_storeable). Malformed hashes never match.
+    pub fn match_with(&self, stored: Vec<u8>) -> bool {
+        super::bcrypt_hash::verify(&self.0, stored)
+    }
 }
 
 /// We want to have just `Into<String>` and no `From<String>` to avoid creating
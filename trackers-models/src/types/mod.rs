@@ -24,7 +24,10 @@ pub mod null_or_true;
 pub use null_or_true::NullOrTrue;
 pub mod list_items;
 pub use list_items::{ListItem, ListItems};
+pub mod bcrypt_hash;
 pub mod client_secret;
 pub use client_secret::ClientSecretStr;
 pub mod view_key_value;
 pub use view_key_value::ViewKV;
+pub mod audit_action;
+pub use audit_action::AuditAction;